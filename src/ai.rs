@@ -0,0 +1,342 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Collider, Health},
+    obstacles::{MovingObstacle, Obstacle},
+    player::Player,
+    pool::Pooled,
+    simulation::{GameRng, SimulationApp, SimulationTime},
+    state::GameState,
+};
+
+/// Distance ahead of the mover the wander circle sits at, its radius, and
+/// how much its target point can drift each tick.
+const WANDER_DISTANCE: f32 = 80.;
+const WANDER_RADIUS: f32 = 40.;
+const WANDER_JITTER: f32 = 0.3;
+/// Seconds of travel at full speed looked ahead for obstacles.
+const LOOKAHEAD: f32 = 0.6;
+/// How far a patrol may stray from home before heading back.
+const PATROL_RADIUS: f32 = 300.;
+
+/// Movement of an autonomous entity, driven by steering forces.
+#[derive(Component, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Steering {
+    pub velocity: Vec2,
+    pub max_speed: f32,
+    /// Largest acceleration, in units per second squared.
+    pub max_force: f32,
+    /// Where on the wander circle the mover is currently heading.
+    pub wander_angle: f32,
+    /// Last force applied, kept for the debug gizmos.
+    #[serde(skip)]
+    pub force: Vec2,
+}
+
+impl Steering {
+    pub fn new(max_speed: f32, max_force: f32) -> Self {
+        Self {
+            max_speed,
+            max_force,
+            ..Default::default()
+        }
+    }
+
+    /// Heads straight for `target` at full speed.
+    pub fn seek(&self, position: Vec2, target: Vec2) -> Vec2 {
+        (target - position).normalize_or_zero() * self.max_speed - self.velocity
+    }
+
+    /// Heads straight away from `threat` at full speed.
+    pub fn flee(&self, position: Vec2, threat: Vec2) -> Vec2 {
+        (position - threat).normalize_or_zero() * self.max_speed - self.velocity
+    }
+
+    /// Like `seek`, slowing down within `slowing_radius` to stop on
+    /// `target`.
+    pub fn arrive(
+        &self,
+        position: Vec2,
+        target: Vec2,
+        slowing_radius: f32,
+    ) -> Vec2 {
+        let offset = target - position;
+        let distance = offset.length();
+        let speed = self.max_speed * (distance / slowing_radius).min(1.);
+
+        offset.normalize_or_zero() * speed - self.velocity
+    }
+
+    /// Meanders by aiming at a point drifting around a circle in front of
+    /// the mover.
+    pub fn wander(&mut self, rng: &mut GameRng) -> Vec2 {
+        self.wander_angle += rng.gen_range(-WANDER_JITTER..=WANDER_JITTER);
+
+        let heading = self.velocity.try_normalize().unwrap_or(Vec2::X);
+        let target = heading * WANDER_DISTANCE
+            + Vec2::from_angle(self.wander_angle) * WANDER_RADIUS;
+
+        target.normalize_or_zero() * self.max_speed - self.velocity
+    }
+
+    /// Pushes away from the nearest of `obstacles` lying on the path ahead.
+    pub fn avoid<'a>(
+        &self,
+        position: Vec2,
+        collider: &Collider,
+        obstacles: impl Iterator<Item = (Vec2, &'a Collider)>,
+    ) -> Vec2 {
+        let ahead = self.velocity * LOOKAHEAD;
+        let size = Vec2::from(*collider);
+
+        let probes = [position + ahead * 0.5, position + ahead];
+
+        obstacles
+            .filter(|(center, obstacle)| {
+                probes.iter().any(|probe| {
+                    collide(
+                        probe.extend(0.),
+                        size,
+                        center.extend(0.),
+                        Vec2::from(**obstacle),
+                    )
+                    .is_some()
+                })
+            })
+            .map(|(center, _)| center)
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+            .map_or(Vec2::ZERO, |center| {
+                (position + ahead - center).normalize_or_zero() * self.max_force
+            })
+    }
+
+    /// Applies `force` for `dt` seconds, returning how far the mover
+    /// travels.
+    pub fn integrate(&mut self, force: Vec2, dt: f32) -> Vec2 {
+        self.force = force.clamp_length_max(self.max_force);
+        self.velocity =
+            (self.velocity + self.force * dt).clamp_length_max(self.max_speed);
+
+        self.velocity * dt
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AiState {
+    /// Wanders around home until a player comes into sight.
+    Patrol,
+    Chase,
+    /// Keeps at a distance from its target, firing at it.
+    Attack,
+    /// Gets away from its target while badly hurt.
+    Retreat,
+}
+
+/// Distances and thresholds that move a `Brain` from one state to the next.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Temperament {
+    pub sight: f32,
+    pub attack_range: f32,
+    /// Fraction of health under which the entity retreats.
+    pub cowardice: f32,
+}
+
+/// What an entity knows about the world when deciding what to do.
+pub struct Perception {
+    /// Where the nearest player is, if any is left.
+    pub target: Option<Vec2>,
+    pub distance: f32,
+    pub health: f32,
+}
+
+impl AiState {
+    /// The state after this one given what the entity perceives. Leaving a
+    /// state takes a little more than entering it, so entities don't
+    /// flicker between two of them on the boundary.
+    pub fn next(
+        self,
+        perception: &Perception,
+        temperament: &Temperament,
+    ) -> Self {
+        let distance = match perception.target {
+            Some(_) => perception.distance,
+            None => return AiState::Patrol,
+        };
+
+        let hurt = perception.health < temperament.cowardice;
+        let in_sight = distance < temperament.sight;
+
+        match self {
+            AiState::Retreat if distance > temperament.sight * 1.5 => {
+                AiState::Patrol
+            }
+            AiState::Retreat => AiState::Retreat,
+            _ if hurt && in_sight => AiState::Retreat,
+
+            AiState::Patrol if in_sight => AiState::Chase,
+            AiState::Patrol => AiState::Patrol,
+
+            AiState::Chase if distance < temperament.attack_range => {
+                AiState::Attack
+            }
+            AiState::Chase if distance > temperament.sight * 1.2 => {
+                AiState::Patrol
+            }
+            AiState::Chase => AiState::Chase,
+
+            AiState::Attack if distance > temperament.attack_range * 1.2 => {
+                AiState::Chase
+            }
+            AiState::Attack => AiState::Attack,
+        }
+    }
+}
+
+/// Decides what an AI-driven entity is up to.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Brain {
+    pub state: AiState,
+    pub temperament: Temperament,
+    /// Where patrols come back to.
+    pub home: Vec2,
+    /// Position of the player being chased, attacked or fled from.
+    pub target: Option<Vec2>,
+}
+
+impl Brain {
+    pub fn new(temperament: Temperament, home: Vec2) -> Self {
+        Self {
+            state: AiState::Patrol,
+            temperament,
+            home,
+            target: None,
+        }
+    }
+}
+
+type ThinkQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static Health, &'static mut Brain),
+    Without<Player>,
+>;
+
+type SteerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static Collider,
+        &'static Brain,
+        &'static mut Steering,
+    ),
+    Without<Obstacle>,
+>;
+
+type AvoidQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static Collider),
+    (With<Obstacle>, Without<MovingObstacle>, Without<Pooled>),
+>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum AiSystem {
+    /// Brains have picked their state and moved for the tick.
+    Steer,
+}
+
+/// Steering behaviours and a patrol, chase, attack and retreat state machine
+/// for entities with a `Brain` and `Steering`.
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_simulation_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::think)
+                .with_system(
+                    Self::steer.label(AiSystem::Steer).after(Self::think),
+                ),
+        );
+    }
+}
+
+impl AiPlugin {
+    fn think(
+        mut brain_query: ThinkQuery,
+        player_query: Query<(&Transform, &Health), With<Player>>,
+    ) {
+        for (transform, health, mut brain) in brain_query.iter_mut() {
+            let position = transform.translation.truncate();
+
+            let nearest = player_query
+                .iter()
+                .filter(|(_, health)| health.current > 0.)
+                .map(|(transform, _)| transform.translation.truncate())
+                .min_by(|a, b| {
+                    a.distance_squared(position)
+                        .total_cmp(&b.distance_squared(position))
+                });
+
+            let perception = Perception {
+                target: nearest,
+                distance: nearest
+                    .map_or(f32::INFINITY, |target| target.distance(position)),
+                health: health.percent(),
+            };
+
+            brain.state = brain.state.next(&perception, &brain.temperament);
+            brain.target = nearest;
+        }
+    }
+
+    fn steer(
+        time: Res<SimulationTime>,
+        mut rng: ResMut<GameRng>,
+        mut steer_query: SteerQuery,
+        obstacle_query: AvoidQuery,
+    ) {
+        let dt = time.delta_seconds();
+
+        for (mut transform, collider, brain, mut steering) in
+            steer_query.iter_mut()
+        {
+            let position = transform.translation.truncate();
+            let standoff = brain.temperament.attack_range * 0.7;
+
+            let force = match (brain.state, brain.target) {
+                (AiState::Chase, Some(target)) => {
+                    steering.seek(position, target)
+                }
+                (AiState::Attack, Some(target)) => {
+                    // Hold position at a distance from the target.
+                    let away = (position - target).normalize_or_zero();
+                    steering.arrive(position, target + away * standoff, 100.)
+                }
+                (AiState::Retreat, Some(target)) => {
+                    steering.flee(position, target)
+                }
+                _ if position.distance(brain.home) > PATROL_RADIUS => {
+                    steering.arrive(position, brain.home, PATROL_RADIUS)
+                }
+                _ => steering.wander(&mut rng),
+            };
+
+            let obstacles =
+                obstacle_query.iter().map(|(transform, collider)| {
+                    (transform.translation.truncate(), collider)
+                });
+            let avoidance = steering.avoid(position, collider, obstacles);
+
+            let travelled = steering.integrate(force + avoidance * 2., dt);
+            transform.translation += travelled.extend(0.);
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use bevy::{app::PluginGroupBuilder, prelude::*};
+#[cfg(feature = "audio")]
+use bevy::{audio::AudioSink, utils::HashMap};
+
+use crate::{
+    events::{ObstacleDestroyed, PlayerHit, ShotFired},
+    settings::Settings,
+    state::GameState,
+};
+
+/// Seconds it takes for one music track to fully replace another.
+const CROSSFADE: f32 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sound {
+    Shot,
+    RockBreak,
+    DebrisBreak,
+    PlayerHit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Music {
+    Menu,
+    Level,
+}
+
+impl Music {
+    fn for_state(state: &GameState) -> Option<Self> {
+        match state {
+            GameState::Level => Some(Music::Level),
+            GameState::MainMenu
+            | GameState::HighScores
+            | GameState::Settings
+            | GameState::GameOver
+            | GameState::Editor => Some(Music::Menu),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioCommand {
+    PlaySound { sound: Sound, volume: f32 },
+    PlayMusic { track: Music, volume: f32 },
+    SetMusicVolume { track: Music, volume: f32 },
+    StopMusic { track: Music },
+}
+
+/// Commands produced by the mixer this frame, drained by whichever backend
+/// the plugin was built with.
+#[derive(Default)]
+pub struct AudioQueue(Vec<AudioCommand>);
+
+impl AudioQueue {
+    pub fn push(&mut self, command: AudioCommand) {
+        self.0.push(command);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioBackend {
+    /// Plays through `bevy_audio`, which needs an output device.
+    #[cfg(feature = "audio")]
+    Bevy,
+    /// Discards every command, for headless runs.
+    Null,
+}
+
+impl AudioBackend {
+    /// `SHOOTER_NO_AUDIO` in the environment selects the null backend, as
+    /// does a build without the `audio` feature.
+    pub fn from_env() -> Self {
+        #[cfg(feature = "audio")]
+        if std::env::var_os("SHOOTER_NO_AUDIO").is_none() {
+            return AudioBackend::Bevy;
+        }
+
+        AudioBackend::Null
+    }
+
+    /// Leaves `bevy_audio` out of the default plugins unless it plays the
+    /// sound, so headless runs never open an output device.
+    pub fn plugins(
+        self,
+        group: &mut PluginGroupBuilder,
+    ) -> &mut PluginGroupBuilder {
+        #[cfg(feature = "audio")]
+        if self == AudioBackend::Null {
+            group.disable::<bevy::audio::AudioPlugin>();
+        }
+
+        group
+    }
+}
+
+struct MusicVoice {
+    track: Music,
+    gain: f32,
+    target: f32,
+}
+
+#[derive(Default)]
+struct MusicMixer {
+    voices: Vec<MusicVoice>,
+}
+
+#[cfg(feature = "audio")]
+struct SoundAssets {
+    sounds: HashMap<Sound, Handle<AudioSource>>,
+    music: HashMap<Music, Handle<AudioSource>>,
+}
+
+pub struct SoundPlugin {
+    pub backend: AudioBackend,
+}
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioQueue>()
+            .init_resource::<MusicMixer>()
+            .add_system(Self::play_sounds)
+            .add_system(Self::switch_music)
+            .add_system(Self::crossfade_music.after(Self::switch_music));
+
+        match self.backend {
+            #[cfg(feature = "audio")]
+            AudioBackend::Bevy => {
+                app.add_startup_system(Self::load_audio)
+                    .add_system_to_stage(
+                        CoreStage::PostUpdate,
+                        Self::bevy_backend,
+                    );
+            }
+
+            AudioBackend::Null => {
+                app.add_system_to_stage(
+                    CoreStage::PostUpdate,
+                    Self::null_backend,
+                );
+            }
+        }
+    }
+}
+
+impl SoundPlugin {
+    #[cfg(feature = "audio")]
+    fn load_audio(mut commands: Commands, asset_server: Res<AssetServer>) {
+        let sounds = [
+            (Sound::Shot, "audio/shot.wav"),
+            (Sound::RockBreak, "audio/rock_break.wav"),
+            (Sound::DebrisBreak, "audio/debris_break.wav"),
+            (Sound::PlayerHit, "audio/player_hit.wav"),
+        ]
+        .into_iter()
+        .map(|(sound, path)| (sound, asset_server.load(path)))
+        .collect();
+
+        let music = [
+            (Music::Menu, "audio/music_menu.wav"),
+            (Music::Level, "audio/music_level.wav"),
+        ]
+        .into_iter()
+        .map(|(track, path)| (track, asset_server.load(path)))
+        .collect();
+
+        commands.insert_resource(SoundAssets { sounds, music });
+    }
+
+    fn play_sounds(
+        settings: Res<Settings>,
+        mut queue: ResMut<AudioQueue>,
+        mut shot_evr: EventReader<ShotFired>,
+        mut destroyed_evr: EventReader<ObstacleDestroyed>,
+        mut hit_evr: EventReader<PlayerHit>,
+    ) {
+        let volume = settings.master_volume * settings.sfx_volume;
+
+        let shots = shot_evr.iter().map(|_| Sound::Shot);
+        let destroyed = destroyed_evr.iter().map(|ev| {
+            if ev.split {
+                Sound::RockBreak
+            } else {
+                Sound::DebrisBreak
+            }
+        });
+        let hits = hit_evr.iter().map(|_| Sound::PlayerHit);
+
+        for sound in shots.chain(destroyed).chain(hits) {
+            queue.push(AudioCommand::PlaySound { sound, volume });
+        }
+    }
+
+    fn switch_music(
+        state: Res<State<GameState>>,
+        mut mixer: ResMut<MusicMixer>,
+    ) {
+        if !state.is_changed() {
+            return;
+        }
+
+        let next = Music::for_state(state.current());
+
+        for voice in mixer.voices.iter_mut() {
+            voice.target = if Some(voice.track) == next { 1. } else { 0. };
+        }
+
+        if let Some(track) = next {
+            if !mixer.voices.iter().any(|voice| voice.track == track) {
+                mixer.voices.push(MusicVoice {
+                    track,
+                    gain: 0.,
+                    target: 1.,
+                });
+            }
+        }
+    }
+
+    fn crossfade_music(
+        time: Res<Time>,
+        settings: Res<Settings>,
+        mut mixer: ResMut<MusicMixer>,
+        mut queue: ResMut<AudioQueue>,
+        mut started: Local<Vec<Music>>,
+    ) {
+        let step = time.delta_seconds() / CROSSFADE;
+        let volume = settings.master_volume * settings.music_volume;
+
+        for voice in mixer.voices.iter_mut() {
+            if !started.contains(&voice.track) {
+                started.push(voice.track);
+                queue.push(AudioCommand::PlayMusic {
+                    track: voice.track,
+                    volume: voice.gain * volume,
+                });
+            }
+
+            if voice.gain < voice.target {
+                voice.gain = (voice.gain + step).min(voice.target);
+            } else if voice.gain > voice.target {
+                voice.gain = (voice.gain - step).max(voice.target);
+            }
+
+            queue.push(AudioCommand::SetMusicVolume {
+                track: voice.track,
+                volume: voice.gain * volume,
+            });
+        }
+
+        for voice in mixer.voices.iter() {
+            if voice.gain <= 0. && voice.target <= 0. {
+                started.retain(|track| *track != voice.track);
+                queue.push(AudioCommand::StopMusic { track: voice.track });
+            }
+        }
+
+        mixer
+            .voices
+            .retain(|voice| voice.gain > 0. || voice.target > 0.);
+    }
+
+    #[cfg(feature = "audio")]
+    fn bevy_backend(
+        audio: Res<Audio>,
+        assets: Res<SoundAssets>,
+        sinks: Res<Assets<AudioSink>>,
+        mut queue: ResMut<AudioQueue>,
+        mut music_sinks: Local<HashMap<Music, Handle<AudioSink>>>,
+    ) {
+        for command in queue.0.drain(..) {
+            match command {
+                AudioCommand::PlaySound { sound, volume } => {
+                    audio.play_with_settings(
+                        assets.sounds[&sound].clone(),
+                        PlaybackSettings::ONCE.with_volume(volume),
+                    );
+                }
+
+                AudioCommand::PlayMusic { track, volume } => {
+                    let sink = audio.play_with_settings(
+                        assets.music[&track].clone(),
+                        PlaybackSettings::LOOP.with_volume(volume),
+                    );
+                    music_sinks.insert(track, sinks.get_handle(sink));
+                }
+
+                AudioCommand::SetMusicVolume { track, volume } => {
+                    if let Some(sink) =
+                        music_sinks.get(&track).and_then(|h| sinks.get(h))
+                    {
+                        sink.set_volume(volume);
+                    }
+                }
+
+                AudioCommand::StopMusic { track } => {
+                    if let Some(sink) =
+                        music_sinks.remove(&track).and_then(|h| sinks.get(&h))
+                    {
+                        sink.stop();
+                    }
+                }
+            }
+        }
+    }
+
+    fn null_backend(mut queue: ResMut<AudioQueue>) {
+        queue.0.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventsPlugin;
+
+    /// Every command the mixer queued, seen before the backend drains them.
+    #[derive(Default)]
+    struct Recorded(Vec<AudioCommand>);
+
+    fn record(queue: Res<AudioQueue>, mut recorded: ResMut<Recorded>) {
+        recorded.0.extend(queue.0.iter().copied());
+    }
+
+    fn headless_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(Settings::default())
+            .init_resource::<Recorded>()
+            .add_state(GameState::MainMenu)
+            .add_plugin(EventsPlugin)
+            .add_plugin(SoundPlugin {
+                backend: AudioBackend::Null,
+            })
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                record.before(SoundPlugin::null_backend),
+            );
+        app
+    }
+
+    #[test]
+    fn null_backend_drains_sounds() {
+        let mut app = headless_app();
+        app.world.send_event(ShotFired);
+        app.world.send_event(ObstacleDestroyed {
+            player: 0,
+            position: Vec2::ZERO,
+            impact: Vec2::ZERO,
+            direction: 0.,
+            split: true,
+        });
+        app.update();
+
+        let sounds: Vec<Sound> = app
+            .world
+            .resource::<Recorded>()
+            .0
+            .iter()
+            .filter_map(|command| match command {
+                AudioCommand::PlaySound { sound, .. } => Some(*sound),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(sounds, [Sound::Shot, Sound::RockBreak]);
+        assert!(app.world.resource::<AudioQueue>().0.is_empty());
+    }
+
+    #[test]
+    fn music_follows_the_state() {
+        let mut app = headless_app();
+        app.update();
+
+        let recorded = &app.world.resource::<Recorded>().0;
+        assert!(recorded.iter().any(|command| matches!(
+            command,
+            AudioCommand::PlayMusic {
+                track: Music::Menu,
+                ..
+            }
+        )));
+    }
+}
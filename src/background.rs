@@ -0,0 +1,203 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, transform::TransformSystem};
+use rand::Rng;
+
+use crate::{
+    camera::{CameraSystem, MainCamera},
+    graphics,
+    player::Player,
+    run::RunSystem,
+    simulation::GameRng,
+    state::GameState,
+};
+
+/// Stream of the seed the starfield is generated from, kept apart from the
+/// gameplay one so it doesn't change how a run plays out.
+const STARFIELD_STREAM: u64 = 1;
+
+/// Units per second the whole field drifts down at, before parallax.
+const DRIFT: f32 = 40.;
+
+/// How much of the player's own movement is fed back into the scroll.
+const MOVEMENT_FEEDBACK: f32 = 0.5;
+
+/// Extra room around the visible area, so stars wrap out of sight.
+const MARGIN: f32 = 16.;
+
+struct StarLayer {
+    count: usize,
+    /// `0` stays glued to the screen, `1` moves along with the world.
+    depth: f32,
+    size: f32,
+    brightness: f32,
+    z: f32,
+}
+
+const LAYERS: [StarLayer; 3] = [
+    StarLayer {
+        count: 90,
+        depth: 0.15,
+        size: 1.,
+        brightness: 0.35,
+        z: 0.1,
+    },
+    StarLayer {
+        count: 50,
+        depth: 0.35,
+        size: 2.,
+        brightness: 0.55,
+        z: 0.2,
+    },
+    StarLayer {
+        count: 20,
+        depth: 0.7,
+        size: 3.,
+        brightness: 0.85,
+        z: 0.3,
+    },
+];
+
+#[derive(Component)]
+pub struct Star {
+    /// Normalized position inside the wrapping area.
+    origin: Vec2,
+    depth: f32,
+    brightness: f32,
+    twinkle_speed: f32,
+    twinkle_phase: f32,
+}
+
+type StarQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static mut Sprite, &'static Star),
+    (Without<MainCamera>, Without<Player>),
+>;
+
+#[derive(Default)]
+struct Starfield {
+    scroll: Vec2,
+    last_player: Option<Vec2>,
+}
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Starfield>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Level)
+                    .with_system(Self::spawn_stars.after(RunSystem::Reset)),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                Self::scroll_stars
+                    .after(CameraSystem::Position)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+impl BackgroundPlugin {
+    fn spawn_stars(
+        mut commands: Commands,
+        rng: Res<GameRng>,
+        mut starfield: ResMut<Starfield>,
+        star_query: Query<Entity, With<Star>>,
+    ) {
+        for e in star_query.iter() {
+            commands.entity(e).despawn();
+        }
+
+        *starfield = Starfield::default();
+
+        let mut rng = rng.fork(STARFIELD_STREAM);
+        let color = Color::hex(graphics::WHITE).unwrap();
+
+        for layer in LAYERS.iter() {
+            for _ in 0..layer.count {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color,
+                            custom_size: Some(Vec2::splat(layer.size)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_xyz(0., 0., layer.z),
+                        ..Default::default()
+                    })
+                    .insert(Star {
+                        origin: Vec2::new(rng.gen(), rng.gen()),
+                        depth: layer.depth,
+                        brightness: layer.brightness * rng.gen_range(0.6..=1.),
+                        twinkle_speed: rng.gen_range(0.5..3.),
+                        twinkle_phase: rng.gen_range(0.0..TAU),
+                    });
+            }
+        }
+    }
+
+    fn scroll_stars(
+        time: Res<Time>,
+        mut starfield: ResMut<Starfield>,
+        camera_query: Query<
+            (&OrthographicProjection, &Transform),
+            With<MainCamera>,
+        >,
+        player_query: Query<&Transform, With<Player>>,
+        mut star_query: StarQuery,
+    ) {
+        let Ok((projection, camera)) = camera_query.get_single() else {
+            return;
+        };
+
+        let dt = time.delta_seconds();
+
+        // Players move as a group as far as the stars are concerned.
+        let player = (!player_query.is_empty()).then(|| {
+            player_query
+                .iter()
+                .map(|transform| transform.translation.truncate())
+                .fold(Vec2::ZERO, |total, position| total + position)
+                / player_query.iter().len() as f32
+        });
+        let movement = match (starfield.last_player, player) {
+            (Some(last), Some(current)) => current - last,
+            _ => Vec2::ZERO,
+        };
+        starfield.last_player = player;
+
+        starfield.scroll +=
+            Vec2::new(0., DRIFT) * dt + movement * MOVEMENT_FEEDBACK;
+
+        let center = camera.translation.truncate();
+        let area = Vec2::new(
+            projection.right - projection.left,
+            projection.top - projection.bottom,
+        ) * projection.scale
+            + MARGIN * 2.;
+
+        let t = time.seconds_since_startup() as f32;
+
+        for (mut transform, mut sprite, star) in star_query.iter_mut() {
+            // Far layers are dragged along with the camera while near ones
+            // lag behind it, which is what sells the parallax.
+            let offset = wrap(
+                star.origin * area - (starfield.scroll + center) * star.depth,
+                area,
+            );
+            let position = center - area / 2. + offset;
+
+            transform.translation = position.extend(transform.translation.z);
+
+            let twinkle = 0.75
+                + 0.25 * (t * star.twinkle_speed + star.twinkle_phase).sin();
+            sprite.color.set_a(star.brightness * twinkle);
+        }
+    }
+}
+
+fn wrap(position: Vec2, area: Vec2) -> Vec2 {
+    Vec2::new(position.x.rem_euclid(area.x), position.y.rem_euclid(area.y))
+}
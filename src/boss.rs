@@ -0,0 +1,491 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{
+    common::{Collider, Health, Playfield, Threat},
+    events::ShotFired,
+    graphics::{self, TexturesSheets},
+    minimap::MinimapBlip,
+    obstacles::Spawner,
+    patterns::{self, Emission, Patterns},
+    player::Player,
+    projectiles::ENEMY,
+    run::GameMode,
+    simulation::{Interpolated, SimulationApp, SimulationTime},
+    state::{GameState, LevelScoped},
+};
+
+/// Every how many waves a boss shows up.
+const BOSS_EVERY: u32 = 4;
+/// Direction the bosses fire in when not aiming, down towards the players.
+const HEADING: f32 = -FRAC_PI_2;
+
+/// A pattern fired from a part for as long as the part stands.
+#[derive(Clone, Copy, Debug)]
+pub struct Attack {
+    pub part: usize,
+    /// Name of the pattern in `Patterns`.
+    pub pattern: &'static str,
+}
+
+/// What the boss does once its health falls to `threshold`, a fraction of
+/// its full health.
+#[derive(Clone, Copy, Debug)]
+pub struct Phase {
+    pub threshold: f32,
+    pub attacks: &'static [Attack],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PartDef {
+    pub sprite: usize,
+    /// Position relative to the core, the first part.
+    pub offset: Vec2,
+    pub scale: f32,
+    pub collider: Vec2,
+    pub health: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BossDef {
+    pub name: &'static str,
+    /// The core comes first; the boss is defeated along with it.
+    pub parts: &'static [PartDef],
+    /// Ordered by decreasing threshold, the first one starting at `1`.
+    pub phases: &'static [Phase],
+    /// How far the boss sways around where it appeared.
+    pub sway: Vec2,
+}
+
+pub const BOSSES: &[BossDef] = &[BossDef {
+    name: "DREADNOUGHT",
+    parts: &[
+        PartDef {
+            sprite: 2,
+            offset: Vec2::ZERO,
+            scale: 2.,
+            collider: Vec2::new(180., 170.),
+            health: 600.,
+        },
+        PartDef {
+            sprite: 0,
+            offset: Vec2::new(-170., -40.),
+            scale: 1.,
+            collider: Vec2::new(90., 85.),
+            health: 150.,
+        },
+        PartDef {
+            sprite: 0,
+            offset: Vec2::new(170., -40.),
+            scale: 1.,
+            collider: Vec2::new(90., 85.),
+            health: 150.,
+        },
+    ],
+    phases: &[
+        Phase {
+            threshold: 1.,
+            attacks: &[
+                Attack {
+                    part: 0,
+                    pattern: "ring",
+                },
+                Attack {
+                    part: 1,
+                    pattern: "aimed_fan",
+                },
+                Attack {
+                    part: 2,
+                    pattern: "aimed_fan",
+                },
+            ],
+        },
+        Phase {
+            threshold: 0.6,
+            attacks: &[
+                Attack {
+                    part: 0,
+                    pattern: "spiral",
+                },
+                Attack {
+                    part: 1,
+                    pattern: "wide_fan",
+                },
+                Attack {
+                    part: 2,
+                    pattern: "wide_fan",
+                },
+            ],
+        },
+        Phase {
+            threshold: 0.25,
+            attacks: &[
+                Attack {
+                    part: 0,
+                    pattern: "reverse_spiral",
+                },
+                Attack {
+                    part: 0,
+                    pattern: "flower",
+                },
+            ],
+        },
+    ],
+    sway: Vec2::new(220., 60.),
+}];
+
+/// Index in `BOSSES` of the boss called `name`.
+pub fn by_name(name: &str) -> Option<usize> {
+    BOSSES
+        .iter()
+        .position(|def| def.name.eq_ignore_ascii_case(name))
+}
+
+/// The boss joining the given wave, if any; none in versus.
+pub fn for_wave(wave: u32, mode: GameMode) -> Option<usize> {
+    match mode {
+        GameMode::Versus { .. } => None,
+        _ if wave.is_multiple_of(BOSS_EVERY) => {
+            Some((wave / BOSS_EVERY - 1) as usize % BOSSES.len())
+        }
+        _ => None,
+    }
+}
+
+/// Where the boss `def` shows up: as far from the positions in `avoid` as
+/// the playfield allows, with room for it to sway.
+pub fn spawn_point(def: usize, playfield: &Playfield, avoid: &[Vec2]) -> Vec2 {
+    let def = &BOSSES[def];
+
+    let reach = def
+        .parts
+        .iter()
+        .map(|part| part.offset.abs() + part.collider / 2.)
+        .fold(Vec2::ZERO, Vec2::max)
+        + def.sway;
+    let min = reach.min(playfield.center());
+    let max = (playfield.size() - reach).max(playfield.center());
+
+    let candidates = [
+        Vec2::new(0.25, 0.25),
+        Vec2::new(0.75, 0.25),
+        Vec2::new(0.25, 0.75),
+        Vec2::new(0.75, 0.75),
+    ]
+    .map(|fraction| (fraction * playfield.size()).clamp(min, max));
+
+    let clearance = |point: &Vec2| {
+        avoid
+            .iter()
+            .map(|seat| seat.distance(*point))
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    candidates
+        .into_iter()
+        .max_by(|a, b| clearance(a).total_cmp(&clearance(b)))
+        .unwrap_or_else(|| playfield.center())
+}
+
+/// The core of a boss, which the other parts are mounted on.
+#[derive(Component)]
+pub struct Boss {
+    /// Index in `BOSSES`.
+    pub def: usize,
+    pub phase: usize,
+    /// Where the boss appeared and sways around.
+    pub home: Vec2,
+    pub elapsed: f32,
+    /// How far into its pattern each attack of the current phase is.
+    pub emissions: Vec<Emission>,
+}
+
+impl Boss {
+    pub fn def(&self) -> &'static BossDef {
+        &BOSSES[self.def]
+    }
+
+    fn emissions(phase: &Phase) -> Vec<Emission> {
+        vec![Emission::default(); phase.attacks.len()]
+    }
+}
+
+/// One of the pieces of a boss, each with its own collider and health.
+#[derive(Component, Clone, Copy)]
+pub struct BossPart {
+    /// Index in the boss's `parts`, `0` being the core.
+    pub index: usize,
+}
+
+impl BossPart {
+    pub fn is_core(&self) -> bool {
+        self.index == 0
+    }
+}
+
+/// Brings in the boss `def` at `position`, fully healed.
+pub fn spawn_boss(
+    commands: &mut Commands,
+    ts: &TexturesSheets,
+    def: usize,
+    position: Vec2,
+) {
+    let boss = Boss {
+        def,
+        phase: 0,
+        home: position,
+        elapsed: 0.,
+        emissions: Boss::emissions(&BOSSES[def].phases[0]),
+    };
+
+    let parts = BOSSES[def]
+        .parts
+        .iter()
+        .map(|part| Health::new(part.health))
+        .enumerate()
+        .collect::<Vec<_>>();
+
+    restore_boss(commands, ts, boss, position, &parts);
+}
+
+/// Spawns `boss` with its core at `position` and the given parts, the
+/// others having been destroyed.
+pub fn restore_boss(
+    commands: &mut Commands,
+    ts: &TexturesSheets,
+    boss: Boss,
+    position: Vec2,
+    parts: &[(usize, Health)],
+) {
+    let def = boss.def();
+    let mut boss = Some(boss);
+
+    for &(index, health) in parts {
+        let part = &def.parts[index];
+
+        let mut transform =
+            Transform::from_translation((position + part.offset).extend(1.));
+        transform.scale = Vec3::new(part.scale, part.scale, 1.);
+
+        let mut entity = commands.spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite::new(part.sprite),
+            texture_atlas: ts.entities.clone(),
+            transform,
+            ..Default::default()
+        });
+
+        entity
+            .insert(BossPart { index })
+            .insert(Collider {
+                width: part.collider.x,
+                height: part.collider.y,
+                ..Default::default()
+            })
+            .insert(health)
+            .insert(Threat)
+            .insert(MinimapBlip::new(graphics::SPACESHIP_RED, 8.))
+            .insert(Interpolated::default())
+            .insert(Name::new(format!("{} part {}", def.name, index)))
+            .insert(LevelScoped);
+
+        if index == 0 {
+            if let Some(boss) = boss.take() {
+                entity.insert(boss);
+            }
+        }
+    }
+}
+
+/// Health of the whole boss as a fraction of its full health, counting
+/// destroyed parts as empty.
+pub fn health_fraction<'a>(
+    def: &BossDef,
+    parts: impl Iterator<Item = &'a Health>,
+) -> f32 {
+    let max: f32 = def.parts.iter().map(|part| part.health).sum();
+    let current: f32 = parts.map(|health| health.current).sum();
+
+    current / max
+}
+
+type PartQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static BossPart,
+        &'static Health,
+        &'static mut Transform,
+    ),
+    Without<Boss>,
+>;
+
+/// Large enemies made of several parts, going through phases of bullet
+/// patterns as they lose health.
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_simulation_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::sway)
+                .with_system(Self::carry_parts.after(Self::sway))
+                .with_system(Self::advance_phase)
+                .with_system(
+                    Self::attack
+                        .after(Self::carry_parts)
+                        .after(Self::advance_phase),
+                )
+                .with_system(Self::remove_wreckage),
+        );
+    }
+}
+
+impl BossPlugin {
+    fn sway(
+        time: Res<SimulationTime>,
+        mut boss_query: Query<(&mut Boss, &mut Transform)>,
+    ) {
+        for (mut boss, mut transform) in boss_query.iter_mut() {
+            boss.elapsed += time.delta_seconds();
+
+            let sway = boss.def().sway;
+            let offset = Vec2::new(
+                (boss.elapsed * 0.4).sin() * sway.x,
+                (boss.elapsed * 0.7).sin() * sway.y,
+            );
+
+            let z = transform.translation.z;
+            transform.translation = (boss.home + offset).extend(z);
+        }
+    }
+
+    /// Keeps every part in place on the core.
+    fn carry_parts(
+        boss_query: Query<(&Boss, &Transform)>,
+        mut part_query: PartQuery,
+    ) {
+        let (boss, core) = match boss_query.get_single() {
+            Ok(boss) => boss,
+            Err(_) => return,
+        };
+
+        let position = core.translation.truncate();
+        for (_, part, _, mut transform) in part_query.iter_mut() {
+            let offset = boss.def().parts[part.index].offset;
+            let z = transform.translation.z;
+            transform.translation = (position + offset).extend(z);
+        }
+    }
+
+    /// Moves on to the last phase whose threshold the boss's health has
+    /// fallen to.
+    fn advance_phase(
+        mut boss_query: Query<(&mut Boss, &Health)>,
+        part_query: PartQuery,
+    ) {
+        for (mut boss, core_health) in boss_query.iter_mut() {
+            let def = boss.def();
+            let parts = part_query.iter().map(|(_, _, health, _)| health);
+            let health =
+                health_fraction(def, std::iter::once(core_health).chain(parts));
+
+            let phase = def
+                .phases
+                .iter()
+                .rposition(|phase| health <= phase.threshold)
+                .unwrap_or(0);
+
+            if phase > boss.phase {
+                boss.phase = phase;
+                boss.emissions = Boss::emissions(&def.phases[phase]);
+            }
+        }
+    }
+
+    fn attack(
+        mut spawner: Spawner,
+        patterns: Res<Patterns>,
+        time: Res<SimulationTime>,
+        mut boss_query: Query<(&mut Boss, &Transform)>,
+        part_query: PartQuery,
+        player_query: Query<(&Transform, &Health), With<Player>>,
+        mut shot_evw: EventWriter<ShotFired>,
+    ) {
+        let (mut boss, core) = match boss_query.get_single_mut() {
+            Ok(boss) => boss,
+            Err(_) => return,
+        };
+
+        let phase = &boss.def().phases[boss.phase];
+
+        for (i, attack) in phase.attacks.iter().enumerate() {
+            let origin = if attack.part == 0 {
+                Some(core.translation.truncate())
+            } else {
+                part_query
+                    .iter()
+                    .find(|(_, part, ..)| part.index == attack.part)
+                    .map(|(.., transform)| transform.translation.truncate())
+            };
+
+            let target = origin.and_then(|origin| {
+                player_query
+                    .iter()
+                    .filter(|(_, health)| health.current > 0.)
+                    .map(|(transform, _)| transform.translation.truncate())
+                    .min_by(|a, b| {
+                        a.distance_squared(origin)
+                            .total_cmp(&b.distance_squared(origin))
+                    })
+                    .map(|target| {
+                        let offset = target - origin;
+                        offset.y.atan2(offset.x)
+                    })
+            });
+
+            let pattern = match patterns.get(attack.pattern) {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            let shots = boss.emissions[i].advance(
+                pattern,
+                time.delta_seconds(),
+                HEADING,
+                target.unwrap_or(HEADING),
+            );
+
+            // The part firing this attack is gone.
+            let origin = match origin {
+                Some(origin) if !shots.is_empty() => origin,
+                _ => continue,
+            };
+
+            patterns::fire(
+                &mut spawner,
+                pattern,
+                ENEMY,
+                origin,
+                Vec2::ZERO,
+                &shots,
+            );
+            shot_evw.send(ShotFired);
+        }
+    }
+
+    /// Takes the rest of a boss down with its core.
+    fn remove_wreckage(
+        mut commands: Commands,
+        boss_query: Query<(), With<Boss>>,
+        part_query: PartQuery,
+    ) {
+        if !boss_query.is_empty() {
+            return;
+        }
+
+        for (e, ..) in part_query.iter() {
+            commands.entity(e).despawn_recursive();
+        }
+    }
+}
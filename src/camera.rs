@@ -0,0 +1,458 @@
+use bevy::{
+    prelude::*,
+    render::camera::{ScalingMode as ProjectionScaling, Viewport},
+    transform::TransformSystem,
+    window::WindowResized,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::Playfield,
+    events::{ObstacleDestroyed, PlayerHit},
+    player::{Cursor, Player},
+    simulation::SimulationTime,
+    HEIGHT, WIDTH,
+};
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum ScalingMode {
+    /// Shows exactly the view, scaled to fit and centered with bars on the
+    /// remaining sides.
+    #[default]
+    Letterbox,
+    /// Shows the whole view and whatever lies around it.
+    Fit,
+    /// Fills the window, cropping the view along one axis.
+    Expand,
+}
+
+/// Area of the world the camera is designed to show at once, before the
+/// window's aspect ratio is taken into account.
+pub const VIEW_SIZE: Vec2 = Vec2::new(WIDTH, HEIGHT);
+
+/// How quickly the camera catches up with the player; higher is snappier.
+const FOLLOW_SHARPNESS: f32 = 6.;
+
+/// How hard shaking can push the camera away from where it should be.
+const MAX_SHAKE_OFFSET: f32 = 18.;
+/// Trauma lost per second.
+const TRAUMA_DECAY: f32 = 1.4;
+/// Fraction of the distance to the cursor the camera leans towards.
+const LEAD_FACTOR: f32 = 0.08;
+const MAX_LEAD: f32 = 48.;
+/// Room kept around the players when zooming out to fit them all.
+const ZOOM_MARGIN: Vec2 = Vec2::new(480., 320.);
+const HIT_STOP: f32 = 0.06;
+const HIT_STOP_SCALE: f32 = 0.1;
+
+#[derive(Default, PartialEq, Eq)]
+pub struct CameraSettings {
+    pub scaling: ScalingMode,
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+    pub camera_lead: bool,
+}
+
+/// Transient state of the camera effects layered on top of where the camera
+/// would otherwise be looking.
+#[derive(Default)]
+pub struct CameraEffects {
+    /// In `[0, 1]`; the shake amplitude grows with its square.
+    trauma: f32,
+    lead: Vec2,
+    hit_stop: Timer,
+    /// Where the camera is following, before effects. `None` until there is
+    /// something to follow, so the first frame snaps instead of panning.
+    focus: Option<Vec2>,
+    /// How far the camera has zoomed out to keep every player in view.
+    zoom: Option<f32>,
+    /// Leaves the camera wherever it's put instead of following anything.
+    free: bool,
+}
+
+impl CameraEffects {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.);
+    }
+
+    pub fn hit_stop(&mut self, seconds: f32) {
+        self.hit_stop = Timer::from_seconds(seconds, false);
+    }
+
+    /// Hands the camera over to whoever moves it, or takes it back, snapping
+    /// to the players on the next frame.
+    #[cfg(debug_assertions)]
+    pub fn set_free(&mut self, free: bool) {
+        self.free = free;
+        self.focus = None;
+        self.zoom = None;
+    }
+}
+
+#[derive(Component, Default)]
+pub struct MainCamera;
+
+/// Lets other plugins order themselves around where the camera ends up
+/// looking this frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum CameraSystem {
+    Position,
+}
+
+type CameraTransformQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut OrthographicProjection, &'static mut Transform),
+    (With<MainCamera>, Without<Player>, Without<Cursor>),
+>;
+
+/// Keeps an entity at a fixed offset from a point of the visible area,
+/// regardless of window size or where the camera is looking. `anchor` is
+/// normalized, `(0, 0)` being the bottom-left corner and `(1, 1)` the
+/// top-right one.
+#[derive(Component, Clone, Copy)]
+pub struct ScreenAnchor {
+    pub anchor: Vec2,
+    pub offset: Vec2,
+}
+
+impl ScreenAnchor {
+    pub fn top_left(offset: Vec2) -> Self {
+        Self {
+            anchor: Vec2::new(0., 1.),
+            offset,
+        }
+    }
+
+    pub fn center(offset: Vec2) -> Self {
+        Self {
+            anchor: Vec2::splat(0.5),
+            offset,
+        }
+    }
+
+    pub fn top(offset: Vec2) -> Self {
+        Self {
+            anchor: Vec2::new(0.5, 1.),
+            offset,
+        }
+    }
+
+    pub fn top_right(offset: Vec2) -> Self {
+        Self {
+            anchor: Vec2::ONE,
+            offset,
+        }
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Playfield>()
+            .init_resource::<CameraSettings>()
+            .init_resource::<CameraEffects>()
+            .add_startup_system(Self::setup_camera)
+            .add_system(Self::react_to_events)
+            .add_system(Self::update_hit_stop.after(Self::react_to_events))
+            .add_system_to_stage(CoreStage::PostUpdate, Self::fit_to_window)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                Self::position_camera
+                    .label(CameraSystem::Position)
+                    .after(Self::fit_to_window),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                Self::anchor_to_screen
+                    .after(Self::position_camera)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+impl CameraPlugin {
+    fn setup_camera(mut commands: Commands) {
+        commands
+            .spawn_bundle(Camera2dBundle::default())
+            .insert(MainCamera);
+    }
+
+    fn fit_to_window(
+        windows: Res<Windows>,
+        settings: Res<CameraSettings>,
+        mut resized_evr: EventReader<WindowResized>,
+        mut camera_query: Query<
+            (
+                &mut Camera,
+                &mut OrthographicProjection,
+                ChangeTrackers<MainCamera>,
+            ),
+            With<MainCamera>,
+        >,
+    ) {
+        let resized = resized_evr.iter().count() > 0;
+
+        for (mut camera, mut projection, tracker) in camera_query.iter_mut() {
+            if !(resized || tracker.is_added() || settings.is_changed()) {
+                continue;
+            }
+
+            let window = windows.get_primary().unwrap();
+            let window_size = Vec2::new(window.width(), window.height());
+            let fit = (window_size / VIEW_SIZE).min_element();
+            let cover = (window_size / VIEW_SIZE).max_element();
+
+            let (visible, viewport) = match settings.scaling {
+                ScalingMode::Letterbox => {
+                    let scale_factor = window.scale_factor() as f32;
+                    let size = VIEW_SIZE * fit * scale_factor;
+                    let position = (window_size * scale_factor - size) / 2.;
+
+                    let viewport = Viewport {
+                        physical_position: position.as_uvec2(),
+                        physical_size: size.as_uvec2(),
+                        ..Default::default()
+                    };
+
+                    (VIEW_SIZE, Some(viewport))
+                }
+
+                ScalingMode::Fit => (window_size / fit, None),
+
+                ScalingMode::Expand => (window_size / cover, None),
+            };
+
+            projection.scaling_mode = ProjectionScaling::None;
+            projection.left = -visible.x / 2.;
+            projection.right = visible.x / 2.;
+            projection.bottom = -visible.y / 2.;
+            projection.top = visible.y / 2.;
+
+            camera.viewport = viewport;
+        }
+    }
+
+    fn react_to_events(
+        settings: Res<CameraSettings>,
+        mut effects: ResMut<CameraEffects>,
+        mut hit_evr: EventReader<PlayerHit>,
+        mut destroyed_evr: EventReader<ObstacleDestroyed>,
+    ) {
+        for _ in hit_evr.iter() {
+            if settings.screen_shake {
+                effects.add_trauma(0.5);
+            }
+        }
+
+        for ev in destroyed_evr.iter() {
+            if settings.screen_shake && ev.split {
+                effects.add_trauma(0.3);
+            }
+
+            if settings.hit_stop {
+                effects.hit_stop(HIT_STOP);
+            }
+        }
+    }
+
+    fn update_hit_stop(
+        time: Res<Time>,
+        mut effects: ResMut<CameraEffects>,
+        mut simulation_time: ResMut<SimulationTime>,
+    ) {
+        effects.hit_stop.tick(time.delta());
+
+        let scale = if effects.hit_stop.finished() {
+            1.
+        } else {
+            HIT_STOP_SCALE
+        };
+
+        simulation_time.set_scale(scale);
+    }
+
+    fn position_camera(
+        time: Res<Time>,
+        playfield: Res<Playfield>,
+        settings: Res<CameraSettings>,
+        mut effects: ResMut<CameraEffects>,
+        player_query: Query<(&Transform, &Player)>,
+        cursor_query: Query<(&Transform, &Cursor), Without<Player>>,
+        mut camera_query: CameraTransformQuery,
+    ) {
+        if effects.free {
+            return;
+        }
+
+        let dt = time.delta_seconds();
+
+        let players: Vec<Vec2> = player_query
+            .iter()
+            .map(|(transform, _)| transform.translation.truncate())
+            .collect();
+
+        // Follows the middle of all players, or the middle of the world
+        // while there are none.
+        let (min, max) = players
+            .iter()
+            .fold(None, |bounds, &position| match bounds {
+                Some((min, max)) => {
+                    Some((position.min(min), position.max(max)))
+                }
+                None => Some((position, position)),
+            })
+            .unwrap_or_else(|| (playfield.center(), playfield.center()));
+
+        let target = (min + max) / 2.;
+        let follow = 1. - (-FOLLOW_SHARPNESS * dt).exp();
+        let focus = match effects.focus {
+            Some(focus) => focus.lerp(target, follow),
+            None => target,
+        };
+        effects.focus = Some(focus);
+
+        // Never zooms out further than it takes to see the whole world.
+        let max_zoom = (playfield.size() / VIEW_SIZE).max_element().max(1.);
+        let target_zoom = ((max - min + ZOOM_MARGIN) / VIEW_SIZE)
+            .max_element()
+            .clamp(1., max_zoom);
+        let zoom = match effects.zoom {
+            Some(zoom) => zoom + (target_zoom - zoom) * follow,
+            None => target_zoom,
+        };
+        effects.zoom = Some(zoom);
+
+        effects.trauma = (effects.trauma - TRAUMA_DECAY * dt).max(0.);
+
+        let target_lead = if settings.camera_lead && !players.is_empty() {
+            let total = player_query
+                .iter()
+                .filter_map(|(transform, player)| {
+                    cursor_query
+                        .iter()
+                        .find(|(_, cursor)| cursor.player == player.id)
+                        .map(|(cursor, _)| {
+                            (cursor.translation - transform.translation)
+                                .truncate()
+                        })
+                })
+                .fold(Vec2::ZERO, |total, lead| total + lead);
+
+            (total / players.len() as f32 * LEAD_FACTOR)
+                .clamp_length_max(MAX_LEAD)
+        } else {
+            Vec2::ZERO
+        };
+        effects.lead = effects.lead.lerp(target_lead, (dt * 4.).min(1.));
+
+        let t = time.seconds_since_startup() as f32;
+        let shake = Vec2::new(shake_noise(t, 0.), shake_noise(t, 17.))
+            * MAX_SHAKE_OFFSET
+            * effects.trauma
+            * effects.trauma;
+
+        for (mut projection, mut transform) in camera_query.iter_mut() {
+            projection.scale = zoom;
+
+            let visible = Vec2::new(
+                projection.right - projection.left,
+                projection.top - projection.bottom,
+            ) * projection.scale;
+
+            let position =
+                clamp_to_world(focus + effects.lead, visible, &playfield);
+
+            transform.translation =
+                (position + shake).extend(transform.translation.z);
+        }
+    }
+
+    fn anchor_to_screen(
+        camera_query: Query<
+            (&OrthographicProjection, &Transform),
+            With<MainCamera>,
+        >,
+        mut anchored_query: Query<
+            (&mut Transform, &ScreenAnchor),
+            Without<MainCamera>,
+        >,
+    ) {
+        let (projection, camera) = camera_query.single();
+
+        let center = camera.translation.truncate();
+        let min = center
+            + Vec2::new(projection.left, projection.bottom) * projection.scale;
+        let max = center
+            + Vec2::new(projection.right, projection.top) * projection.scale;
+
+        // Anchored entities keep their size on screen however far the
+        // camera zooms out.
+        for (mut transform, anchor) in anchored_query.iter_mut() {
+            let position = min
+                + (max - min) * anchor.anchor
+                + anchor.offset * projection.scale;
+
+            transform.translation = position.extend(transform.translation.z);
+            transform.scale = Vec3::new(projection.scale, projection.scale, 1.);
+        }
+    }
+}
+
+/// Keeps a camera showing `visible` units within the world, centering it on
+/// any axis along which the world is smaller than the view.
+fn clamp_to_world(
+    position: Vec2,
+    visible: Vec2,
+    playfield: &Playfield,
+) -> Vec2 {
+    let half = visible / 2.;
+    let size = playfield.size();
+
+    let clamp_axis = |position: f32, half: f32, size: f32| {
+        if size <= half * 2. {
+            size / 2.
+        } else {
+            position.clamp(half, size - half)
+        }
+    };
+
+    Vec2::new(
+        clamp_axis(position.x, half.x, size.x),
+        clamp_axis(position.y, half.y, size.y),
+    )
+}
+
+/// Cheap smooth noise in `[-1, 1]`, good enough to make shaking look
+/// organic rather than periodic.
+fn shake_noise(t: f32, seed: f32) -> f32 {
+    ((t * 31. + seed).sin() + (t * 57. + seed * 2.).sin() * 0.5) / 1.5
+}
+
+/// Converts a cursor position, in logical window pixels from the bottom-left,
+/// into world coordinates as seen through `camera`.
+pub fn window_to_world(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    window: &Window,
+    position: Vec2,
+) -> Option<Vec2> {
+    let (min, max) = camera.logical_viewport_rect()?;
+
+    // Viewports are measured from the top-left of the window, cursor
+    // positions from its bottom-left.
+    let bottom = window.height() - max.y;
+    let size = max - min;
+
+    let ndc =
+        Vec2::new(position.x - min.x, position.y - bottom) / size * 2. - 1.;
+
+    let ndc_to_world = camera_transform.compute_matrix()
+        * camera.projection_matrix().inverse();
+
+    Some(ndc_to_world.project_point3(ndc.extend(-1.)).truncate())
+}
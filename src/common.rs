@@ -1,15 +1,16 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{HEIGHT, WIDTH};
 
-#[derive(Component, Reflect, Default)]
+#[derive(Component, Clone, Copy, Reflect, Default, Serialize, Deserialize)]
 #[reflect(Component)]
 pub struct Velocity {
     pub vx: f32,
     pub vy: f32,
 }
 
-#[derive(Component, Clone, Copy, Reflect, Default)]
+#[derive(Component, Clone, Copy, Reflect, Default, Serialize, Deserialize)]
 #[reflect(Component)]
 pub struct Collider {
     pub height: f32,
@@ -17,6 +18,27 @@ pub struct Collider {
     pub offset: Option<Vec2>,
 }
 
+#[derive(Component, Clone, Copy, Reflect, Default, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn percent(&self) -> f32 {
+        self.current / self.max
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.);
+    }
+}
+
 impl From<Collider> for Vec2 {
     fn from(c: Collider) -> Self {
         Vec2::new(c.width, c.height)
@@ -31,10 +53,170 @@ pub fn ease_in_out_sine(x: f32) -> f32 {
     -((std::f32::consts::PI * x).cos() - 1.) / 2.
 }
 
-pub fn out_of_bounds_x(x: f32, width: f32) -> bool {
-    x + width >= WIDTH || x - width < 0.
+/// Time of impact, in `[0, 1]`, of a box of `size` moving from `start` to
+/// `end` against a static box centered at `target`. `None` means the two
+/// never touch along the way.
+pub fn sweep_aabb(
+    start: Vec2,
+    end: Vec2,
+    size: Vec2,
+    target: Vec2,
+    target_size: Vec2,
+) -> Option<f32> {
+    let half_extents = (size + target_size) / 2.;
+    let min = target - half_extents;
+    let max = target + half_extents;
+    let delta = end - start;
+
+    let mut entry = 0f32;
+    let mut exit = 1f32;
+
+    for axis in 0..2 {
+        if delta[axis].abs() <= f32::EPSILON {
+            if start[axis] <= min[axis] || start[axis] >= max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t0 = (min[axis] - start[axis]) / delta[axis];
+        let t1 = (max[axis] - start[axis]) / delta[axis];
+
+        entry = entry.max(t0.min(t1));
+        exit = exit.min(t0.max(t1));
+
+        if entry > exit {
+            return None;
+        }
+    }
+
+    Some(entry)
 }
 
-pub fn out_of_bounds_y(y: f32, height: f32) -> bool {
-    y + height >= HEIGHT || y - height < 0.
+/// Something worth pointing at when it's close but off screen.
+#[derive(Component, Default)]
+pub struct Threat;
+
+/// Size of the world in world units, independent of the window it is shown
+/// in and usually larger than what fits on screen. The bottom-left corner
+/// sits at the world origin.
+#[derive(Clone, Copy)]
+pub struct Playfield {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Playfield {
+    fn default() -> Self {
+        Self {
+            width: WIDTH,
+            height: HEIGHT,
+        }
+    }
+}
+
+impl Playfield {
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.size() / 2.
+    }
+
+    pub fn out_of_bounds_x(&self, x: f32, width: f32) -> bool {
+        x + width >= self.width || x - width < 0.
+    }
+
+    pub fn out_of_bounds_y(&self, y: f32, height: f32) -> bool {
+        y + height >= self.height || y - height < 0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BULLET: Vec2 = Vec2::new(2., 2.);
+
+    #[test]
+    fn fast_bullet_hits_thin_debris_it_skips_over() {
+        // Both ends of the tick are well clear of the debris.
+        let toi = sweep_aabb(
+            Vec2::new(-100., 0.),
+            Vec2::new(100., 0.),
+            BULLET,
+            Vec2::ZERO,
+            Vec2::new(4., 40.),
+        );
+
+        assert!((toi.unwrap() - 0.485).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bullet_passing_beside_misses() {
+        let toi = sweep_aabb(
+            Vec2::new(-100., 30.),
+            Vec2::new(100., 30.),
+            BULLET,
+            Vec2::ZERO,
+            Vec2::new(4., 40.),
+        );
+
+        assert_eq!(toi, None);
+    }
+
+    #[test]
+    fn sliding_along_an_edge_misses() {
+        // Edges exactly touching, the bullet moving parallel to them.
+        let toi = sweep_aabb(
+            Vec2::new(-100., 21.),
+            Vec2::new(100., 21.),
+            BULLET,
+            Vec2::ZERO,
+            Vec2::new(4., 40.),
+        );
+
+        assert_eq!(toi, None);
+    }
+
+    #[test]
+    fn graze_touching_a_corner_hits() {
+        // Heads diagonally so it only meets the box at its corner.
+        let toi = sweep_aabb(
+            Vec2::new(-13., 11.),
+            Vec2::new(7., 31.),
+            BULLET,
+            Vec2::ZERO,
+            Vec2::new(4., 40.),
+        );
+
+        assert!((toi.unwrap() - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bullet_starting_inside_hits_at_once() {
+        let toi = sweep_aabb(
+            Vec2::new(0., 5.),
+            Vec2::new(50., 5.),
+            BULLET,
+            Vec2::ZERO,
+            Vec2::new(4., 40.),
+        );
+
+        assert_eq!(toi, Some(0.));
+    }
+
+    #[test]
+    fn bullet_stopping_short_misses() {
+        let toi = sweep_aabb(
+            Vec2::new(-100., 0.),
+            Vec2::new(-10., 0.),
+            BULLET,
+            Vec2::ZERO,
+            Vec2::new(4., 40.),
+        );
+
+        assert_eq!(toi, None);
+    }
 }
@@ -1,6 +1,135 @@
-use crate::{common, player, projectiles};
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{
+    ai::{AiState, Brain, Steering},
+    boss::BossPart,
+    camera::MainCamera,
+    common::{self, Collider},
+    enemies::Enemy,
+    graphics, labels,
+    obstacles::{MovingObstacle, Obstacle},
+    particles::Particle,
+    patterns::{Emission, Patterns},
+    pickups::Pickup,
+    player::{self, Cursor, Player},
+    pool::{self, Pooled},
+    projectiles::{self, Bullet},
+    simulation::{self, Interpolated},
+};
 use bevy::prelude::*;
-use bevy_inspector_egui::WorldInspectorPlugin;
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContext},
+    WorldInspectorPlugin,
+};
+
+/// Thickness of the lines drawn for the AI.
+const GIZMO_WIDTH: f32 = 2.;
+/// Steering forces are much larger than velocities, scale them down to fit.
+const FORCE_SCALE: f32 = 0.3;
+
+/// How far above the middle of the view previewed patterns are fired
+/// from, aiming at the middle.
+const PREVIEW_HEIGHT: f32 = 200.;
+
+/// Shows or hides the overlay.
+const OVERLAY_KEY: KeyCode = KeyCode::F3;
+/// Seconds ahead velocity vectors reach.
+const VELOCITY_SCALE: f32 = 0.25;
+/// Length of the lines showing where bullets head and ships aim.
+const BULLET_LINE: f32 = 30.;
+const AIM_LINE: f32 = 120.;
+
+/// Every kind of entity the overlay counts, in the order it lists them.
+const KINDS: [&str; 10] = [
+    "players",
+    "enemies",
+    "boss parts",
+    "bullets",
+    "debris",
+    "obstacles",
+    "pickups",
+    "particles",
+    "pooled",
+    "other",
+];
+
+type KindQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static Player>,
+        Option<&'static Enemy>,
+        Option<&'static BossPart>,
+        Option<&'static Bullet>,
+        Option<&'static MovingObstacle>,
+        Option<&'static Obstacle>,
+        Option<&'static Pickup>,
+        Option<&'static Particle>,
+        Option<&'static Pooled>,
+    ),
+    (Without<AiGizmo>, Without<OverlayGizmo>, Without<AimGizmo>),
+>;
+
+/// The sprites drawing lines for one kind of gizmo.
+type GizmoQuery<'w, 's, M> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+    ),
+    With<M>,
+>;
+
+/// What the overlay outlines: live entities other than its own lines.
+type Drawn = (Without<Pooled>, Without<OverlayGizmo>);
+
+type AiLabelQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Text,
+        &'static mut Visibility,
+    ),
+    (With<AiGizmo>, Without<Sprite>),
+>;
+
+/// A line or label drawn for the AI, kept around and moved from one frame
+/// to the next.
+#[derive(Component, Clone, Copy)]
+struct AiGizmo;
+
+/// A line drawn by the overlay for colliders and velocities.
+#[derive(Component, Clone, Copy)]
+struct OverlayGizmo;
+
+/// A line drawn by the overlay for where ships aim.
+#[derive(Component, Clone, Copy)]
+struct AimGizmo;
+
+/// Whether colliders, velocities, aim and what the AI thinks are drawn over
+/// the level.
+#[derive(Default)]
+struct Overlay {
+    visible: bool,
+}
+
+/// A bullet of a previewed pattern, harmless and outside the simulation.
+#[derive(Component)]
+struct PreviewShot {
+    direction: Vec2,
+    lifetime: Timer,
+}
+
+/// The pattern being previewed, if it's playing.
+#[derive(Default)]
+struct PatternPreview {
+    selected: usize,
+    playing: bool,
+    emission: Emission,
+}
 
 pub struct DebugPlugin;
 
@@ -11,6 +140,483 @@ impl Plugin for DebugPlugin {
             .register_type::<projectiles::Bullet>()
             .register_type::<common::Collider>()
             .register_type::<common::Velocity>()
-            .register_type::<player::Player>();
+            .register_type::<player::Player>()
+            .register_type::<pool::Pooled>()
+            .init_resource::<Overlay>()
+            .add_system(Self::pool_metrics)
+            .add_system(Self::simulation_info)
+            .add_system(Self::ai_gizmos)
+            .add_system(Self::preview_patterns)
+            .add_system(Self::move_preview_shots)
+            .add_system(Self::toggle_overlay)
+            .add_system(Self::overlay_info.after(Self::toggle_overlay))
+            .add_system(Self::draw_colliders.after(Self::toggle_overlay))
+            .add_system(Self::draw_aim.after(Self::toggle_overlay));
+    }
+}
+
+impl DebugPlugin {
+    fn pool_metrics(
+        mut egui_context: ResMut<EguiContext>,
+        pools: Res<pool::Pools>,
+    ) {
+        egui::Window::new("Pools").show(egui_context.ctx_mut(), |ui| {
+            egui::Grid::new("pool_metrics").show(ui, |ui| {
+                ui.label("pool");
+                ui.label("size");
+                ui.label("free");
+                ui.label("hit rate");
+                ui.end_row();
+
+                for (name, pool) in [
+                    ("bullets", &pools.bullets),
+                    ("debris", &pools.debris),
+                    ("particles", &pools.particles),
+                ] {
+                    let metrics = pool.metrics();
+                    ui.label(name);
+                    ui.label(metrics.size.to_string());
+                    ui.label(metrics.free.to_string());
+                    ui.label(format!("{:.0}%", metrics.hit_rate() * 100.));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn simulation_info(
+        mut egui_context: ResMut<EguiContext>,
+        time: Res<simulation::SimulationTime>,
+        rng: Res<simulation::GameRng>,
+    ) {
+        egui::Window::new("Simulation").show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!("tick: {}", time.tick()));
+            ui.label(format!("seed: {}", rng.seed()));
+        });
+    }
+
+    /// Draws every brain's state above it, a line to its target, and its
+    /// velocity and steering force, while the overlay is up.
+    fn ai_gizmos(
+        mut commands: Commands,
+        overlay: Res<Overlay>,
+        fonts: Res<graphics::Fonts>,
+        mut gizmo_query: GizmoQuery<AiGizmo>,
+        mut label_query: AiLabelQuery,
+        brain_query: Query<(&Transform, &Brain, &Steering), Without<AiGizmo>>,
+    ) {
+        let mut lines = Vec::new();
+        let mut state_labels = label_query.iter_mut();
+
+        for (transform, brain, steering) in
+            brain_query.iter().filter(|_| overlay.visible)
+        {
+            let position = transform.translation.truncate();
+
+            let (state, color) = match brain.state {
+                AiState::Patrol => ("PATROL", graphics::ASTEROID_GREY),
+                AiState::Chase => ("CHASE", graphics::EXPLOSION_ORANGE),
+                AiState::Attack => ("ATTACK", graphics::SPACESHIP_RED),
+                AiState::Retreat => ("RETREAT", graphics::PLAYER_TWO_BLUE),
+            };
+            let translation = (position + Vec2::new(0., 60.)).extend(5.);
+
+            match state_labels.next() {
+                Some((mut label_transform, mut text, mut visibility)) => {
+                    label_transform.translation = translation;
+                    visibility.is_visible = true;
+                    if text.sections[0].value != state {
+                        text.sections[0].value = state.to_owned();
+                        text.sections[0].style.color =
+                            Color::hex(color).unwrap();
+                    }
+                }
+                None => {
+                    let mut text = labels::label(
+                        &fonts,
+                        state,
+                        16.,
+                        color,
+                        TextAlignment::CENTER,
+                    );
+                    text.transform = Transform::from_translation(translation);
+                    commands.spawn_bundle(text).insert(AiGizmo);
+                }
+            }
+
+            if let Some(target) = brain.target {
+                if brain.state != AiState::Patrol {
+                    lines.push(Line::new(position, target, color));
+                }
+            }
+
+            let velocity = position + steering.velocity;
+            let force = position + steering.force * FORCE_SCALE;
+            lines.push(Line::new(position, velocity, graphics::WHITE));
+            lines.push(Line::new(position, force, graphics::EXPLOSION_ORANGE));
+        }
+
+        for (_, _, mut visibility) in state_labels {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+        }
+
+        draw_lines(&mut commands, &mut gizmo_query, lines, AiGizmo);
+    }
+
+    /// Plays any pattern on a loop above the middle of the view, without
+    /// firing real bullets.
+    fn preview_patterns(
+        mut commands: Commands,
+        mut egui_context: ResMut<EguiContext>,
+        mut preview: Local<PatternPreview>,
+        patterns: Res<Patterns>,
+        ts: Res<graphics::TexturesSheets>,
+        time: Res<Time>,
+        camera_query: Query<&Transform, With<MainCamera>>,
+    ) {
+        let names: Vec<&str> = patterns.iter().map(|(name, _)| name).collect();
+        if names.is_empty() {
+            return;
+        }
+        let selected = preview.selected;
+
+        egui::Window::new("Patterns").show(egui_context.ctx_mut(), |ui| {
+            egui::ComboBox::from_id_source("pattern")
+                .selected_text(names[selected])
+                .show_ui(ui, |ui| {
+                    for (i, name) in names.iter().enumerate() {
+                        ui.selectable_value(&mut preview.selected, i, *name);
+                    }
+                });
+            ui.checkbox(&mut preview.playing, "Play");
+        });
+
+        if preview.selected != selected || !preview.playing {
+            preview.emission = Emission::default();
+        }
+
+        let center = match camera_query.get_single() {
+            Ok(transform) if preview.playing => {
+                transform.translation.truncate()
+            }
+            _ => return,
+        };
+
+        let (_, pattern) = patterns.iter().nth(preview.selected).unwrap();
+        let origin = center + Vec2::new(0., PREVIEW_HEIGHT);
+        let heading = -FRAC_PI_2;
+        let shots = preview.emission.advance(
+            pattern,
+            time.delta_seconds(),
+            heading,
+            heading,
+        );
+
+        for angle in shots {
+            let mut sprite = TextureAtlasSprite::new(0);
+            sprite.color = Color::hex(graphics::PLAYER_TWO_BLUE).unwrap();
+
+            let mut transform = Transform::from_translation(origin.extend(5.));
+            transform.rotation = Quat::from_rotation_z(angle - FRAC_PI_2);
+
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    sprite,
+                    texture_atlas: ts.projectiles.clone(),
+                    transform,
+                    ..Default::default()
+                })
+                .insert(PreviewShot {
+                    direction: Vec2::from_angle(angle),
+                    lifetime: Timer::from_seconds(
+                        pattern.bullet.lifetime,
+                        false,
+                    ),
+                });
+        }
+    }
+
+    fn move_preview_shots(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut shot_query: Query<(Entity, &mut Transform, &mut PreviewShot)>,
+    ) {
+        for (e, mut transform, mut shot) in shot_query.iter_mut() {
+            shot.lifetime.tick(time.delta());
+            if shot.lifetime.finished() {
+                commands.entity(e).despawn();
+                continue;
+            }
+
+            let travelled = shot.direction
+                * projectiles::BULLET_SPEED
+                * time.delta_seconds();
+            transform.translation += travelled.extend(0.);
+        }
     }
+
+    fn toggle_overlay(
+        keyboard: Res<Input<KeyCode>>,
+        mut overlay: ResMut<Overlay>,
+    ) {
+        if keyboard.just_pressed(OVERLAY_KEY) {
+            overlay.visible = !overlay.visible;
+        }
+    }
+
+    /// How many entities of each kind there are, and the angles drawn from
+    /// each ship.
+    fn overlay_info(
+        mut egui_context: ResMut<EguiContext>,
+        mut overlay: ResMut<Overlay>,
+        kind_query: KindQuery,
+        cursor_query: Query<&Cursor>,
+    ) {
+        if !overlay.visible {
+            return;
+        }
+
+        let mut counts = [0; KINDS.len()];
+        for kinds in kind_query.iter() {
+            let kind = match kinds {
+                (.., Some(_)) => 8,
+                (Some(_), ..) => 0,
+                (_, Some(_), ..) => 1,
+                (_, _, Some(_), ..) => 2,
+                (_, _, _, Some(_), ..) => 3,
+                (_, _, _, _, Some(_), ..) => 4,
+                (_, _, _, _, _, Some(_), ..) => 5,
+                (_, _, _, _, _, _, Some(_), ..) => 6,
+                (_, _, _, _, _, _, _, Some(_), _) => 7,
+                _ => 9,
+            };
+            counts[kind] += 1;
+        }
+
+        egui::Window::new("Overlay (F3)")
+            .open(&mut overlay.visible)
+            .show(egui_context.ctx_mut(), |ui| {
+                egui::Grid::new("entity_counts").show(ui, |ui| {
+                    for (kind, count) in KINDS.iter().zip(counts) {
+                        ui.label(*kind);
+                        ui.label(count.to_string());
+                        ui.end_row();
+                    }
+
+                    ui.label("total");
+                    ui.label(counts.iter().sum::<usize>().to_string());
+                    ui.end_row();
+                });
+
+                for cursor in cursor_query.iter() {
+                    ui.label(format!(
+                        "P{} aim: computed {:.1}°, actual {:.1}°",
+                        cursor.player + 1,
+                        cursor.computed_angle.to_degrees(),
+                        cursor.actual_angle().to_degrees(),
+                    ));
+                }
+            });
+    }
+
+    /// Outlines every collider the way collisions see it, bullets being
+    /// offset and scaled as they shrink, along with where bullets head and
+    /// how fast everything moves.
+    fn draw_colliders(
+        mut commands: Commands,
+        overlay: Res<Overlay>,
+        time: Res<simulation::SimulationTime>,
+        mut gizmo_query: GizmoQuery<OverlayGizmo>,
+        collider_query: Query<(&Transform, &Collider, Option<&Bullet>), Drawn>,
+        motion_query: Query<(&Transform, &Interpolated), Drawn>,
+    ) {
+        let mut lines = Vec::new();
+
+        for (transform, collider, bullet) in
+            collider_query.iter().filter(|_| overlay.visible)
+        {
+            let position = transform.translation.truncate();
+            let size = Vec2::from(*collider);
+
+            let (center, size, color) = match bullet {
+                Some(bullet) => {
+                    lines.push(Line::new(
+                        position,
+                        position + bullet.direction * BULLET_LINE,
+                        graphics::WHITE,
+                    ));
+
+                    (
+                        position + collider.offset.unwrap_or_default(),
+                        size * transform.scale.truncate(),
+                        graphics::PLAYER_TWO_BLUE,
+                    )
+                }
+                None => (position, size, graphics::PICKUP_GREEN),
+            };
+
+            lines.extend(outline(center, size, color));
+        }
+
+        for (transform, interpolated) in
+            motion_query.iter().filter(|_| overlay.visible)
+        {
+            let velocity = match interpolated.step() {
+                Some(step) if step != Vec2::ZERO => step / time.delta_seconds(),
+                _ => continue,
+            };
+
+            let position = transform.translation.truncate();
+            lines.push(Line::new(
+                position,
+                position + velocity * VELOCITY_SCALE,
+                graphics::EXPLOSION_ORANGE,
+            ));
+        }
+
+        draw_lines(&mut commands, &mut gizmo_query, lines, OverlayGizmo);
+    }
+
+    /// Draws from each ship the angle its cursor computed, and the one its
+    /// sprite is actually turned to.
+    fn draw_aim(
+        mut commands: Commands,
+        overlay: Res<Overlay>,
+        mut gizmo_query: GizmoQuery<AimGizmo>,
+        player_query: Query<(&Transform, &Player), Without<AimGizmo>>,
+        cursor_query: Query<&Cursor>,
+    ) {
+        let mut lines = Vec::new();
+
+        for cursor in cursor_query.iter().filter(|_| overlay.visible) {
+            let position = match player_query
+                .iter()
+                .find(|(_, player)| player.id == cursor.player)
+            {
+                Some((transform, _)) => transform.translation.truncate(),
+                None => continue,
+            };
+
+            for (angle, color) in [
+                (cursor.computed_angle, graphics::WHITE),
+                (cursor.actual_angle(), graphics::SPACESHIP_RED),
+            ] {
+                lines.push(Line::new(
+                    position,
+                    position + Vec2::from_angle(angle) * AIM_LINE,
+                    color,
+                ));
+            }
+        }
+
+        draw_lines(&mut commands, &mut gizmo_query, lines, AimGizmo);
+    }
+}
+
+/// A straight line drawn with a thin sprite.
+#[derive(Clone, Copy)]
+struct Line {
+    from: Vec2,
+    to: Vec2,
+    color: Color,
+}
+
+impl Line {
+    fn new(from: Vec2, to: Vec2, color: &str) -> Self {
+        Self {
+            from,
+            to,
+            color: Color::hex(color).unwrap(),
+        }
+    }
+
+    /// Halfway between both ends, turned along the line.
+    fn transform(&self) -> Transform {
+        let offset = self.to - self.from;
+        let mut transform = Transform::from_translation(
+            ((self.from + self.to) / 2.).extend(5.),
+        );
+        transform.rotation = Quat::from_rotation_z(offset.y.atan2(offset.x));
+        transform
+    }
+
+    fn sprite(&self) -> Sprite {
+        Sprite {
+            color: self.color,
+            custom_size: Some(Vec2::new(
+                self.from.distance(self.to),
+                GIZMO_WIDTH,
+            )),
+            ..Default::default()
+        }
+    }
+}
+
+/// The four edges of a `size` box centered on `center`.
+fn outline(center: Vec2, size: Vec2, color: &str) -> [Line; 4] {
+    let half = size / 2.;
+    let corners = [
+        center + Vec2::new(-half.x, -half.y),
+        center + Vec2::new(half.x, -half.y),
+        center + Vec2::new(half.x, half.y),
+        center + Vec2::new(-half.x, half.y),
+    ];
+
+    [0, 1, 2, 3].map(|i| Line::new(corners[i], corners[(i + 1) % 4], color))
+}
+
+/// Shows `lines` with the sprites tagged `marker`, moving the ones drawn
+/// last frame into place, spawning more when there are too few and hiding
+/// those left over.
+fn draw_lines<M: Component + Copy>(
+    commands: &mut Commands,
+    gizmo_query: &mut GizmoQuery<M>,
+    lines: Vec<Line>,
+    marker: M,
+) {
+    let mut lines = lines.into_iter().filter(|line| line.from != line.to);
+
+    for (mut transform, mut sprite, mut visibility) in gizmo_query.iter_mut() {
+        match lines.next() {
+            Some(line) => {
+                *transform = line.transform();
+                *sprite = line.sprite();
+                visibility.is_visible = true;
+            }
+            None if visibility.is_visible => visibility.is_visible = false,
+            None => {}
+        }
+    }
+
+    for line in lines {
+        spawn_line(commands, line, marker);
+    }
+}
+
+/// Draws the outline of a `size` box centered on `center`, tagged with
+/// `marker` so it can be cleared along with the rest of its kind.
+pub fn spawn_outline(
+    commands: &mut Commands,
+    center: Vec2,
+    size: Vec2,
+    color: &str,
+    marker: impl Component + Copy,
+) {
+    for line in outline(center, size, color) {
+        if line.from != line.to {
+            spawn_line(commands, line, marker);
+        }
+    }
+}
+
+fn spawn_line(commands: &mut Commands, line: Line, marker: impl Component) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: line.sprite(),
+            transform: line.transform(),
+            ..Default::default()
+        })
+        .insert(marker);
 }
@@ -0,0 +1,927 @@
+use std::{fs, marker::PhantomData, mem, path::Path};
+
+use bevy::{ecs::system::SystemParam, input::mouse::MouseWheel, prelude::*};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    boss::BOSSES,
+    camera::{self, CameraEffects, MainCamera, VIEW_SIZE},
+    common::Playfield,
+    debug, enemies,
+    graphics::{self, TexturesSheets},
+    obstacles::ObstaclePlugin,
+    pickups::{self, PickupKind},
+    run::GameMode,
+    stage::{Campaign, Cue, Stage, StageEvent, Victory},
+    state::{self, GameState},
+};
+
+/// Where a new stage is saved, relative to where the game runs from.
+const DEFAULT_PATH: &str = "assets/stages/custom.stage.ron";
+/// Switches between the editor and trying the stage out.
+const TRY_OUT_KEY: KeyCode = KeyCode::F5;
+
+/// World units per second the view pans at, before zooming.
+const PAN_SPEED: f32 = 900.;
+/// How much each notch of the mouse wheel zooms, and how far it goes.
+const ZOOM_STEP: f32 = 1.1;
+const ZOOM_RANGE: (f32, f32) = (0.25, 8.);
+/// Room left around the playfield when the view first frames it.
+const FRAME_MARGIN: f32 = 1.05;
+
+/// Side of the square dragged to resize a collider.
+const HANDLE_SIZE: f32 = 14.;
+/// Smallest a collider can be resized to.
+const MIN_COLLIDER: f32 = 8.;
+/// Longest side of the sprites shown in the palette.
+const THUMBNAIL: f32 = 40.;
+
+/// A sprite or line drawn for the stage this frame, replaced on the next.
+#[derive(Component, Clone, Copy)]
+struct EditorShape;
+
+/// The stage being edited, kept while it's tried out.
+struct Draft {
+    stage: Stage,
+    path: String,
+    /// Outcome of the last load or save.
+    status: String,
+    /// Index in the timeline of the cue being edited.
+    selected: Option<usize>,
+    /// Seconds into the stage new cues happen at.
+    at: f32,
+    /// The stage as last tried out, overwritten by the next try.
+    trial: Option<Handle<Stage>>,
+}
+
+impl Default for Draft {
+    fn default() -> Self {
+        Self {
+            stage: Stage {
+                name: "Custom".to_string(),
+                timeline: Vec::new(),
+                victory: Victory::Clear,
+            },
+            path: DEFAULT_PATH.to_string(),
+            status: String::new(),
+            selected: None,
+            at: 0.,
+            trial: None,
+        }
+    }
+}
+
+impl Draft {
+    fn load(&mut self) {
+        let loaded = fs::read_to_string(&self.path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                ron::from_str::<Stage>(&contents).map_err(|err| err.to_string())
+            });
+
+        self.status = match loaded {
+            Ok(stage) => {
+                self.stage = stage;
+                self.selected = None;
+                format!("Loaded {}", self.path)
+            }
+            Err(err) => format!("Failed to load {}: {}", self.path, err),
+        };
+    }
+
+    fn save(&mut self) {
+        let path = Path::new(&self.path);
+
+        let saved =
+            ron::ser::to_string_pretty(&self.sorted(), Default::default())
+                .map_err(|err| err.to_string())
+                .and_then(|contents| {
+                    path.parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|_| fs::write(path, contents))
+                        .map_err(|err| err.to_string())
+                });
+
+        self.status = match saved {
+            Ok(()) => format!("Saved {}", self.path),
+            Err(err) => format!("Failed to save {}: {}", self.path, err),
+        };
+    }
+
+    /// The stage as it would be loaded from its file.
+    fn sorted(&self) -> Stage {
+        let mut stage = self.stage.clone();
+        stage.sort_timeline();
+        stage
+    }
+
+    fn add(&mut self, event: StageEvent) {
+        self.stage.timeline.push(Cue { at: self.at, event });
+        self.selected = Some(self.stage.timeline.len() - 1);
+    }
+
+    fn remove_selected(&mut self) {
+        if let Some(selected) = self.selected.take() {
+            self.stage.timeline.remove(selected);
+        }
+    }
+
+    /// The topmost cue whose footprint is under `cursor`.
+    fn pick(&self, cursor: Vec2, field: Vec2) -> Option<usize> {
+        self.stage.timeline.iter().rposition(|cue| {
+            match (position(&cue.event), footprint(&cue.event)) {
+                (Some(position), Some(size)) => {
+                    ((position * field - cursor).abs() * 2.).cmple(size).all()
+                }
+                _ => false,
+            }
+        })
+    }
+
+    /// Corner of the selected obstacle's collider, dragged to resize it.
+    fn handle(&self, field: Vec2) -> Option<Vec2> {
+        let cue = self.stage.timeline.get(self.selected?)?;
+
+        match cue.event {
+            StageEvent::Obstacle { .. } => Some(
+                position(&cue.event)? * field + footprint(&cue.event)? / 2.,
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// What clicking an empty spot puts down.
+#[derive(Clone, Copy, PartialEq)]
+enum Brush {
+    Obstacle(usize),
+    Enemy(usize),
+    Pickup(PickupKind),
+}
+
+impl Brush {
+    fn event(self, position: Vec2) -> StageEvent {
+        match self {
+            Brush::Obstacle(sprite) => StageEvent::Obstacle {
+                sprite,
+                position,
+                collider: None,
+            },
+            Brush::Enemy(ship) => StageEvent::Enemy { ship, position },
+            Brush::Pickup(kind) => StageEvent::Pickup { kind, position },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Drag {
+    /// Keeps the cue at this offset from the cursor.
+    Move(Vec2),
+    Resize,
+}
+
+#[derive(Default)]
+struct Tools {
+    /// Nothing is put down while it's `None`, only picked.
+    brush: Option<Brush>,
+    drag: Option<Drag>,
+}
+
+/// Leaves the editor, to try the stage out or for good.
+#[derive(SystemParam)]
+struct Exit<'w, 's> {
+    state: ResMut<'w, State<GameState>>,
+    keyboard: ResMut<'w, Input<KeyCode>>,
+    mode: ResMut<'w, GameMode>,
+    campaign: ResMut<'w, Campaign>,
+    stages: ResMut<'w, Assets<Stage>>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl Exit<'_, '_> {
+    /// Plays the stage as it's laid out right now, alone. Whatever mode
+    /// was last picked from the menu, a stage is tried out with one ship.
+    fn try_out(&mut self, draft: &mut Draft) {
+        *self.mode = GameMode::Solo;
+
+        let stage = draft.sorted();
+        let handle = match draft.trial.clone() {
+            Some(handle) => {
+                self.stages.set_untracked(&handle, stage);
+                handle
+            }
+            None => self.stages.add(stage),
+        };
+        draft.trial = Some(handle.clone());
+        self.campaign.try_out(handle);
+
+        self.keyboard.clear();
+        self.state.set(GameState::Level).unwrap();
+    }
+
+    fn leave(&mut self) {
+        self.campaign.stop();
+
+        self.keyboard.clear();
+        self.state.set(GameState::MainMenu).unwrap();
+    }
+}
+
+/// Sprites of the atlases, as egui draws them.
+struct Palette {
+    obstacles: egui::TextureId,
+    entities: egui::TextureId,
+    ui: egui::TextureId,
+}
+
+/// Lays out stages with the mouse, saves them, and tries them out on the
+/// spot. Debug builds only, from the main menu.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Draft>()
+            .init_resource::<Tools>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Editor)
+                    .with_system(Self::frame_playfield),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .with_system(Self::panels)
+                    .with_system(Self::edit_with_mouse.after(Self::panels))
+                    .with_system(Self::edit_with_keys.after(Self::panels))
+                    .with_system(
+                        Self::draw
+                            .after(Self::edit_with_mouse)
+                            .after(Self::edit_with_keys),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Editor)
+                    .with_system(Self::release_camera)
+                    .with_system(state::despawn_with::<EditorShape>),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(Self::back_to_editor),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(Self::back_to_editor),
+            );
+    }
+}
+
+impl EditorPlugin {
+    /// Takes the camera over and shows the whole playfield.
+    fn frame_playfield(
+        mut windows: ResMut<Windows>,
+        mut effects: ResMut<CameraEffects>,
+        playfield: Res<Playfield>,
+        mut camera_query: Query<
+            (&mut Transform, &mut OrthographicProjection),
+            With<MainCamera>,
+        >,
+    ) {
+        windows
+            .get_primary_mut()
+            .unwrap()
+            .set_cursor_visibility(true);
+
+        effects.set_free(true);
+
+        for (mut transform, mut projection) in camera_query.iter_mut() {
+            projection.scale =
+                (playfield.size() / VIEW_SIZE).max_element() * FRAME_MARGIN;
+            transform.translation =
+                playfield.center().extend(transform.translation.z);
+        }
+    }
+
+    fn release_camera(mut effects: ResMut<CameraEffects>) {
+        effects.set_free(false);
+    }
+
+    /// While a stage is tried out, goes back to editing it.
+    fn back_to_editor(
+        mut keyboard: ResMut<Input<KeyCode>>,
+        campaign: Res<Campaign>,
+        mut state: ResMut<State<GameState>>,
+    ) {
+        if campaign.is_trial() && keyboard.just_pressed(TRY_OUT_KEY) {
+            keyboard.clear();
+            state.set(GameState::Editor).unwrap();
+        }
+    }
+
+    fn panels(
+        mut egui_context: ResMut<EguiContext>,
+        mut draft: ResMut<Draft>,
+        mut tools: ResMut<Tools>,
+        mut exit: Exit,
+        ts: Res<TexturesSheets>,
+        atlases: Res<Assets<TextureAtlas>>,
+        mut palette: Local<Option<Palette>>,
+    ) {
+        let atlases = match (
+            atlases.get(&ts.obstacles),
+            atlases.get(&ts.entities),
+            atlases.get(&ts.ui),
+        ) {
+            (Some(obstacles), Some(entities), Some(ui)) => {
+                (obstacles, entities, ui)
+            }
+            _ => return,
+        };
+
+        // Registered once, the atlases' textures never change.
+        let palette = palette.get_or_insert_with(|| Palette {
+            obstacles: egui_context.add_image(atlases.0.texture.clone()),
+            entities: egui_context.add_image(atlases.1.texture.clone()),
+            ui: egui_context.add_image(atlases.2.texture.clone()),
+        });
+
+        let mut try_out = exit.keyboard.just_pressed(TRY_OUT_KEY);
+        let mut leave = false;
+
+        let ctx = egui_context.ctx_mut();
+
+        egui::Window::new("Stage").show(ctx, |ui| {
+            stage_ui(ui, &mut draft);
+            ui.separator();
+
+            palette_ui(ui, &mut tools, palette, atlases);
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                try_out |= ui.button("Try out (F5)").clicked();
+                leave = ui.button("Main menu").clicked();
+            });
+        });
+
+        egui::Window::new("Timeline").show(ctx, |ui| {
+            timeline_ui(ui, &mut draft);
+        });
+
+        if draft.selected.is_some() {
+            egui::Window::new("Cue").show(ctx, |ui| {
+                cue_ui(ui, &mut draft);
+            });
+        }
+
+        if try_out {
+            exit.try_out(&mut draft);
+        } else if leave {
+            exit.leave();
+        }
+    }
+
+    /// Clicking a cue picks it and starts moving it, clicking an empty spot
+    /// puts down whatever the brush holds.
+    fn edit_with_mouse(
+        mouse: Res<Input<MouseButton>>,
+        windows: Res<Windows>,
+        mut egui_context: ResMut<EguiContext>,
+        camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+        playfield: Res<Playfield>,
+        mut draft: ResMut<Draft>,
+        mut tools: ResMut<Tools>,
+    ) {
+        if !mouse.pressed(MouseButton::Left) {
+            tools.drag = None;
+        }
+
+        let (camera, camera_transform) = camera_query.single();
+        let window = windows.get_primary().unwrap();
+        let cursor = match window.cursor_position().and_then(|position| {
+            camera::window_to_world(camera, camera_transform, window, position)
+        }) {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let field = playfield.size();
+
+        if mouse.just_pressed(MouseButton::Left)
+            && !egui_context.ctx_mut().wants_pointer_input()
+        {
+            let over_handle = draft.handle(field).is_some_and(|handle| {
+                ((handle - cursor).abs() * 2.)
+                    .cmple(Vec2::splat(HANDLE_SIZE))
+                    .all()
+            });
+
+            tools.drag = if over_handle {
+                Some(Drag::Resize)
+            } else if let Some(picked) = draft.pick(cursor, field) {
+                let position = position(&draft.stage.timeline[picked].event);
+                draft.selected = Some(picked);
+                position.map(|position| Drag::Move(position * field - cursor))
+            } else if let Some(brush) = tools.brush {
+                draft.add(brush.event(cursor / field));
+                Some(Drag::Move(Vec2::ZERO))
+            } else {
+                draft.selected = None;
+                None
+            };
+        }
+
+        let (drag, selected) = match (tools.drag, draft.selected) {
+            (Some(drag), Some(selected)) => (drag, selected),
+            _ => return,
+        };
+        let event = &mut draft.stage.timeline[selected].event;
+
+        match (drag, event) {
+            (
+                Drag::Resize,
+                StageEvent::Obstacle {
+                    position, collider, ..
+                },
+            ) => {
+                let size = (*position * field - cursor).abs() * 2.;
+                *collider = Some(size.max(Vec2::splat(MIN_COLLIDER)));
+            }
+
+            (Drag::Move(offset), event) => {
+                if let Some(position) = position_mut(event) {
+                    *position = ((cursor + offset) / field)
+                        .clamp(Vec2::ZERO, Vec2::ONE);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Arrows or WASD pan the view, the mouse wheel zooms it, and Delete
+    /// removes the cue being edited.
+    fn edit_with_keys(
+        keyboard: Res<Input<KeyCode>>,
+        mut wheel_evr: EventReader<MouseWheel>,
+        time: Res<Time>,
+        mut egui_context: ResMut<EguiContext>,
+        mut draft: ResMut<Draft>,
+        mut camera_query: Query<
+            (&mut Transform, &mut OrthographicProjection),
+            With<MainCamera>,
+        >,
+    ) {
+        let ctx = egui_context.ctx_mut();
+        let (mut transform, mut projection) = camera_query.single_mut();
+
+        let notches: f32 = wheel_evr.iter().map(|ev| ev.y.signum()).sum();
+        if !ctx.wants_pointer_input() {
+            projection.scale = (projection.scale * ZOOM_STEP.powf(-notches))
+                .clamp(ZOOM_RANGE.0, ZOOM_RANGE.1);
+        }
+
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let pressed = |keys: [KeyCode; 2]| {
+            if keys.iter().any(|key| keyboard.pressed(*key)) {
+                1.
+            } else {
+                0.
+            }
+        };
+        let direction = Vec2::new(
+            pressed([KeyCode::Right, KeyCode::D])
+                - pressed([KeyCode::Left, KeyCode::A]),
+            pressed([KeyCode::Up, KeyCode::W])
+                - pressed([KeyCode::Down, KeyCode::S]),
+        );
+        let pan =
+            direction * PAN_SPEED * projection.scale * time.delta_seconds();
+        transform.translation += pan.extend(0.);
+
+        if keyboard.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+            draft.remove_selected();
+        }
+    }
+
+    /// Draws every cue that puts something on the playfield, the outline
+    /// of what it collides with, and the playfield's own edges.
+    fn draw(
+        mut commands: Commands,
+        shape_query: Query<Entity, With<EditorShape>>,
+        draft: Res<Draft>,
+        ts: Res<TexturesSheets>,
+        playfield: Res<Playfield>,
+    ) {
+        for e in shape_query.iter() {
+            commands.entity(e).despawn();
+        }
+
+        let field = playfield.size();
+        debug::spawn_outline(
+            &mut commands,
+            playfield.center(),
+            field,
+            graphics::WHITE,
+            EditorShape,
+        );
+
+        for (i, cue) in draft.stage.timeline.iter().enumerate() {
+            let (position, size) =
+                match (position(&cue.event), footprint(&cue.event)) {
+                    (Some(position), Some(size)) => (position * field, size),
+                    _ => continue,
+                };
+
+            let (texture_atlas, index, color) = match cue.event {
+                StageEvent::Obstacle { sprite, .. } => {
+                    (ts.obstacles.clone(), sprite, graphics::WHITE)
+                }
+                StageEvent::Enemy { ship, .. } => (
+                    ts.entities.clone(),
+                    enemies::SPRITES.start() + ship,
+                    graphics::WHITE,
+                ),
+                StageEvent::Pickup { kind, .. } => {
+                    let (index, color) = kind.icon();
+                    (ts.ui.clone(), index, color)
+                }
+                _ => continue,
+            };
+
+            let mut sprite = TextureAtlasSprite::new(index);
+            sprite.color = Color::hex(color).unwrap();
+            if matches!(cue.event, StageEvent::Pickup { .. }) {
+                sprite.custom_size = Some(pickups::SIZE);
+            }
+
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    sprite,
+                    texture_atlas,
+                    transform: Transform::from_translation(position.extend(1.)),
+                    ..Default::default()
+                })
+                .insert(EditorShape);
+
+            let outline = if draft.selected == Some(i) {
+                graphics::EXPLOSION_ORANGE
+            } else {
+                graphics::ASTEROID_GREY
+            };
+            debug::spawn_outline(
+                &mut commands,
+                position,
+                size,
+                outline,
+                EditorShape,
+            );
+        }
+
+        if let Some(handle) = draft.handle(field) {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::hex(graphics::EXPLOSION_ORANGE).unwrap(),
+                        custom_size: Some(Vec2::splat(HANDLE_SIZE)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(handle.extend(5.)),
+                    ..Default::default()
+                })
+                .insert(EditorShape);
+        }
+    }
+}
+
+/// Name, victory condition and file of the stage.
+fn stage_ui(ui: &mut egui::Ui, draft: &mut Draft) {
+    ui.horizontal(|ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut draft.stage.name);
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Victory");
+
+        let victory = &mut draft.stage.victory;
+        egui::ComboBox::from_id_source("victory")
+            .selected_text(victory_name(*victory))
+            .show_ui(ui, |ui| {
+                for choice in
+                    [Victory::Clear, Victory::DefeatBoss, Victory::Survive(60.)]
+                {
+                    let current = mem::discriminant(victory)
+                        == mem::discriminant(&choice);
+                    if ui
+                        .selectable_label(current, victory_name(choice))
+                        .clicked()
+                        && !current
+                    {
+                        *victory = choice;
+                    }
+                }
+            });
+
+        if let Victory::Survive(seconds) = victory {
+            ui.add(
+                egui::DragValue::new(seconds)
+                    .suffix("s")
+                    .clamp_range(0.0..=f32::MAX),
+            );
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("File");
+        ui.text_edit_singleline(&mut draft.path);
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("Load").clicked() {
+            draft.load();
+        }
+        if ui.button("Save").clicked() {
+            draft.save();
+        }
+        if ui.button("New").clicked() {
+            *draft = Draft {
+                path: mem::take(&mut draft.path),
+                ..Default::default()
+            };
+        }
+    });
+
+    if !draft.status.is_empty() {
+        ui.label(&draft.status);
+    }
+}
+
+/// What clicking an empty spot puts down, picked from the atlases.
+fn palette_ui(
+    ui: &mut egui::Ui,
+    tools: &mut Tools,
+    palette: &Palette,
+    (obstacles, entities, icons): (&TextureAtlas, &TextureAtlas, &TextureAtlas),
+) {
+    if ui
+        .selectable_label(tools.brush.is_none(), "Pick only")
+        .clicked()
+    {
+        tools.brush = None;
+    }
+
+    let mut brushes: Vec<(Brush, egui::TextureId, &TextureAtlas, usize, &str)> =
+        Vec::new();
+
+    for sprite in 0..obstacles.len() {
+        if ObstaclePlugin::for_large_obstacle(sprite).is_some() {
+            brushes.push((
+                Brush::Obstacle(sprite),
+                palette.obstacles,
+                obstacles,
+                sprite,
+                graphics::WHITE,
+            ));
+        }
+    }
+
+    for (ship, index) in enemies::SPRITES.enumerate() {
+        brushes.push((
+            Brush::Enemy(ship),
+            palette.entities,
+            entities,
+            index,
+            graphics::WHITE,
+        ));
+    }
+
+    for kind in PickupKind::ALL {
+        let (index, color) = kind.icon();
+        brushes.push((Brush::Pickup(kind), palette.ui, icons, index, color));
+    }
+
+    ui.horizontal_wrapped(|ui| {
+        for (brush, texture, atlas, index, color) in brushes {
+            let button = thumbnail(texture, atlas, index, color)
+                .selected(tools.brush == Some(brush));
+
+            if ui.add(button).clicked() {
+                tools.brush = Some(brush);
+            }
+        }
+    });
+}
+
+fn thumbnail(
+    texture: egui::TextureId,
+    atlas: &TextureAtlas,
+    index: usize,
+    color: &str,
+) -> egui::ImageButton {
+    let rect = atlas.textures[index];
+    let size = rect.max - rect.min;
+    let size = size / size.max_element() * THUMBNAIL;
+
+    let uv = egui::Rect::from_min_max(
+        egui::pos2(rect.min.x / atlas.size.x, rect.min.y / atlas.size.y),
+        egui::pos2(rect.max.x / atlas.size.x, rect.max.y / atlas.size.y),
+    );
+    let [r, g, b, a] = Color::hex(color).unwrap().as_rgba_f32();
+
+    egui::ImageButton::new(texture, egui::vec2(size.x, size.y))
+        .uv(uv)
+        .tint(egui::Rgba::from_rgba_unmultiplied(r, g, b, a))
+}
+
+/// Every cue in the order they happen, and the ones not placed with the
+/// mouse.
+fn timeline_ui(ui: &mut egui::Ui, draft: &mut Draft) {
+    ui.horizontal(|ui| {
+        ui.label("New cues at");
+        ui.add(
+            egui::DragValue::new(&mut draft.at)
+                .suffix("s")
+                .clamp_range(0.0..=f32::MAX),
+        );
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("+ Squad").clicked() {
+            draft.add(StageEvent::Squad(3));
+        }
+        if ui.button("+ Boss").clicked() {
+            draft.add(StageEvent::Boss(BOSSES[0].name.to_string()));
+        }
+        if ui.button("+ Background").clicked() {
+            draft.add(StageEvent::Background(graphics::BG_COLOR.to_string()));
+        }
+    });
+
+    ui.separator();
+
+    let mut order: Vec<usize> = (0..draft.stage.timeline.len()).collect();
+    order.sort_by(|&a, &b| {
+        let timeline = &draft.stage.timeline;
+        timeline[a].at.total_cmp(&timeline[b].at)
+    });
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for i in order {
+            let cue = &draft.stage.timeline[i];
+            let label = format!("{:>6.1}s  {}", cue.at, describe(&cue.event));
+
+            if ui
+                .selectable_label(draft.selected == Some(i), label)
+                .clicked()
+            {
+                draft.selected = Some(i);
+            }
+        }
+    });
+}
+
+/// Everything about the cue being edited.
+fn cue_ui(ui: &mut egui::Ui, draft: &mut Draft) {
+    let cue = match draft.selected {
+        Some(selected) => &mut draft.stage.timeline[selected],
+        None => return,
+    };
+
+    ui.label(describe(&cue.event));
+    ui.horizontal(|ui| {
+        ui.label("At");
+        ui.add(
+            egui::DragValue::new(&mut cue.at)
+                .suffix("s")
+                .clamp_range(0.0..=f32::MAX),
+        );
+    });
+
+    if let Some(position) = position_mut(&mut cue.event) {
+        ui.horizontal(|ui| {
+            ui.label("Position");
+            for axis in [&mut position.x, &mut position.y] {
+                ui.add(
+                    egui::DragValue::new(axis)
+                        .speed(0.005)
+                        .clamp_range(0.0..=1.0),
+                );
+            }
+        });
+    }
+
+    match &mut cue.event {
+        StageEvent::Obstacle {
+            sprite, collider, ..
+        } => {
+            let mut size = collider.unwrap_or_else(|| {
+                ObstaclePlugin::for_large_obstacle(*sprite)
+                    .map_or(Vec2::ZERO, Vec2::from)
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Collider");
+                let mut resized = false;
+                for side in [&mut size.x, &mut size.y] {
+                    resized |= ui
+                        .add(
+                            egui::DragValue::new(side)
+                                .clamp_range(MIN_COLLIDER..=f32::MAX),
+                        )
+                        .changed();
+                }
+                if resized {
+                    *collider = Some(size);
+                }
+
+                if ui.button("Reset").clicked() {
+                    *collider = None;
+                }
+            });
+        }
+
+        StageEvent::Squad(size) => {
+            ui.horizontal(|ui| {
+                ui.label("Enemies");
+                ui.add(egui::DragValue::new(size).clamp_range(1..=32));
+            });
+        }
+
+        StageEvent::Boss(name) => {
+            egui::ComboBox::from_id_source("boss")
+                .selected_text(name.as_str())
+                .show_ui(ui, |ui| {
+                    for def in BOSSES {
+                        ui.selectable_value(
+                            name,
+                            def.name.to_string(),
+                            def.name,
+                        );
+                    }
+                });
+        }
+
+        StageEvent::Background(color) => {
+            ui.horizontal(|ui| {
+                ui.label("Color");
+                ui.text_edit_singleline(color);
+            });
+        }
+
+        StageEvent::Enemy { .. } | StageEvent::Pickup { .. } => {}
+    }
+
+    if ui.button("Delete").clicked() {
+        draft.remove_selected();
+    }
+}
+
+fn victory_name(victory: Victory) -> &'static str {
+    match victory {
+        Victory::Clear => "Clear",
+        Victory::DefeatBoss => "Defeat the boss",
+        Victory::Survive(_) => "Survive",
+    }
+}
+
+fn describe(event: &StageEvent) -> String {
+    match event {
+        StageEvent::Obstacle { sprite, .. } => format!("Obstacle {}", sprite),
+        StageEvent::Enemy { ship, .. } => format!("Enemy {}", ship),
+        StageEvent::Squad(size) => format!("Squad of {}", size),
+        StageEvent::Boss(name) => format!("Boss {}", name),
+        StageEvent::Pickup { kind, .. } => format!("Pickup {:?}", kind),
+        StageEvent::Background(color) => format!("Background #{}", color),
+    }
+}
+
+/// Where an event puts something, as a fraction of the playfield.
+fn position(event: &StageEvent) -> Option<Vec2> {
+    match event {
+        StageEvent::Obstacle { position, .. }
+        | StageEvent::Enemy { position, .. }
+        | StageEvent::Pickup { position, .. } => Some(*position),
+        _ => None,
+    }
+}
+
+fn position_mut(event: &mut StageEvent) -> Option<&mut Vec2> {
+    match event {
+        StageEvent::Obstacle { position, .. }
+        | StageEvent::Enemy { position, .. }
+        | StageEvent::Pickup { position, .. } => Some(position),
+        _ => None,
+    }
+}
+
+/// Size of what an event puts down, what the mouse picks it by.
+fn footprint(event: &StageEvent) -> Option<Vec2> {
+    match event {
+        StageEvent::Obstacle {
+            sprite, collider, ..
+        } => Stage::obstacle_collider(*sprite, *collider).map(Vec2::from),
+        StageEvent::Enemy { .. } => Some(enemies::SIZE),
+        StageEvent::Pickup { .. } => Some(pickups::SIZE),
+        _ => None,
+    }
+}
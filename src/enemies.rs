@@ -0,0 +1,251 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    ai::{AiState, AiSystem, Brain, Steering, Temperament},
+    common::{Collider, Health, Playfield, Threat},
+    events::ShotFired,
+    graphics::{self, TexturesSheets},
+    minimap::MinimapBlip,
+    obstacles::Spawner,
+    patterns::{self, Emission, Patterns},
+    projectiles::ENEMY,
+    run::GameMode,
+    simulation::{GameRng, Interpolated, SimulationApp, SimulationTime},
+    state::{GameState, LevelScoped},
+};
+
+pub const TEMPERAMENT: Temperament = Temperament {
+    sight: 500.,
+    attack_range: 320.,
+    cowardice: 0.3,
+};
+
+const MAX_SPEED: f32 = 140.;
+const MAX_FORCE: f32 = 220.;
+const HEALTH: f32 = 40.;
+/// Fired while attacking.
+const PATTERN: &str = "enemy_shot";
+
+/// Enemies spawned with a wave at most, and how far from the players they
+/// show up.
+const MAX_SQUAD: u32 = 6;
+const SPAWN_DISTANCE: f32 = 600.;
+const SPAWN_ATTEMPTS: usize = 32;
+
+/// Sprites of the enemy ships in the entities sheet.
+pub const SPRITES: std::ops::RangeInclusive<usize> = 4..=7;
+/// Size of an enemy's collider.
+pub const SIZE: Vec2 = Vec2::new(90., 80.);
+
+#[derive(Component)]
+pub struct Enemy {
+    pub sprite_index: usize,
+    /// How far into its pattern the enemy is, only moving on while
+    /// attacking.
+    pub gun: Emission,
+}
+
+/// Enemies joining the given wave; none in versus, where the players are
+/// each other's enemies.
+pub fn squad_size(wave: u32, mode: GameMode) -> u32 {
+    match mode {
+        GameMode::Versus { .. } => 0,
+        _ => wave.div_ceil(2).min(MAX_SQUAD),
+    }
+}
+
+/// Spawns `size` enemies away from the positions in `avoid`.
+pub fn spawn_squad(
+    commands: &mut Commands,
+    rng: &mut GameRng,
+    playfield: &Playfield,
+    ts: &TexturesSheets,
+    size: u32,
+    avoid: &[Vec2],
+) {
+    let margin = 100.;
+
+    for _ in 0..size {
+        let mut position = Vec2::ZERO;
+        for _ in 0..SPAWN_ATTEMPTS {
+            position = Vec2::new(
+                rng.gen_range(margin..playfield.width - margin),
+                rng.gen_range(margin..playfield.height - margin),
+            );
+
+            if avoid
+                .iter()
+                .all(|seat| seat.distance(position) > SPAWN_DISTANCE)
+            {
+                break;
+            }
+        }
+
+        let sprite_index = rng.gen_range(SPRITES) - SPRITES.start();
+        spawn_fresh(commands, ts, sprite_index, position);
+    }
+}
+
+/// Spawns a new enemy at `position`, patrolling around it. `sprite_index`
+/// picks one of the enemy ships, from `0`.
+pub fn spawn_fresh(
+    commands: &mut Commands,
+    ts: &TexturesSheets,
+    sprite_index: usize,
+    position: Vec2,
+) -> Entity {
+    let sprite_index = SPRITES.start() + sprite_index % SPRITES.clone().count();
+
+    spawn_enemy(
+        commands,
+        ts,
+        Enemy {
+            sprite_index,
+            gun: Emission::default(),
+        },
+        Transform::from_translation(position.extend(1.)),
+        Health::new(HEALTH),
+        Steering::new(MAX_SPEED, MAX_FORCE),
+        Brain::new(TEMPERAMENT, position),
+    )
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    ts: &TexturesSheets,
+    enemy: Enemy,
+    transform: Transform,
+    health: Health,
+    steering: Steering,
+    brain: Brain,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite::new(enemy.sprite_index),
+            texture_atlas: ts.entities.clone(),
+            transform,
+            ..Default::default()
+        })
+        .insert(enemy)
+        .insert(Collider {
+            width: SIZE.x,
+            height: SIZE.y,
+            ..Default::default()
+        })
+        .insert(health)
+        .insert(steering)
+        .insert(brain)
+        .insert(Threat)
+        .insert(MinimapBlip::new(graphics::SPACESHIP_RED, 4.))
+        .insert(Interpolated::default())
+        .insert(Name::new("Enemy"))
+        .insert(LevelScoped)
+        .id()
+}
+
+/// Enemy ships, driven by the AI.
+pub struct EnemiesPlugin;
+
+impl Plugin for EnemiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_simulation_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::confine.after(AiSystem::Steer))
+                .with_system(Self::face.after(AiSystem::Steer))
+                .with_system(Self::fire.after(AiSystem::Steer)),
+        );
+    }
+}
+
+impl EnemiesPlugin {
+    /// Keeps enemies within the world, killing their speed against its
+    /// edges.
+    fn confine(
+        playfield: Res<Playfield>,
+        mut enemy_query: Query<
+            (&mut Transform, &Collider, &mut Steering),
+            With<Enemy>,
+        >,
+    ) {
+        for (mut transform, collider, mut steering) in enemy_query.iter_mut() {
+            let half = Vec2::from(*collider) / 2.;
+            let position = transform.translation.truncate();
+            let confined = position.clamp(half, playfield.size() - half);
+
+            if confined.x != position.x {
+                steering.velocity.x = 0.;
+            }
+            if confined.y != position.y {
+                steering.velocity.y = 0.;
+            }
+
+            transform.translation = confined.extend(transform.translation.z);
+        }
+    }
+
+    /// Turns ships towards their target while attacking, and along their
+    /// heading otherwise. The sprites face down.
+    fn face(
+        mut enemy_query: Query<
+            (&mut Transform, &Brain, &Steering),
+            With<Enemy>,
+        >,
+    ) {
+        for (mut transform, brain, steering) in enemy_query.iter_mut() {
+            let position = transform.translation.truncate();
+
+            let direction = match (brain.state, brain.target) {
+                (AiState::Attack, Some(target)) => target - position,
+                _ => steering.velocity,
+            };
+
+            if let Some(direction) = direction.try_normalize() {
+                let angle = direction.y.atan2(direction.x);
+                transform.rotation = Quat::from_rotation_z(angle + FRAC_PI_2);
+            }
+        }
+    }
+
+    fn fire(
+        mut spawner: Spawner,
+        patterns: Res<Patterns>,
+        time: Res<SimulationTime>,
+        mut enemy_query: Query<(&Transform, &Collider, &Brain, &mut Enemy)>,
+        mut shot_evw: EventWriter<ShotFired>,
+    ) {
+        let pattern = match patterns.get(PATTERN) {
+            Some(pattern) => pattern,
+            None => return,
+        };
+
+        for (transform, collider, brain, mut enemy) in enemy_query.iter_mut() {
+            let target = match (brain.state, brain.target) {
+                (AiState::Attack, Some(target)) => target,
+                _ => continue,
+            };
+
+            let position = transform.translation.truncate();
+            let offset = target - position;
+            let aim = offset.y.atan2(offset.x);
+
+            let shots =
+                enemy.gun.advance(pattern, time.delta_seconds(), aim, aim);
+            if shots.is_empty() {
+                continue;
+            }
+
+            patterns::fire(
+                &mut spawner,
+                pattern,
+                ENEMY,
+                position,
+                Vec2::splat(collider.height / 2.),
+                &shots,
+            );
+            shot_evw.send(ShotFired);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+use crate::pickups::PickupKind;
+
+pub struct ShotFired;
+
+pub struct ObstacleDestroyed {
+    /// Seat of the player whose shot it was.
+    pub player: usize,
+    pub position: Vec2,
+    /// Where the bullet struck, and the angle it was travelling at.
+    pub impact: Vec2,
+    pub direction: f32,
+    /// Whether the obstacle broke apart into debris rather than vanishing.
+    pub split: bool,
+}
+
+pub struct PlayerHit {
+    pub position: Vec2,
+    pub damage: f32,
+}
+
+/// A ship brought down to zero health.
+pub struct PlayerDestroyed {
+    pub player: usize,
+    /// Seat of the player whose shot it was, if it was one.
+    pub by: Option<usize>,
+}
+
+pub struct EnemyDestroyed {
+    /// Seat of the player whose shot it was.
+    pub player: usize,
+    pub position: Vec2,
+}
+
+/// The core of a boss destroyed, taking the rest of it down.
+pub struct BossDefeated {
+    /// Seat of the player whose shot it was.
+    pub player: usize,
+    pub position: Vec2,
+}
+
+pub struct PickupCollected {
+    pub player: usize,
+    pub kind: PickupKind,
+    pub position: Vec2,
+}
+
+/// A stage of the campaign begins, `stage` counting from 0.
+pub struct StageStarted {
+    pub stage: usize,
+    pub name: String,
+}
+
+/// Drops every gameplay event sent so far, for when ticks are replayed
+/// after a rollback. Their first run already got read, so replayed copies
+/// would only play sounds and effects a second time.
+pub fn clear(world: &mut World) {
+    world.resource_mut::<Events<ShotFired>>().clear();
+    world.resource_mut::<Events<ObstacleDestroyed>>().clear();
+    world.resource_mut::<Events<PlayerHit>>().clear();
+    world.resource_mut::<Events<PlayerDestroyed>>().clear();
+    world.resource_mut::<Events<EnemyDestroyed>>().clear();
+    world.resource_mut::<Events<BossDefeated>>().clear();
+    world.resource_mut::<Events<PickupCollected>>().clear();
+    world.resource_mut::<Events<StageStarted>>().clear();
+}
+
+pub struct EventsPlugin;
+
+impl Plugin for EventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShotFired>()
+            .add_event::<ObstacleDestroyed>()
+            .add_event::<PlayerHit>()
+            .add_event::<PlayerDestroyed>()
+            .add_event::<EnemyDestroyed>()
+            .add_event::<BossDefeated>()
+            .add_event::<PickupCollected>()
+            .add_event::<StageStarted>();
+    }
+}
@@ -0,0 +1,331 @@
+use std::marker::PhantomData;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    camera::ScreenAnchor,
+    graphics::{self, Fonts},
+    highscores::{HighScore, HighScores, MAX_NAME_LENGTH},
+    labels,
+    netcode::NetSession,
+    player,
+    run::{GameMode, Lives, Round, Score, Wave},
+    settings::Settings,
+    simulation::{GameRng, SimulationTime},
+    stage::{Campaign, Progress},
+    state::{self, GameState},
+};
+
+const NAME_PROMPT: &str = "Press Enter to confirm";
+const RESTART_PROMPT: &str = "Enter to play again, Escape for the menu";
+const DEFAULT_NAME: &str = "PILOT";
+
+#[derive(Component)]
+pub struct GameOverScreen;
+
+/// Text, size, color and height of a line of the game-over screen.
+type Line = (String, f32, &'static str, f32, Option<GameOverLine>);
+
+/// Lines of the game-over screen that change while it is shown.
+#[derive(Component)]
+pub enum GameOverLine {
+    Name,
+    Prompt,
+}
+
+/// Name being typed for a score that made it into the high-score table.
+#[derive(Default)]
+struct NameEntry {
+    name: String,
+    active: bool,
+}
+
+impl NameEntry {
+    fn format(name: &str) -> String {
+        format!("{:_<width$}", name, width = MAX_NAME_LENGTH)
+    }
+}
+
+/// What ends a run: the last life lost, a versus round decided, or the
+/// last stage of the campaign cleared.
+#[derive(SystemParam)]
+struct RunEnd<'w, 's> {
+    lives: Res<'w, Lives>,
+    round: Res<'w, Round>,
+    mode: Res<'w, GameMode>,
+    settings: Res<'w, Settings>,
+    campaign: Res<'w, Campaign>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl RunEnd<'_, '_> {
+    fn is_over(&self) -> bool {
+        let round_over =
+            self.mode.is_versus() && self.round.is_over(&self.settings.rules);
+
+        self.lives.0 == 0 || round_over || self.campaign.is_complete()
+    }
+}
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NameEntry>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(Self::detect_game_over),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(Self::show_game_over)
+                    .with_system(Self::show_scoreboard),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(Self::enter_name)
+                    .with_system(Self::update_lines.after(Self::enter_name))
+                    .with_system(Self::restart.after(Self::enter_name)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver)
+                    .with_system(state::despawn_with::<GameOverScreen>),
+            );
+    }
+}
+
+impl GameOverPlugin {
+    /// Online, only once the peer's inputs can't take the last life back.
+    fn detect_game_over(
+        mut state: ResMut<State<GameState>>,
+        run_end: RunEnd,
+        session: Option<Res<NetSession>>,
+        time: Res<SimulationTime>,
+    ) {
+        if session.is_some_and(|session| !session.is_settled(time.tick())) {
+            return;
+        }
+
+        if run_end.is_over() {
+            // Wins over anything else queued this frame, like opening the
+            // settings.
+            state.overwrite_set(GameState::GameOver).unwrap();
+        }
+    }
+
+    fn show_game_over(
+        mut commands: Commands,
+        fonts: Res<Fonts>,
+        score: Res<Score>,
+        progress: Progress,
+        high_scores: Res<HighScores>,
+        mode: Res<GameMode>,
+        mut entry: ResMut<NameEntry>,
+    ) {
+        // A versus round is about frags, and trying out a stage is no run at
+        // all, neither belongs in the high-score table.
+        let versus = mode.is_versus();
+        let entering = !versus
+            && !progress.campaign.is_trial()
+            && high_scores.qualifies(score.0);
+        *entry = NameEntry {
+            name: String::new(),
+            active: entering,
+        };
+
+        let (title, color) = if progress.campaign.is_complete() {
+            ("VICTORY", graphics::PICKUP_GREEN)
+        } else {
+            ("GAME OVER", graphics::SPACESHIP_RED)
+        };
+
+        let mut lines = if versus {
+            Vec::new()
+        } else {
+            vec![
+                (title.to_string(), 72., color, 120., None),
+                (
+                    format!("SCORE {:06}   {}", score.0, progress.label()),
+                    32.,
+                    graphics::WHITE,
+                    40.,
+                    None,
+                ),
+            ]
+        };
+
+        if entering {
+            lines.push((
+                "NEW HIGH SCORE! Type your name".to_string(),
+                24.,
+                graphics::EXPLOSION_ORANGE,
+                -20.,
+                None,
+            ));
+            lines.push((
+                NameEntry::format(""),
+                32.,
+                graphics::WHITE,
+                -60.,
+                Some(GameOverLine::Name),
+            ));
+        }
+
+        let prompt = if entering {
+            NAME_PROMPT
+        } else {
+            RESTART_PROMPT
+        };
+        lines.push((
+            prompt.to_string(),
+            22.,
+            graphics::WHITE,
+            -120.,
+            Some(GameOverLine::Prompt),
+        ));
+
+        spawn_lines(&mut commands, &fonts, lines);
+    }
+
+    /// The winner of a versus round, then frags and deaths of every seat.
+    fn show_scoreboard(
+        mut commands: Commands,
+        fonts: Res<Fonts>,
+        mode: Res<GameMode>,
+        round: Res<Round>,
+    ) {
+        if !mode.is_versus() {
+            return;
+        }
+
+        let (title, color) = match round.winner() {
+            Some(seat) => {
+                (format!("PLAYER {} WINS", seat + 1), player::color(seat))
+            }
+            None => ("DRAW".to_string(), graphics::WHITE),
+        };
+
+        let mut lines = vec![(title, 72., color, 120., None)];
+
+        for (seat, (frags, deaths)) in
+            round.frags.iter().zip(round.deaths.iter()).enumerate()
+        {
+            lines.push((
+                format!(
+                    "P{}   FRAGS {:>2}   DEATHS {:>2}",
+                    seat + 1,
+                    frags,
+                    deaths
+                ),
+                28.,
+                player::color(seat),
+                50. - 36. * seat as f32,
+                None,
+            ));
+        }
+
+        spawn_lines(&mut commands, &fonts, lines);
+    }
+
+    fn enter_name(
+        mut keyboard: ResMut<Input<KeyCode>>,
+        mut chars_evr: EventReader<ReceivedCharacter>,
+        mut entry: ResMut<NameEntry>,
+        mut high_scores: ResMut<HighScores>,
+        score: Res<Score>,
+        wave: Res<Wave>,
+        rng: Res<GameRng>,
+    ) {
+        if !entry.active {
+            chars_evr.clear();
+            return;
+        }
+
+        for ev in chars_evr.iter() {
+            let c = ev.char.to_ascii_uppercase();
+            if (c.is_ascii_alphanumeric() || c == ' ')
+                && entry.name.len() < MAX_NAME_LENGTH
+            {
+                entry.name.push(c);
+            }
+        }
+
+        if keyboard.just_pressed(KeyCode::Back) {
+            entry.name.pop();
+        }
+
+        if keyboard.just_pressed(KeyCode::Return) {
+            let name = match entry.name.trim() {
+                "" => DEFAULT_NAME.to_string(),
+                name => name.to_string(),
+            };
+
+            high_scores.insert(HighScore::new(
+                &name,
+                score.0,
+                wave.0,
+                rng.seed(),
+            ));
+            high_scores.save();
+
+            entry.name = name;
+            entry.active = false;
+            // Don't let the same press restart the game right away.
+            keyboard.clear();
+        }
+    }
+
+    fn update_lines(
+        entry: Res<NameEntry>,
+        mut line_query: Query<(&GameOverLine, &mut Text)>,
+    ) {
+        if !entry.is_changed() {
+            return;
+        }
+
+        for (line, mut text) in line_query.iter_mut() {
+            text.sections[0].value = match (line, entry.active) {
+                (GameOverLine::Name, true) => NameEntry::format(&entry.name),
+                (GameOverLine::Name, false) => entry.name.clone(),
+                (GameOverLine::Prompt, true) => NAME_PROMPT.to_string(),
+                (GameOverLine::Prompt, false) => RESTART_PROMPT.to_string(),
+            };
+        }
+    }
+
+    fn restart(
+        mut keyboard: ResMut<Input<KeyCode>>,
+        entry: Res<NameEntry>,
+        mut state: ResMut<State<GameState>>,
+    ) {
+        if entry.active {
+            return;
+        }
+
+        if keyboard.just_pressed(KeyCode::Return) {
+            keyboard.clear();
+            state.set(GameState::Level).unwrap();
+        } else if keyboard.just_pressed(KeyCode::Escape) {
+            keyboard.clear();
+            state.set(GameState::MainMenu).unwrap();
+        }
+    }
+}
+
+fn spawn_lines(commands: &mut Commands, fonts: &Fonts, lines: Vec<Line>) {
+    for (value, size, color, y, line) in lines {
+        let mut text =
+            labels::label(fonts, value, size, color, TextAlignment::CENTER);
+        text.transform = Transform::from_xyz(0., 0., 5.);
+
+        let mut entity = commands.spawn_bundle(text);
+        entity
+            .insert(ScreenAnchor::center(Vec2::new(0., y)))
+            .insert(GameOverScreen);
+
+        if let Some(line) = line {
+            entity.insert(line);
+        }
+    }
+}
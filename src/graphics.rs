@@ -1,11 +1,23 @@
 use bevy::prelude::*;
 
-pub const BG_COLOR: &'static str = "272034";
-pub const SPACESHIP_RED: &'static str = "ac3939";
-pub const WHITE: &'static str = "ffffff";
+pub const BG_COLOR: &str = "272034";
+pub const SPACESHIP_RED: &str = "ac3939";
+pub const WHITE: &str = "ffffff";
+pub const EXPLOSION_ORANGE: &str = "f2a65a";
+pub const ASTEROID_GREY: &str = "8e8a99";
+pub const PLAYER_TWO_BLUE: &str = "8fc6f0";
+pub const PLAYER_THREE_YELLOW: &str = "f0d35a";
+pub const PLAYER_FOUR_PINK: &str = "e58bc4";
+pub const PICKUP_GREEN: &str = "7bc96f";
+
+pub const FONT: &str = "fonts/DejaVuSansMono-Bold.ttf";
 
 pub struct GraphicsPlugin;
 
+pub struct Fonts {
+    pub main: Handle<Font>,
+}
+
 #[derive(Clone)]
 pub struct TexturesSheets {
     pub entities: Handle<TextureAtlas>,
@@ -20,7 +32,8 @@ impl Plugin for GraphicsPlugin {
         app.add_startup_system_to_stage(
             StartupStage::PreStartup,
             load_texture_sheets,
-        );
+        )
+        .add_startup_system_to_stage(StartupStage::PreStartup, load_fonts);
     }
 }
 
@@ -62,3 +75,9 @@ fn load_texture_sheets(
         ui,
     });
 }
+
+fn load_fonts(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Fonts {
+        main: asset_server.load(FONT),
+    });
+}
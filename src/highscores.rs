@@ -0,0 +1,329 @@
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+
+use crate::state::GameState;
+
+const HIGH_SCORES_FILE: &str = "highscores.ron";
+
+/// Bumped whenever `HighScore` changes in a way older files can't be read
+/// into.
+const FORMAT_VERSION: u32 = 1;
+
+pub const MAX_ENTRIES: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub wave: u32,
+    /// Seed the run was played with, enough to replay its obstacle layout.
+    pub seed: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl HighScore {
+    pub fn new(name: &str, score: u32, wave: u32, seed: u64) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        Self {
+            name: name.chars().take(MAX_NAME_LENGTH).collect(),
+            score,
+            wave,
+            seed,
+            timestamp,
+        }
+    }
+
+    /// `YYYY-MM-DD`, in UTC.
+    pub fn date(&self) -> String {
+        let (year, month, day) =
+            civil_from_days((self.timestamp / 86400) as i64);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HighScoreFile {
+    version: u32,
+    entries: Vec<HighScore>,
+}
+
+/// Best scores so far, highest first.
+#[derive(Default)]
+pub struct HighScores {
+    entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir()
+            .map(|dir| dir.join("space-shooter").join(HIGH_SCORES_FILE))
+    }
+
+    /// Reads the high-score file. A missing file means no scores yet; an
+    /// unreadable or outdated one is set aside so it isn't overwritten, and
+    /// the table starts over.
+    pub fn load() -> Self {
+        Self::path().map_or_else(Self::default, |path| Self::load_from(&path))
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        let file = match ron::from_str::<HighScoreFile>(&contents) {
+            Ok(file) if file.version == FORMAT_VERSION => file,
+
+            Ok(file) => {
+                warn!(
+                    "high scores in {} are version {}, expected {}",
+                    path.display(),
+                    file.version,
+                    FORMAT_VERSION
+                );
+                Self::set_aside(path);
+                return Self::default();
+            }
+
+            Err(err) => {
+                warn!("corrupted high scores in {}: {}", path.display(), err);
+                Self::set_aside(path);
+                return Self::default();
+            }
+        };
+
+        let mut high_scores = Self {
+            entries: file.entries,
+        };
+        high_scores.sort();
+        high_scores
+    }
+
+    fn set_aside(path: &Path) {
+        let backup = path.with_extension("ron.bak");
+
+        if let Err(err) = fs::rename(path, &backup) {
+            warn!("failed to back up {}: {}", path.display(), err);
+        }
+    }
+
+    pub fn save(&self) {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let file = HighScoreFile {
+            version: FORMAT_VERSION,
+            entries: self.entries.clone(),
+        };
+
+        let contents =
+            match ron::ser::to_string_pretty(&file, Default::default()) {
+                Ok(contents) => contents,
+                Err(err) => {
+                    warn!("failed to serialize high scores: {}", err);
+                    return;
+                }
+            };
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, contents));
+
+        if let Err(err) = written {
+            warn!("failed to save high scores to {}: {}", path.display(), err);
+        }
+    }
+
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+
+    /// Whether `score` would make it into the table.
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_ENTRIES
+                || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    pub fn insert(&mut self, entry: HighScore) {
+        self.entries.push(entry);
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        // Stable, so earlier entries win ties.
+        self.entries.sort_by_key(|entry| Reverse(entry.score));
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load()).add_system_set(
+            SystemSet::on_update(GameState::HighScores)
+                .with_system(Self::high_scores_menu),
+        );
+    }
+}
+
+impl HighScoresPlugin {
+    fn high_scores_menu(
+        mut egui_context: ResMut<EguiContext>,
+        high_scores: Res<HighScores>,
+        mut state: ResMut<State<GameState>>,
+        mut keyboard: ResMut<Input<KeyCode>>,
+    ) {
+        let mut back = keyboard.just_pressed(KeyCode::Escape);
+
+        egui::Window::new("High scores")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(egui_context.ctx_mut(), |ui| {
+                if high_scores.entries().is_empty() {
+                    ui.label("No scores yet.");
+                } else {
+                    egui::Grid::new("high_scores").striped(true).show(
+                        ui,
+                        |ui| {
+                            for header in
+                                ["#", "Name", "Score", "Wave", "Date", "Seed"]
+                            {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+
+                            for (i, entry) in
+                                high_scores.entries().iter().enumerate()
+                            {
+                                ui.label((i + 1).to_string());
+                                ui.label(&entry.name);
+                                ui.label(entry.score.to_string());
+                                ui.label(entry.wave.to_string());
+                                ui.label(entry.date());
+                                ui.label(format!("{:016x}", entry.seed));
+                                ui.end_row();
+                            }
+                        },
+                    );
+                }
+
+                ui.separator();
+
+                if ui.button("Back").clicked() {
+                    back = true;
+                }
+            });
+
+        if back {
+            keyboard.clear();
+            state.pop().unwrap();
+        }
+    }
+}
+
+/// Converts days since the Unix epoch into a proleptic Gregorian date.
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path of its own in the temp directory, with nothing there yet.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("space-shooter-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join(name).with_extension("ron");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(path.with_extension("ron.bak"));
+        path
+    }
+
+    fn write_file(path: &Path, version: u32, scores: &[u32]) {
+        let file = HighScoreFile {
+            version,
+            entries: scores
+                .iter()
+                .map(|&score| HighScore::new("ACE", score, 1, 0))
+                .collect(),
+        };
+        fs::write(path, ron::to_string(&file).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_file_starts_empty() {
+        let path = scratch("missing");
+
+        assert!(HighScores::load_from(&path).entries().is_empty());
+        assert!(!path.with_extension("ron.bak").exists());
+    }
+
+    #[test]
+    fn valid_file_is_loaded_sorted() {
+        let path = scratch("valid");
+        write_file(&path, FORMAT_VERSION, &[100, 300, 200]);
+
+        let scores: Vec<u32> = HighScores::load_from(&path)
+            .entries()
+            .iter()
+            .map(|entry| entry.score)
+            .collect();
+
+        assert_eq!(scores, [300, 200, 100]);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn corrupt_file_is_set_aside() {
+        let path = scratch("corrupt");
+        fs::write(&path, "(version: 1, entries: [(name: ").unwrap();
+
+        assert!(HighScores::load_from(&path).entries().is_empty());
+        assert!(!path.exists());
+        assert!(path.with_extension("ron.bak").exists());
+    }
+
+    #[test]
+    fn other_version_is_set_aside() {
+        let path = scratch("version");
+        write_file(&path, FORMAT_VERSION + 1, &[100]);
+
+        assert!(HighScores::load_from(&path).entries().is_empty());
+        assert!(!path.exists());
+        assert!(path.with_extension("ron.bak").exists());
+    }
+}
@@ -0,0 +1,534 @@
+use bevy::prelude::*;
+
+use crate::{
+    boss::{self, Boss, BossPart},
+    camera::ScreenAnchor,
+    common::Health,
+    graphics::{self, Fonts, TexturesSheets},
+    labels,
+    player::{self, Cursor, Player},
+    run::{GameMode, Lives, Round, Score, Wave, STARTING_LIVES},
+    settings::Settings,
+    stage::Campaign,
+    state::{GameState, LevelScoped},
+};
+
+/// Size of a single icon or digit from the UI sheet.
+const ICON: f32 = 38.;
+/// Distance of the first column from the left edge of the screen.
+const LEFT: f32 = ICON / 2. + 10.;
+const ROW_SPACING: f32 = 6.;
+/// Padding between the frame of a bar and its fill.
+const BAR_PADDING: f32 = 6.;
+
+const SHIP_ICON: usize = 10;
+const TEXT_SIZE: f32 = 28.;
+
+fn row(index: f32) -> f32 {
+    -(ICON + (ICON + ROW_SPACING) * index)
+}
+
+/// A line of text showing one of the numbers tracked by the run.
+#[derive(Component, Clone, Copy)]
+pub enum HudCounter {
+    Score,
+    Wave,
+    /// Stage of the campaign being played.
+    Stage,
+    /// Seconds left in a versus round, or spent in it when untimed.
+    Clock,
+}
+
+impl HudCounter {
+    fn format(self, value: u32) -> String {
+        match self {
+            HudCounter::Score => format!("SCORE {:06}", value),
+            HudCounter::Wave => format!("WAVE {}", value),
+            HudCounter::Stage => format!("STAGE {}", value),
+            HudCounter::Clock => format!("{}:{:02}", value / 60, value % 60),
+        }
+    }
+}
+
+/// The fill of a bar, shrinking towards its left edge as it empties.
+#[derive(Component)]
+pub struct HudBar {
+    left: Vec2,
+    size: Vec2,
+}
+
+/// One of the ship icons counting the lives left, the `n`th being shown
+/// while more than `n` lives remain.
+#[derive(Component)]
+pub struct LifeIcon(u32);
+
+/// Points earned by a single player, or their frags in versus, shown when
+/// there is more than one.
+#[derive(Component, Clone, Copy)]
+pub struct PlayerScore(usize);
+
+impl PlayerScore {
+    fn format(self, value: u32, mode: GameMode) -> String {
+        match mode {
+            GameMode::Versus { .. } => {
+                format!("P{} FRAGS {}", self.0 + 1, value)
+            }
+            _ => format!("P{} {:06}", self.0 + 1, value),
+        }
+    }
+}
+
+/// Health of the player in the given seat.
+#[derive(Component)]
+pub struct HealthBar(usize);
+
+/// Fire cooldown of the player in the given seat.
+#[derive(Component)]
+pub struct ReloadBar(usize);
+
+/// Part of the boss's health bar and name, only shown during a fight.
+#[derive(Component)]
+pub struct BossHud;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Level).with_system(Self::setup_hud),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::update_lives)
+                .with_system(Self::update_counters)
+                .with_system(Self::update_player_scores)
+                .with_system(Self::update_health_bar)
+                .with_system(Self::update_reload_bar)
+                .with_system(Self::update_boss_bar),
+        );
+    }
+}
+
+impl HudPlugin {
+    fn setup_hud(
+        mut commands: Commands,
+        ts: Res<TexturesSheets>,
+        fonts: Res<Fonts>,
+        mode: Res<GameMode>,
+        campaign: Res<Campaign>,
+    ) {
+        // Versus ships respawn for free and play for frags instead.
+        let versus = mode.is_versus();
+        let lives = if versus { 0 } else { STARTING_LIVES };
+
+        for i in 0..lives {
+            let icon = spawn_icon(
+                &mut commands,
+                ts.ui.clone(),
+                SHIP_ICON,
+                graphics::WHITE,
+                Vec2::splat(ICON),
+                ScreenAnchor::top_left(Vec2::new(
+                    LEFT + ICON * i as f32,
+                    row(0.),
+                )),
+            );
+            commands.entity(icon).insert(LifeIcon(i));
+        }
+
+        if !versus {
+            spawn_counter(
+                &mut commands,
+                &fonts,
+                HudCounter::Score,
+                labels::LEFT,
+                ScreenAnchor::top_left(Vec2::new(LEFT - ICON / 2., row(1.))),
+            );
+        }
+
+        spawn_counter(
+            &mut commands,
+            &fonts,
+            if versus {
+                HudCounter::Clock
+            } else if campaign.is_active() {
+                HudCounter::Stage
+            } else {
+                HudCounter::Wave
+            },
+            TextAlignment::CENTER,
+            ScreenAnchor::top(Vec2::new(0., row(0.))),
+        );
+
+        for id in 0..mode.players() {
+            Self::setup_player_hud(&mut commands, &ts, &fonts, id, *mode);
+        }
+
+        Self::setup_boss_hud(&mut commands, &fonts);
+    }
+
+    /// The boss's bar sits under the wave counter, its name under the bar.
+    fn setup_boss_hud(commands: &mut Commands, fonts: &Fonts) {
+        let (frame, fill) = spawn_bar(
+            commands,
+            Vec2::new(ICON * 12., ICON),
+            graphics::SPACESHIP_RED,
+            ScreenAnchor::top(Vec2::new(0., row(1.))),
+        );
+        // Hidden until a boss shows up.
+        for e in [frame, fill] {
+            commands
+                .entity(e)
+                .insert(BossHud)
+                .insert(Visibility { is_visible: false });
+        }
+
+        let mut text = labels::label(
+            fonts,
+            String::new(),
+            TEXT_SIZE,
+            graphics::SPACESHIP_RED,
+            TextAlignment::CENTER,
+        );
+        text.transform = Transform::from_xyz(0., 0., 2.);
+
+        commands
+            .spawn_bundle(text)
+            .insert(ScreenAnchor::top(Vec2::new(0., row(2.))))
+            .insert(BossHud)
+            .insert(Visibility { is_visible: false })
+            .insert(LevelScoped);
+    }
+
+    /// The first player's bars sit next to the lives, the others get rows
+    /// of their own below the score.
+    fn setup_player_hud(
+        commands: &mut Commands,
+        ts: &TexturesSheets,
+        fonts: &Fonts,
+        id: usize,
+        mode: GameMode,
+    ) {
+        let (health_row, reload_row) = match id {
+            0 => (row(0.), row(2.)),
+            _ => (row(1. + 2. * id as f32), row(2. + 2. * id as f32)),
+        };
+
+        if id > 0 {
+            spawn_icon(
+                commands,
+                ts.ui.clone(),
+                SHIP_ICON,
+                player::color(id),
+                Vec2::splat(ICON),
+                ScreenAnchor::top_left(Vec2::new(LEFT, health_row)),
+            );
+        }
+
+        let (_, health) = spawn_bar(
+            commands,
+            Vec2::new(ICON * 5., ICON),
+            graphics::SPACESHIP_RED,
+            ScreenAnchor::top_left(Vec2::new(LEFT + ICON * 5.5, health_row)),
+        );
+        commands.entity(health).insert(HealthBar(id));
+
+        spawn_icon(
+            commands,
+            ts.projectiles.clone(),
+            0,
+            graphics::WHITE,
+            Vec2::new(13., 37.) * 0.75,
+            ScreenAnchor::top_left(Vec2::new(LEFT, reload_row)),
+        );
+
+        let (_, reload) = spawn_bar(
+            commands,
+            Vec2::new(ICON * 3., ICON / 2.),
+            graphics::EXPLOSION_ORANGE,
+            ScreenAnchor::top_left(Vec2::new(LEFT + ICON * 2., reload_row)),
+        );
+        commands.entity(reload).insert(ReloadBar(id));
+
+        if mode.players() > 1 {
+            let score = PlayerScore(id);
+            let mut text = labels::label(
+                fonts,
+                score.format(0, mode),
+                TEXT_SIZE,
+                player::color(id),
+                labels::LEFT,
+            );
+            text.transform = Transform::from_xyz(0., 0., 2.);
+
+            commands
+                .spawn_bundle(text)
+                .insert(ScreenAnchor::top_left(Vec2::new(
+                    LEFT + ICON * 8.5,
+                    health_row,
+                )))
+                .insert(score)
+                .insert(LevelScoped);
+        }
+    }
+
+    fn update_lives(
+        lives: Res<Lives>,
+        mut icon_query: Query<(&LifeIcon, &mut Visibility)>,
+    ) {
+        if !lives.is_changed() {
+            return;
+        }
+
+        for (icon, mut visibility) in icon_query.iter_mut() {
+            visibility.is_visible = icon.0 < lives.0;
+        }
+    }
+
+    fn update_counters(
+        score: Res<Score>,
+        wave: Res<Wave>,
+        round: Res<Round>,
+        campaign: Res<Campaign>,
+        settings: Res<Settings>,
+        mut counter_query: Query<(&HudCounter, &mut Text)>,
+    ) {
+        for (counter, mut text) in counter_query.iter_mut() {
+            let (changed, value) = match counter {
+                HudCounter::Score => (score.is_changed(), score.0),
+                HudCounter::Wave => (wave.is_changed(), wave.0),
+                HudCounter::Stage => {
+                    let stage = campaign.stage_number().unwrap_or_default();
+                    (campaign.is_changed(), stage as u32)
+                }
+                HudCounter::Clock => {
+                    let shown = round.remaining(&settings.rules).map_or(
+                        round.elapsed.as_secs(),
+                        |remaining| {
+                            // Counting down, 0:00 only once it's over.
+                            remaining.as_secs_f64().ceil() as u64
+                        },
+                    );
+                    (round.is_changed(), shown as u32)
+                }
+            };
+
+            if changed {
+                text.sections[0].value = counter.format(value);
+            }
+        }
+    }
+
+    fn update_player_scores(
+        mode: Res<GameMode>,
+        round: Res<Round>,
+        player_query: Query<&Player>,
+        mut score_query: Query<(&PlayerScore, &mut Text)>,
+    ) {
+        for player in player_query.iter() {
+            let points = match *mode {
+                GameMode::Versus { .. } => {
+                    round.frags.get(player.id).copied().unwrap_or_default()
+                }
+                _ => player.score,
+            };
+
+            for (score, mut text) in score_query.iter_mut() {
+                let value = score.format(points, *mode);
+                if score.0 == player.id && text.sections[0].value != value {
+                    text.sections[0].value = value;
+                }
+            }
+        }
+    }
+
+    fn update_health_bar(
+        health_query: Query<(&Player, &Health), Changed<Health>>,
+        mut bar_query: Query<(
+            &HealthBar,
+            &HudBar,
+            &mut Sprite,
+            &mut ScreenAnchor,
+        )>,
+    ) {
+        for (player, health) in health_query.iter() {
+            for (id, bar, mut sprite, mut anchor) in bar_query.iter_mut() {
+                if id.0 == player.id {
+                    bar.fill(&mut sprite, &mut anchor, health.percent());
+                }
+            }
+        }
+    }
+
+    fn update_reload_bar(
+        cursor_query: Query<&Cursor, Changed<Cursor>>,
+        mut bar_query: Query<(
+            &ReloadBar,
+            &HudBar,
+            &mut Sprite,
+            &mut ScreenAnchor,
+        )>,
+    ) {
+        for cursor in cursor_query.iter() {
+            for (id, bar, mut sprite, mut anchor) in bar_query.iter_mut() {
+                if id.0 == cursor.player {
+                    bar.fill(&mut sprite, &mut anchor, cursor.readiness());
+                }
+            }
+        }
+    }
+
+    /// Shows the boss's name and health while there is one, hiding them
+    /// otherwise.
+    fn update_boss_bar(
+        boss_query: Query<(&Boss, &Health)>,
+        part_query: Query<&Health, (With<BossPart>, Without<Boss>)>,
+        mut hud_query: BossHudQuery,
+    ) {
+        let boss = boss_query.get_single().ok();
+        let health = boss.map(|(boss, core)| {
+            let parts = std::iter::once(core).chain(part_query.iter());
+            boss::health_fraction(boss.def(), parts)
+        });
+
+        for (mut visibility, text, bar, sprite, anchor) in hud_query.iter_mut()
+        {
+            if visibility.is_visible != boss.is_some() {
+                visibility.is_visible = boss.is_some();
+            }
+
+            if let (Some((boss, _)), Some(mut text)) = (boss, text) {
+                if text.sections[0].value != boss.def().name {
+                    text.sections[0].value = boss.def().name.to_string();
+                }
+            }
+
+            if let (
+                Some(health),
+                Some(bar),
+                Some(mut sprite),
+                Some(mut anchor),
+            ) = (health, bar, sprite, anchor)
+            {
+                bar.fill(&mut sprite, &mut anchor, health);
+            }
+        }
+    }
+}
+
+type BossHudQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Visibility,
+        Option<&'static mut Text>,
+        Option<&'static HudBar>,
+        Option<&'static mut Sprite>,
+        Option<&'static mut ScreenAnchor>,
+    ),
+    With<BossHud>,
+>;
+
+impl HudBar {
+    fn fill(&self, sprite: &mut Sprite, anchor: &mut ScreenAnchor, t: f32) {
+        let width = self.size.x * t.clamp(0., 1.);
+
+        sprite.custom_size = Some(Vec2::new(width, self.size.y));
+        anchor.offset = self.left + Vec2::new(width / 2., 0.);
+    }
+}
+
+fn spawn_icon(
+    commands: &mut Commands,
+    texture_atlas: Handle<TextureAtlas>,
+    index: usize,
+    color: &str,
+    size: Vec2,
+    anchor: ScreenAnchor,
+) -> Entity {
+    let mut sprite = TextureAtlasSprite::new(index);
+    sprite.color = Color::hex(color).unwrap();
+    sprite.custom_size = Some(size);
+
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas,
+            transform: Transform::from_xyz(0., 0., 2.),
+            ..Default::default()
+        })
+        .insert(anchor)
+        .insert(LevelScoped)
+        .id()
+}
+
+fn spawn_counter(
+    commands: &mut Commands,
+    fonts: &Fonts,
+    counter: HudCounter,
+    alignment: TextAlignment,
+    anchor: ScreenAnchor,
+) {
+    let mut text = labels::label(
+        fonts,
+        counter.format(0),
+        TEXT_SIZE,
+        graphics::WHITE,
+        alignment,
+    );
+    text.transform = Transform::from_xyz(0., 0., 2.);
+
+    commands
+        .spawn_bundle(text)
+        .insert(anchor)
+        .insert(counter)
+        .insert(LevelScoped);
+}
+
+/// Spawns a white frame centered on `anchor` and returns it along with the
+/// fill inside it.
+fn spawn_bar(
+    commands: &mut Commands,
+    size: Vec2,
+    color: &str,
+    anchor: ScreenAnchor,
+) -> (Entity, Entity) {
+    let frame = commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::hex(graphics::WHITE).unwrap(),
+                custom_size: Some(size),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0., 0., 2.),
+            ..Default::default()
+        })
+        .insert(anchor)
+        .insert(LevelScoped)
+        .id();
+
+    let fill = size - BAR_PADDING * 2.;
+    let left = anchor.offset - Vec2::new(fill.x / 2., 0.);
+
+    let bar = HudBar { left, size: fill };
+
+    let mut sprite = Sprite {
+        color: Color::hex(color).unwrap(),
+        ..Default::default()
+    };
+    let mut anchor = anchor;
+    bar.fill(&mut sprite, &mut anchor, 1.);
+
+    let fill = commands
+        .spawn_bundle(SpriteBundle {
+            sprite,
+            transform: Transform::from_xyz(0., 0., 3.),
+            ..Default::default()
+        })
+        .insert(anchor)
+        .insert(bar)
+        .insert(LevelScoped)
+        .id();
+
+    (frame, fill)
+}
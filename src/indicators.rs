@@ -0,0 +1,130 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::{CameraSystem, MainCamera},
+    common::Threat,
+    graphics,
+    pool::Pooled,
+    state::{GameState, LevelScoped},
+};
+
+/// How many threats can be pointed at simultaneously.
+const MAX_INDICATORS: usize = 8;
+
+/// Threats further than this from the edge of the screen are ignored.
+const THREAT_RANGE: f32 = 600.;
+
+/// Distance between the indicators and the edge of the screen.
+const EDGE_INSET: f32 = 24.;
+
+const INDICATOR_SIZE: f32 = 12.;
+
+#[derive(Component)]
+pub struct ThreatIndicator;
+
+type IndicatorQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+    ),
+    (With<ThreatIndicator>, Without<Threat>, Without<MainCamera>),
+>;
+
+pub struct IndicatorsPlugin;
+
+impl Plugin for IndicatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Level)
+                .with_system(Self::spawn_indicators),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            Self::point_at_threats.after(CameraSystem::Position),
+        );
+    }
+}
+
+impl IndicatorsPlugin {
+    fn spawn_indicators(mut commands: Commands) {
+        for _ in 0..MAX_INDICATORS {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::hex(graphics::SPACESHIP_RED).unwrap(),
+                        custom_size: Some(Vec2::splat(INDICATOR_SIZE)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(0., 0., 2.5),
+                    visibility: Visibility { is_visible: false },
+                    ..Default::default()
+                })
+                .insert(ThreatIndicator)
+                .insert(Name::new("Threat Indicator"))
+                .insert(LevelScoped);
+        }
+    }
+
+    fn point_at_threats(
+        camera_query: Query<
+            (&OrthographicProjection, &Transform),
+            With<MainCamera>,
+        >,
+        threat_query: Query<&Transform, (With<Threat>, Without<Pooled>)>,
+        mut indicator_query: IndicatorQuery,
+    ) {
+        let (projection, camera) = camera_query.single();
+
+        let center = camera.translation.truncate();
+        let half = Vec2::new(
+            projection.right - projection.left,
+            projection.top - projection.bottom,
+        ) * projection.scale
+            / 2.;
+
+        let mut threats: Vec<(f32, Vec2)> = threat_query
+            .iter()
+            .filter_map(|transform| {
+                let offset = transform.translation.truncate() - center;
+                let outside = (offset.abs() - half).max(Vec2::ZERO);
+                let distance = outside.length();
+
+                (distance > 0. && distance < THREAT_RANGE)
+                    .then_some((distance, offset))
+            })
+            .collect();
+
+        threats.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut threats = threats.into_iter();
+
+        for (mut transform, mut sprite, mut visibility) in
+            indicator_query.iter_mut()
+        {
+            let (distance, offset) = match threats.next() {
+                Some(threat) => threat,
+                None => {
+                    visibility.is_visible = false;
+                    continue;
+                }
+            };
+
+            // Scale the offset down until it touches the inset screen edge.
+            let edge = (half - EDGE_INSET).max(Vec2::ONE);
+            let scale = (edge / offset.abs()).min_element();
+            let position = center + offset * scale;
+
+            transform.translation = position.extend(transform.translation.z);
+            transform.rotation =
+                Quat::from_rotation_z(offset.y.atan2(offset.x) + FRAC_PI_4);
+
+            sprite.color.set_a(1. - distance / THREAT_RANGE);
+            visibility.is_visible = true;
+        }
+    }
+}
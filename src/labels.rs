@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    camera::ScreenAnchor,
+    events::{
+        BossDefeated, EnemyDestroyed, ObstacleDestroyed, PickupCollected,
+        PlayerHit, StageStarted,
+    },
+    graphics::{self, Fonts},
+    pickups::PickupKind,
+    player, run,
+    state::{GameState, LevelScoped},
+};
+
+/// The bundled font, baked in so menus drawn by egui can use it too.
+const FONT_DATA: &[u8] =
+    include_bytes!("../assets/fonts/DejaVuSansMono-Bold.ttf");
+
+const POPUP_LIFETIME: f32 = 0.8;
+const POPUP_SPEED: f32 = 60.;
+/// Seconds the name of a stage stays up once it begins.
+const BANNER_LIFETIME: f32 = 3.;
+
+pub const LEFT: TextAlignment = TextAlignment {
+    vertical: VerticalAlign::Center,
+    horizontal: HorizontalAlign::Left,
+};
+
+/// Text floating up from where something happened, fading as it goes.
+#[derive(Component)]
+pub struct Popup {
+    timer: Timer,
+}
+
+pub struct LabelsPlugin;
+
+impl Plugin for LabelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Self::use_bundled_font)
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(Self::spawn_popups)
+                    .with_system(Self::announce_stage)
+                    .with_system(Self::animate_popups),
+            );
+    }
+}
+
+impl LabelsPlugin {
+    fn use_bundled_font(mut egui_context: ResMut<EguiContext>) {
+        let mut fonts = egui::FontDefinitions::default();
+
+        fonts.font_data.insert(
+            graphics::FONT.to_owned(),
+            egui::FontData::from_static(FONT_DATA),
+        );
+
+        for family in
+            [egui::FontFamily::Proportional, egui::FontFamily::Monospace]
+        {
+            fonts
+                .families
+                .entry(family)
+                .or_default()
+                .insert(0, graphics::FONT.to_owned());
+        }
+
+        egui_context.ctx_mut().set_fonts(fonts);
+    }
+
+    fn spawn_popups(
+        mut commands: Commands,
+        fonts: Res<Fonts>,
+        mut destroyed_evr: EventReader<ObstacleDestroyed>,
+        mut enemy_destroyed_evr: EventReader<EnemyDestroyed>,
+        mut boss_defeated_evr: EventReader<BossDefeated>,
+        mut pickup_evr: EventReader<PickupCollected>,
+        mut hit_evr: EventReader<PlayerHit>,
+    ) {
+        let points = destroyed_evr.iter().map(|ev| {
+            let size = if ev.split { 26. } else { 18. };
+            let value = format!("+{}", run::points(ev));
+            (value, ev.position, size, graphics::WHITE)
+        });
+
+        let enemies = enemy_destroyed_evr.iter().map(|ev| {
+            let value = format!("+{}", run::ENEMY_POINTS);
+            (value, ev.position, 30., graphics::EXPLOSION_ORANGE)
+        });
+
+        let bosses = boss_defeated_evr.iter().map(|ev| {
+            let value = format!("+{}", run::BOSS_POINTS);
+            let position = ev.position + Vec2::new(0., 60.);
+            (value, position, 48., graphics::EXPLOSION_ORANGE)
+        });
+
+        let pickups = pickup_evr.iter().map(|ev| {
+            let value = match ev.kind {
+                PickupKind::Repair => "REPAIR",
+                PickupKind::ExtraLife => "+1 LIFE",
+            };
+            (
+                value.to_string(),
+                ev.position,
+                26.,
+                player::color(ev.player),
+            )
+        });
+
+        let damage = hit_evr.iter().map(|ev| {
+            let value = format!("-{}", ev.damage);
+            let position = ev.position + Vec2::new(0., 50.);
+            (value, position, 24., graphics::SPACESHIP_RED)
+        });
+
+        for (value, position, size, color) in points
+            .chain(enemies)
+            .chain(bosses)
+            .chain(pickups)
+            .chain(damage)
+        {
+            let mut text =
+                label(&fonts, value, size, color, TextAlignment::CENTER);
+            text.transform = Transform::from_translation(position.extend(4.));
+
+            commands
+                .spawn_bundle(text)
+                .insert(Popup {
+                    timer: Timer::from_seconds(POPUP_LIFETIME, false),
+                })
+                .insert(LevelScoped);
+        }
+    }
+
+    /// Shows the number and name of a stage over the middle of the screen
+    /// as it begins.
+    fn announce_stage(
+        mut commands: Commands,
+        fonts: Res<Fonts>,
+        mut started_evr: EventReader<StageStarted>,
+    ) {
+        for ev in started_evr.iter() {
+            let value = format!("STAGE {}\n{}", ev.stage + 1, ev.name);
+            let mut text = label(
+                &fonts,
+                value,
+                48.,
+                graphics::WHITE,
+                TextAlignment::CENTER,
+            );
+            text.transform = Transform::from_xyz(0., 0., 4.);
+
+            commands
+                .spawn_bundle(text)
+                .insert(ScreenAnchor::center(Vec2::new(0., 120.)))
+                .insert(Popup {
+                    timer: Timer::from_seconds(BANNER_LIFETIME, false),
+                })
+                .insert(LevelScoped);
+        }
+    }
+
+    fn animate_popups(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut popup_query: Query<(Entity, &mut Transform, &mut Text, &mut Popup)>,
+    ) {
+        for (e, mut transform, mut text, mut popup) in popup_query.iter_mut() {
+            popup.timer.tick(time.delta());
+
+            if popup.timer.finished() {
+                commands.entity(e).despawn();
+                continue;
+            }
+
+            transform.translation.y += POPUP_SPEED * time.delta_seconds();
+
+            let alpha = 1. - popup.timer.percent().powi(2);
+            for section in text.sections.iter_mut() {
+                section.style.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+/// A line of text in the bundled font, positioned by its transform.
+pub fn label(
+    fonts: &Fonts,
+    value: impl Into<String>,
+    size: f32,
+    color: &str,
+    alignment: TextAlignment,
+) -> Text2dBundle {
+    Text2dBundle {
+        text: Text::from_section(
+            value,
+            TextStyle {
+                font: fonts.main.clone(),
+                font_size: size,
+                color: Color::hex(color).unwrap(),
+            },
+        )
+        .with_alignment(alignment),
+        ..Default::default()
+    }
+}
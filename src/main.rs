@@ -1,26 +1,75 @@
+mod ai;
+mod audio;
+mod background;
+mod boss;
+mod camera;
 mod common;
+mod enemies;
+mod events;
+mod game_over;
 mod graphics;
+mod highscores;
+mod hud;
+mod indicators;
+mod labels;
+mod menu;
+mod minimap;
+mod netcode;
 mod obstacles;
+mod particles;
+mod patterns;
+mod pickups;
 mod player;
+mod pool;
 mod projectiles;
+mod run;
+mod settings;
+mod simulation;
+mod snapshot;
+mod stage;
 mod state;
 
 #[cfg(debug_assertions)]
 mod debug;
+#[cfg(debug_assertions)]
+mod editor;
 
-use bevy::{
-    prelude::*,
-    render::{camera::WindowOrigin, texture::ImageSettings},
-    window::WindowMode,
-};
+use ai::AiPlugin;
+use audio::{AudioBackend, SoundPlugin};
+use background::BackgroundPlugin;
+use bevy::{prelude::*, render::texture::ImageSettings};
+use bevy_inspector_egui::bevy_egui::EguiPlugin;
+use boss::BossPlugin;
+use camera::CameraPlugin;
+use enemies::EnemiesPlugin;
+use events::EventsPlugin;
+use game_over::GameOverPlugin;
 use graphics::GraphicsPlugin;
+use highscores::HighScoresPlugin;
+use hud::HudPlugin;
+use indicators::IndicatorsPlugin;
+use labels::LabelsPlugin;
+use menu::MenuPlugin;
+use minimap::MinimapPlugin;
+use netcode::NetcodePlugin;
 use obstacles::ObstaclePlugin;
+use particles::ParticlesPlugin;
+use patterns::PatternsPlugin;
+use pickups::PickupsPlugin;
 use player::PlayerPlugin;
+use pool::PoolPlugin;
 use projectiles::ProjectilesPlugin;
+use run::RunPlugin;
+use settings::{Settings, SettingsPlugin};
+use simulation::SimulationPlugin;
+use snapshot::SnapshotPlugin;
+use stage::StagePlugin;
 use state::GameState;
 
 #[cfg(debug_assertions)]
 use debug::DebugPlugin;
+#[cfg(debug_assertions)]
+use editor::EditorPlugin;
 
 pub const HEIGHT: f32 = 720.0;
 pub const RESOLUTION: f32 = 16.0 / 9.0;
@@ -29,34 +78,48 @@ pub const WIDTH: f32 = HEIGHT * RESOLUTION;
 fn main() {
     let mut app = App::new();
 
+    let settings = Settings::load();
+    let backend = AudioBackend::from_env();
+
     app.insert_resource(ClearColor(Color::hex(graphics::BG_COLOR).unwrap()))
         .insert_resource(ImageSettings::default_nearest())
-        .insert_resource(WindowDescriptor {
-            width: WIDTH,
-            height: HEIGHT,
-            title: "Shooter".to_string(),
-            resizable: false,
-            mode: WindowMode::Windowed,
-            scale_factor_override: Some(1.0),
-            ..Default::default()
-        })
-        .add_state(GameState::Level)
-        .add_plugins(DefaultPlugins)
-        .add_startup_system(setup_camera)
+        .insert_resource(settings.window_descriptor())
+        .insert_resource(settings.playfield())
+        .insert_resource(settings)
+        .add_state(GameState::MainMenu)
+        .add_plugins_with(DefaultPlugins, |group| backend.plugins(group))
+        .add_plugin(EguiPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(EventsPlugin)
+        .add_plugin(SoundPlugin { backend })
+        .add_plugin(CameraPlugin)
+        .add_plugin(SimulationPlugin)
         .add_plugin(GraphicsPlugin)
+        .add_plugin(BackgroundPlugin)
+        .add_plugin(PoolPlugin)
+        .add_plugin(PatternsPlugin)
+        .add_plugin(RunPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(ProjectilesPlugin)
-        .add_plugin(ObstaclePlugin);
+        .add_plugin(ObstaclePlugin)
+        .add_plugin(AiPlugin)
+        .add_plugin(EnemiesPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(PickupsPlugin)
+        .add_plugin(StagePlugin)
+        .add_plugin(ParticlesPlugin)
+        .add_plugin(IndicatorsPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(LabelsPlugin)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(HighScoresPlugin)
+        .add_plugin(SnapshotPlugin)
+        .add_plugin(NetcodePlugin);
 
     #[cfg(debug_assertions)]
-    app.add_plugin(DebugPlugin);
+    app.add_plugin(DebugPlugin).add_plugin(EditorPlugin);
 
     app.run();
 }
-
-fn setup_camera(mut commands: Commands) {
-    let mut camera = Camera2dBundle::default();
-    camera.projection.window_origin = WindowOrigin::BottomLeft;
-
-    commands.spawn_bundle(camera);
-}
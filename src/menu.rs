@@ -0,0 +1,172 @@
+use std::marker::PhantomData;
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    netcode::NetSession,
+    run::GameMode,
+    settings::Settings,
+    snapshot::{PendingRestore, Snapshot},
+    stage::Campaign,
+    state::GameState,
+};
+
+/// What the next run is going to be.
+#[derive(SystemParam)]
+struct NextRun<'w, 's> {
+    mode: ResMut<'w, GameMode>,
+    campaign: ResMut<'w, Campaign>,
+    pending_restore: ResMut<'w, PendingRestore>,
+    settings: Res<'w, Settings>,
+    gamepads: Res<'w, Gamepads>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl NextRun<'_, '_> {
+    /// A fresh run, through the campaign or endless waves.
+    fn start(&mut self, mode: GameMode, campaign: bool) {
+        *self.mode = mode;
+        self.campaign.stop();
+
+        if campaign {
+            self.campaign.start();
+        }
+    }
+
+    /// A versus round, with a seat for each gamepad connected.
+    fn versus(&self) -> GameMode {
+        GameMode::versus(&self.settings.rules, self.gamepads.iter().count())
+    }
+
+    /// The saved run, picked back up where it was left.
+    fn resume(&mut self, snapshot: Snapshot) {
+        *self.mode = snapshot.mode;
+        self.campaign.stop();
+        self.campaign.progress = snapshot.campaign.clone();
+        self.pending_restore.0 = Some(snapshot);
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::MainMenu)
+                .with_system(Self::show_cursor),
+        )
+        .add_system_set(
+            // Coming back from the settings, which hide it on the way out.
+            SystemSet::on_resume(GameState::MainMenu)
+                .with_system(Self::show_cursor),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::MainMenu)
+                .with_system(Self::main_menu),
+        );
+    }
+}
+
+impl MenuPlugin {
+    fn show_cursor(mut windows: ResMut<Windows>) {
+        windows
+            .get_primary_mut()
+            .unwrap()
+            .set_cursor_visibility(true);
+    }
+
+    fn main_menu(
+        mut egui_context: ResMut<EguiContext>,
+        mut state: ResMut<State<GameState>>,
+        mut keyboard: ResMut<Input<KeyCode>>,
+        mut next_run: NextRun,
+        session: Option<Res<NetSession>>,
+        mut exit_evw: EventWriter<AppExit>,
+    ) {
+        let mut next = None;
+
+        egui::Window::new("Space Shooter")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(egui_context.ctx_mut(), |ui| {
+                ui.vertical_centered_justified(|ui| {
+                    if let Some(status) =
+                        session.as_ref().and_then(|session| session.status())
+                    {
+                        ui.label(status);
+                        ui.separator();
+                    }
+
+                    if Snapshot::exists() && ui.button("Continue").clicked() {
+                        // A save that fails to load is set aside, and the
+                        // button goes away with it.
+                        if let Some(snapshot) = Snapshot::load() {
+                            Snapshot::remove();
+                            next_run.resume(snapshot);
+                            next = Some(GameState::Level);
+                        }
+                    }
+
+                    if ui.button("Campaign").clicked() {
+                        next_run.start(GameMode::Solo, true);
+                        next = Some(GameState::Level);
+                    }
+
+                    if ui.button("Play").clicked() {
+                        next_run.start(GameMode::Solo, false);
+                        next = Some(GameState::Level);
+                    }
+
+                    if ui.button("Co-op").clicked() {
+                        next_run.start(GameMode::Coop, false);
+                        next = Some(GameState::Level);
+                    }
+
+                    if ui.button("Versus").clicked() {
+                        let mode = next_run.versus();
+                        next_run.start(mode, false);
+                        next = Some(GameState::Level);
+                    }
+
+                    if cfg!(debug_assertions) && ui.button("Editor").clicked() {
+                        next = Some(GameState::Editor);
+                    }
+
+                    if ui.button("High scores").clicked() {
+                        next = Some(GameState::HighScores);
+                    }
+
+                    if ui.button("Settings").clicked() {
+                        next = Some(GameState::Settings);
+                    }
+
+                    if ui.button("Quit").clicked() {
+                        exit_evw.send(AppExit);
+                    }
+                });
+            });
+
+        if keyboard.just_pressed(KeyCode::Return) {
+            next_run.start(GameMode::Solo, false);
+            next = Some(GameState::Level);
+        }
+
+        match next {
+            Some(next @ (GameState::Level | GameState::Editor)) => {
+                keyboard.clear();
+                state.set(next).unwrap();
+            }
+
+            // Both return here when closed.
+            Some(overlay) => {
+                keyboard.clear();
+                state.push(overlay).unwrap();
+            }
+
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::ScreenAnchor,
+    common::Playfield,
+    graphics,
+    pool::Pooled,
+    settings::Settings,
+    state::{GameState, LevelScoped},
+};
+
+/// Width of the panel; its height follows the aspect ratio of the world.
+const MINIMAP_WIDTH: f32 = 200.;
+const BORDER: f32 = 2.;
+const MARGIN: f32 = 12.;
+
+/// Shows up on the minimap as a dot of the given color and size.
+#[derive(Component, Clone, Copy)]
+pub struct MinimapBlip {
+    pub color: Color,
+    pub size: f32,
+}
+
+impl MinimapBlip {
+    pub fn new(hex: &str, size: f32) -> Self {
+        Self {
+            color: Color::hex(hex).unwrap(),
+            size,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Minimap {
+    size: Vec2,
+    /// Dots spawned so far, reused from frame to frame.
+    dots: Vec<Entity>,
+}
+
+#[derive(Component)]
+pub struct MinimapDot;
+
+type DotQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+    ),
+    (With<MinimapDot>, Without<MinimapBlip>),
+>;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Level)
+                .with_system(Self::spawn_minimap),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::toggle_minimap)
+                .with_system(Self::update_minimap),
+        );
+    }
+}
+
+impl MinimapPlugin {
+    fn spawn_minimap(mut commands: Commands, playfield: Res<Playfield>) {
+        let size = Vec2::new(
+            MINIMAP_WIDTH,
+            MINIMAP_WIDTH * playfield.height / playfield.width,
+        );
+
+        let mut background = Color::hex(graphics::BG_COLOR).unwrap();
+        background.set_a(0.85);
+
+        let border = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::hex(graphics::WHITE).unwrap(),
+                    custom_size: Some(size + BORDER * 2.),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0., 0., -0.01),
+                ..Default::default()
+            })
+            .id();
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: background,
+                    custom_size: Some(size),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0., 0., 2.),
+                ..Default::default()
+            })
+            .insert(ScreenAnchor::top_right(-(size / 2. + MARGIN)))
+            .insert(Minimap {
+                size,
+                ..Default::default()
+            })
+            .insert(Name::new("Minimap"))
+            .insert(LevelScoped)
+            .add_child(border);
+    }
+
+    fn toggle_minimap(
+        keyboard: Res<Input<KeyCode>>,
+        settings: Res<Settings>,
+        mut minimap_query: Query<&mut Visibility, With<Minimap>>,
+    ) {
+        if !settings.bindings.minimap.just_pressed(&keyboard) {
+            return;
+        }
+
+        for mut visibility in minimap_query.iter_mut() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+
+    fn update_minimap(
+        mut commands: Commands,
+        playfield: Res<Playfield>,
+        mut minimap_query: Query<(Entity, &mut Minimap, &Visibility)>,
+        blip_query: Query<(&Transform, &MinimapBlip), Without<Pooled>>,
+        mut dot_query: DotQuery,
+    ) {
+        for (e, mut minimap, visibility) in minimap_query.iter_mut() {
+            if !visibility.is_visible {
+                continue;
+            }
+
+            let scale = minimap.size / playfield.size();
+            let half = minimap.size / 2.;
+            let to_minimap = |transform: &Transform| {
+                (transform.translation.truncate() * scale - half)
+                    .clamp(-half, half)
+                    .extend(0.1)
+            };
+
+            let mut blips = blip_query.iter();
+
+            for &dot in minimap.dots.iter() {
+                let (mut dot_transform, mut sprite, mut dot_visibility) =
+                    match dot_query.get_mut(dot) {
+                        Ok(dot) => dot,
+                        Err(_) => continue,
+                    };
+
+                match blips.next() {
+                    Some((transform, blip)) => {
+                        dot_transform.translation = to_minimap(transform);
+                        sprite.color = blip.color;
+                        sprite.custom_size = Some(Vec2::splat(blip.size));
+                        dot_visibility.is_visible = true;
+                    }
+
+                    None => dot_visibility.is_visible = false,
+                }
+            }
+
+            // More blips than ever before, grow the set of dots.
+            for (transform, blip) in blips {
+                let dot = commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: blip.color,
+                            custom_size: Some(Vec2::splat(blip.size)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(to_minimap(
+                            transform,
+                        )),
+                        ..Default::default()
+                    })
+                    .insert(MinimapDot)
+                    .id();
+
+                commands.entity(e).add_child(dot);
+                minimap.dots.push(dot);
+            }
+        }
+    }
+}
@@ -0,0 +1,1059 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    env,
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    events,
+    player::{Player, PlayerInput},
+    run::{GameMode, NextSeed},
+    simulation::{
+        self, FixedUpdate, SimulationApp, SimulationStage, SimulationSystem,
+        SimulationTime,
+    },
+    snapshot::Snapshot,
+    stage::Campaign,
+    state::GameState,
+};
+
+/// Ticks a local input is held back before it's simulated, giving it time
+/// to reach the peer.
+const INPUT_DELAY: u64 = 2;
+/// How far the simulation may run ahead of the peer's last known input, and
+/// so how far back it may have to roll.
+const MAX_ROLLBACK: u64 = 12;
+/// Snapshots kept: one per tick that may be rolled back, and the ticks a
+/// single frame may run past the point where the simulation stalls.
+const SNAPSHOTS: u64 = MAX_ROLLBACK + simulation::MAX_TICKS_PER_FRAME as u64;
+/// Settled ticks whose checksum is kept for the peer's to arrive.
+const CHECKSUM_HISTORY: u64 = 120;
+/// Inputs sent at most per packet, the oldest ones the peer hasn't
+/// acknowledged yet.
+const MAX_INPUTS_PER_PACKET: usize = 32;
+const MAX_PACKET_SIZE: usize = 4096;
+
+const JOIN_INTERVAL: Duration = Duration::from_millis(500);
+/// Silence after which the peer is considered gone.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Steps per turn the aim is rounded to, and per unit for movement, so both
+/// ends simulate with exactly the same values.
+const AIM_STEPS: f32 = 4096.;
+const MOVEMENT_STEPS: f32 = 128.;
+
+#[derive(Clone, Copy)]
+enum Role {
+    /// Waits for a peer on the given port, and plays the first seat.
+    Host(u16),
+    Join(SocketAddr),
+}
+
+/// Networking options, from the command line: `--host <port>` waits for a
+/// peer and `--join <address>` connects to one. `--latency <ms>` and
+/// `--loss <fraction>` hold back and drop outgoing packets, to try bad
+/// connections out over loopback.
+struct NetConfig {
+    role: Role,
+    latency: Duration,
+    loss: f64,
+}
+
+impl NetConfig {
+    fn from_args() -> Option<Self> {
+        let mut role = None;
+        let mut latency = Duration::ZERO;
+        let mut loss = 0.;
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = match args.next() {
+                Some(value) => value,
+                None => {
+                    warn!("missing value for {}", arg);
+                    break;
+                }
+            };
+
+            match arg.as_str() {
+                "--host" => match value.parse() {
+                    Ok(port) => role = Some(Role::Host(port)),
+                    Err(_) => warn!("invalid port {}", value),
+                },
+
+                "--join" => {
+                    match value.to_socket_addrs().map(|mut addrs| addrs.next())
+                    {
+                        Ok(Some(addr)) => role = Some(Role::Join(addr)),
+                        _ => warn!("invalid address {}", value),
+                    }
+                }
+
+                "--latency" => match value.parse() {
+                    Ok(ms) => latency = Duration::from_millis(ms),
+                    Err(_) => warn!("invalid latency {}", value),
+                },
+
+                "--loss" => match value.parse::<f64>() {
+                    Ok(fraction) => loss = fraction.clamp(0., 1.),
+                    Err(_) => warn!("invalid loss {}", value),
+                },
+
+                _ => warn!("unknown argument {}", arg),
+            }
+        }
+
+        role.map(|role| Self {
+            role,
+            latency,
+            loss,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Packet {
+    Join,
+    Welcome {
+        seed: u64,
+    },
+    /// The sender's inputs for consecutive ticks from `start` on, along with
+    /// the tick before which it has all of the receiver's, and the checksum
+    /// of the latest tick it has settled.
+    Inputs {
+        start: u64,
+        inputs: Vec<PlayerInput>,
+        ack: u64,
+        checksum: Option<(u64, u64)>,
+    },
+}
+
+impl Packet {
+    /// Writes the packet out as a tag byte followed by its fields,
+    /// little-endian, inputs taking six bytes each.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        match self {
+            Packet::Join => out.push(0),
+
+            Packet::Welcome { seed } => {
+                out.push(1);
+                out.extend(seed.to_le_bytes());
+            }
+
+            Packet::Inputs {
+                start,
+                inputs,
+                ack,
+                checksum,
+            } => {
+                out.push(2);
+                out.extend(start.to_le_bytes());
+                out.extend(ack.to_le_bytes());
+
+                match checksum {
+                    Some((tick, checksum)) => {
+                        out.push(1);
+                        out.extend(tick.to_le_bytes());
+                        out.extend(checksum.to_le_bytes());
+                    }
+                    None => out.push(0),
+                }
+
+                out.push(inputs.len() as u8);
+                for input in inputs {
+                    encode_input(*input, &mut out);
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Reads back what `encode` wrote, `None` when it's cut short, has
+    /// bytes left over, or is no packet at all.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+
+        let packet = match reader.u8()? {
+            0 => Packet::Join,
+
+            1 => Packet::Welcome {
+                seed: reader.u64()?,
+            },
+
+            2 => {
+                let start = reader.u64()?;
+                let ack = reader.u64()?;
+                let checksum = match reader.u8()? {
+                    0 => None,
+                    1 => Some((reader.u64()?, reader.u64()?)),
+                    _ => return None,
+                };
+                let inputs = (0..reader.u8()?)
+                    .map(|_| decode_input(&mut reader))
+                    .collect::<Option<_>>()?;
+
+                Packet::Inputs {
+                    start,
+                    inputs,
+                    ack,
+                    checksum,
+                }
+            }
+
+            _ => return None,
+        };
+
+        reader.0.is_empty().then_some(packet)
+    }
+}
+
+/// Set on the encoded aim of an input that fires.
+const FIRE_BIT: u16 = 1 << 15;
+
+/// Writes a quantized input as the number of steps it moves by on each
+/// axis and turns by, which decodes to the very same values.
+fn encode_input(input: PlayerInput, out: &mut Vec<u8>) {
+    let movement = (input.movement * MOVEMENT_STEPS).round();
+    let aim = (input.aim / std::f32::consts::TAU * AIM_STEPS).round() as u16;
+    let fire = if input.fire { FIRE_BIT } else { 0 };
+
+    out.extend((movement.x as i16).to_le_bytes());
+    out.extend((movement.y as i16).to_le_bytes());
+    out.extend((aim | fire).to_le_bytes());
+}
+
+fn decode_input(reader: &mut Reader) -> Option<PlayerInput> {
+    let x = i16::from_le_bytes(reader.bytes()?);
+    let y = i16::from_le_bytes(reader.bytes()?);
+    let aim = u16::from_le_bytes(reader.bytes()?);
+
+    Some(PlayerInput {
+        movement: Vec2::new(x as f32, y as f32) / MOVEMENT_STEPS,
+        aim: (aim & !FIRE_BIT) as f32 / AIM_STEPS * std::f32::consts::TAU,
+        fire: aim & FIRE_BIT != 0,
+    })
+}
+
+/// Takes the fields of a packet off the front of its bytes.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes().map(u8::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+}
+
+/// A non-blocking UDP socket that can hold packets back and drop some of
+/// them on the way out.
+struct Transport {
+    socket: UdpSocket,
+    latency: Duration,
+    loss: f64,
+    outgoing: VecDeque<(Instant, SocketAddr, Vec<u8>)>,
+}
+
+impl Transport {
+    fn bind(port: u16, latency: Duration, loss: f64) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            latency,
+            loss,
+            outgoing: VecDeque::new(),
+        })
+    }
+
+    fn send(&mut self, to: SocketAddr, packet: &Packet) {
+        let bytes = packet.encode();
+
+        if rand::thread_rng().gen_bool(self.loss) {
+            return;
+        }
+
+        self.outgoing
+            .push_back((Instant::now() + self.latency, to, bytes));
+        self.flush();
+    }
+
+    /// Sends the packets held back long enough.
+    fn flush(&mut self) {
+        let now = Instant::now();
+
+        while let Some((at, to, bytes)) = self.outgoing.front() {
+            if *at > now {
+                break;
+            }
+
+            match self.socket.send_to(bytes, to) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => warn!("failed to send to {}: {}", to, err),
+                Ok(_) => {}
+            }
+
+            self.outgoing.pop_front();
+        }
+    }
+
+    fn receive(&mut self) -> Vec<(SocketAddr, Packet)> {
+        let mut packets = Vec::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) => match Packet::decode(&buffer[..len]) {
+                    Some(packet) => packets.push((from, packet)),
+                    None => warn!("malformed packet from {}", from),
+                },
+
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                // A peer that went away shows up as an error on some
+                // platforms; the timeout takes care of it.
+                Err(_) => break,
+            }
+        }
+
+        packets
+    }
+}
+
+/// An online session with a single peer, lasting one run.
+///
+/// Both ends run the same simulation from the same seed. Inputs are
+/// exchanged every tick; the peer's are predicted until they arrive, and a
+/// wrong guess rolls the simulation back to a snapshot and replays it.
+/// Checksums of settled ticks go along with the inputs, and the session
+/// ends if the two ends ever disagree on one.
+pub struct NetSession {
+    transport: Transport,
+    role: Role,
+    peer: Option<SocketAddr>,
+    /// Seed of the run, once it has been agreed upon.
+    seed: Option<u64>,
+    running: bool,
+    local: usize,
+    last_join: Option<Instant>,
+    last_heard: Instant,
+    /// Inputs per seat and tick.
+    inputs: [BTreeMap<u64, PlayerInput>; 2],
+    /// Inputs each simulated tick actually ran with, guesses included.
+    used: BTreeMap<u64, [PlayerInput; 2]>,
+    /// State at the start of each tick that may still be rolled back, in
+    /// slots recorded over again `SNAPSHOTS` ticks later.
+    snapshots: Vec<Option<Snapshot>>,
+    /// Checksums of the state at the start of settled ticks per seat, the
+    /// peer's kept until compared with ours.
+    checksums: [BTreeMap<u64, u64>; 2],
+    /// Checksums are recorded for every settled tick before this one.
+    checked: u64,
+    /// First tick the peer's state was found to differ at.
+    desynced: Option<u64>,
+    /// Every peer input before this tick is known.
+    confirmed: u64,
+    /// The peer has every local input before this tick.
+    acked: u64,
+    /// Earliest tick that ran with a wrong guess.
+    mispredicted: Option<u64>,
+    /// Latest local input, recorded by the next tick.
+    sampled: PlayerInput,
+}
+
+impl NetSession {
+    fn open(config: NetConfig) -> io::Result<Self> {
+        let (port, peer, local) = match config.role {
+            Role::Host(port) => (port, None, 0),
+            Role::Join(addr) => (0, Some(addr), 1),
+        };
+
+        Ok(Self {
+            transport: Transport::bind(port, config.latency, config.loss)?,
+            role: config.role,
+            peer,
+            seed: None,
+            running: false,
+            local,
+            last_join: None,
+            last_heard: Instant::now(),
+            inputs: Default::default(),
+            used: BTreeMap::new(),
+            snapshots: vec![None; SNAPSHOTS as usize],
+            checksums: Default::default(),
+            checked: 0,
+            desynced: None,
+            confirmed: 0,
+            acked: 0,
+            mispredicted: None,
+            sampled: PlayerInput::default(),
+        })
+    }
+
+    fn remote(&self) -> usize {
+        1 - self.local
+    }
+
+    /// What the main menu shows while the session is being set up.
+    pub fn status(&self) -> Option<String> {
+        if let Some(tick) = self.desynced {
+            return Some(format!("Lost sync with the peer at tick {}.", tick));
+        }
+
+        match (self.role, self.seed) {
+            (Role::Host(port), None) => {
+                Some(format!("Waiting for a player on port {}...", port))
+            }
+            (Role::Join(addr), None) => Some(format!("Joining {}...", addr)),
+            (_, Some(_)) if !self.running => {
+                Some("Online session over.".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether every tick before `tick` ran with the peer's actual inputs,
+    /// so none of it can be rolled back anymore.
+    pub fn is_settled(&self, tick: u64) -> bool {
+        tick <= self.confirmed
+    }
+
+    /// The seat's input for `tick`, or the last one known before it.
+    fn input(&self, seat: usize, tick: u64) -> PlayerInput {
+        self.inputs[seat]
+            .range(..=tick)
+            .next_back()
+            .map(|(_, input)| *input)
+            .unwrap_or_default()
+    }
+
+    /// Forgets the previous run and starts the new one on tick `0`, the
+    /// first ticks running without any input as they are held back.
+    fn reset(&mut self) {
+        self.used.clear();
+        self.snapshots.fill(None);
+        self.checksums = Default::default();
+        self.checked = 0;
+        self.mispredicted = None;
+        self.sampled = PlayerInput::default();
+
+        for inputs in self.inputs.iter_mut() {
+            inputs.clear();
+            for tick in 0..INPUT_DELAY {
+                inputs.insert(tick, PlayerInput::default());
+            }
+        }
+
+        self.confirmed = INPUT_DELAY;
+        self.acked = INPUT_DELAY;
+    }
+
+    fn receive(&mut self, world: &mut World) {
+        for (from, packet) in self.transport.receive() {
+            if self.peer.is_some() && self.peer != Some(from) {
+                continue;
+            }
+
+            self.last_heard = Instant::now();
+
+            match packet {
+                Packet::Join => {
+                    if let Role::Host(_) = self.role {
+                        let seed = match self.seed {
+                            Some(seed) => Some(seed),
+                            None if Self::can_start(world) => {
+                                let seed = rand::thread_rng().gen();
+                                self.peer = Some(from);
+                                self.start(world, seed);
+                                Some(seed)
+                            }
+                            // Busy elsewhere, the peer will ask again.
+                            None => None,
+                        };
+
+                        if let Some(seed) = seed {
+                            self.transport
+                                .send(from, &Packet::Welcome { seed });
+                        }
+                    }
+                }
+
+                Packet::Welcome { seed } => {
+                    if self.seed.is_none() && Self::can_start(world) {
+                        self.start(world, seed);
+                    }
+                }
+
+                Packet::Inputs {
+                    start,
+                    inputs,
+                    ack,
+                    checksum,
+                } => {
+                    self.acked = self.acked.max(ack);
+                    self.record_remote_inputs(start, inputs);
+
+                    if let Some((tick, checksum)) = checksum {
+                        self.checksums[self.remote()].insert(tick, checksum);
+                    }
+                }
+            }
+        }
+    }
+
+    fn can_start(world: &World) -> bool {
+        *world.resource::<State<GameState>>().current() == GameState::MainMenu
+    }
+
+    fn start(&mut self, world: &mut World, seed: u64) {
+        info!("starting online run with seed {:016x}", seed);
+
+        self.seed = Some(seed);
+        self.running = true;
+        self.last_heard = Instant::now();
+
+        *world.resource_mut::<GameMode>() =
+            GameMode::Online { local: self.local };
+        world.resource_mut::<Campaign>().stop();
+        world.resource_mut::<NextSeed>().0 = Some(seed);
+        world
+            .resource_mut::<State<GameState>>()
+            .overwrite_replace(GameState::Level)
+            .unwrap();
+    }
+
+    fn record_remote_inputs(&mut self, start: u64, inputs: Vec<PlayerInput>) {
+        if !self.running {
+            return;
+        }
+
+        let remote = self.remote();
+
+        for (tick, input) in (start..).zip(inputs) {
+            if tick < self.confirmed {
+                continue;
+            }
+
+            let guessed = self.used.get(&tick).map(|used| used[remote]);
+            if guessed.is_some_and(|guessed| guessed != input) {
+                self.mispredicted =
+                    Some(self.mispredicted.map_or(tick, |t| t.min(tick)));
+            }
+
+            self.inputs[remote].insert(tick, input);
+        }
+
+        while self.inputs[remote].contains_key(&self.confirmed) {
+            self.confirmed += 1;
+        }
+    }
+
+    /// Sends every local input the peer hasn't acknowledged yet, so a lost
+    /// packet is made up for by the next one.
+    fn send_inputs(&mut self) {
+        let peer = match self.peer {
+            Some(peer) => peer,
+            None => return,
+        };
+
+        let inputs: Vec<PlayerInput> = self.inputs[self.local]
+            .range(self.acked..)
+            .take(MAX_INPUTS_PER_PACKET)
+            .map(|(_, input)| *input)
+            .collect();
+
+        if inputs.is_empty() && !self.running {
+            return;
+        }
+
+        let checksum = self.checksums[self.local]
+            .last_key_value()
+            .map(|(tick, checksum)| (*tick, *checksum));
+
+        let packet = Packet::Inputs {
+            start: self.acked,
+            inputs,
+            ack: self.confirmed,
+            checksum,
+        };
+        self.transport.send(peer, &packet);
+    }
+
+    /// Drops whatever can't be rolled back to or resent anymore.
+    fn prune(&mut self) {
+        let confirmed = self.confirmed;
+        let oldest_local = self.acked.min(confirmed);
+
+        self.used = self.used.split_off(&confirmed);
+        for checksums in self.checksums.iter_mut() {
+            *checksums = checksums
+                .split_off(&confirmed.saturating_sub(CHECKSUM_HISTORY));
+        }
+        self.inputs[self.local] =
+            self.inputs[self.local].split_off(&oldest_local);
+
+        // The last confirmed input is still what the peer is guessed to do.
+        let remote = self.remote();
+        self.inputs[remote] =
+            self.inputs[remote].split_off(&confirmed.saturating_sub(1));
+    }
+
+    fn slot(tick: u64) -> usize {
+        (tick % SNAPSHOTS) as usize
+    }
+
+    /// The state `tick` started from, while it's still kept.
+    fn snapshot(&self, tick: u64) -> Option<&Snapshot> {
+        self.snapshots[Self::slot(tick)]
+            .as_ref()
+            .filter(|snapshot| snapshot.tick == tick)
+    }
+
+    /// Records the state `tick` starts from over the oldest snapshot.
+    fn record_snapshot(&mut self, world: &mut World, tick: u64) {
+        let slot = &mut self.snapshots[Self::slot(tick)];
+        let mut snapshot = slot.take().unwrap_or_default();
+
+        if snapshot.record(world) {
+            *slot = Some(snapshot);
+        }
+    }
+
+    /// Records the checksum of every tick up to `current` that ran with
+    /// the peer's actual inputs and has been replayed if it had to be.
+    fn settle(&mut self, current: u64) {
+        let settled = self.confirmed.min(current);
+
+        for tick in self.checked..settled {
+            if let Some(checksum) = self.snapshot(tick).map(Snapshot::checksum)
+            {
+                self.checksums[self.local].insert(tick, checksum);
+            }
+        }
+
+        self.checked = self.checked.max(settled);
+    }
+
+    /// Compares the peer's checksums with ours, returning the first tick
+    /// they differ at. Those of the peer's ticks we haven't settled yet are
+    /// kept for later.
+    fn compare_checksums(&mut self) -> Option<u64> {
+        let mut pending = std::mem::take(&mut self.checksums[self.remote()]);
+        let ours = &self.checksums[self.local];
+        let mut desynced = None;
+
+        pending.retain(|tick, theirs| match ours.get(tick) {
+            Some(ours) => {
+                if ours != theirs && desynced.is_none() {
+                    desynced = Some(*tick);
+                }
+                false
+            }
+            None => true,
+        });
+
+        self.checksums[self.remote()] = pending;
+        desynced
+    }
+
+    /// Ends the run and goes back to the main menu, the rest of the
+    /// session being lost.
+    fn abandon(&mut self, world: &mut World) {
+        self.running = false;
+        *world.resource_mut::<GameMode>() = GameMode::Solo;
+        world.resource_mut::<SimulationTime>().set_stalled(false);
+        world
+            .resource_mut::<State<GameState>>()
+            .overwrite_replace(GameState::MainMenu)
+            .unwrap();
+    }
+}
+
+fn quantize(input: PlayerInput) -> PlayerInput {
+    let turn = std::f32::consts::TAU;
+
+    PlayerInput {
+        movement: (input.movement * MOVEMENT_STEPS).round() / MOVEMENT_STEPS,
+        aim: (input.aim.rem_euclid(turn) / turn * AIM_STEPS).round()
+            / AIM_STEPS
+            * turn,
+        fire: input.fire,
+    }
+}
+
+/// Plays a run against a peer over UDP, when started with `--host` or
+/// `--join`.
+pub struct NetcodePlugin;
+
+impl Plugin for NetcodePlugin {
+    fn build(&self, app: &mut App) {
+        let config = match NetConfig::from_args() {
+            Some(config) => config,
+            None => return,
+        };
+
+        match NetSession::open(config) {
+            Ok(session) => {
+                app.insert_resource(session);
+            }
+            Err(err) => {
+                error!("failed to open the network session: {}", err);
+                return;
+            }
+        }
+
+        app.add_system_to_stage(
+            FixedUpdate,
+            Self::network
+                .exclusive_system()
+                .before(SimulationSystem::Run),
+        )
+        .add_simulation_system_to_stage(
+            SimulationStage::PreTick,
+            Self::prepare_tick.exclusive_system(),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Level).with_system(Self::begin_run),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Level).with_system(Self::end_run),
+        );
+    }
+}
+
+impl NetcodePlugin {
+    fn begin_run(
+        mut session: ResMut<NetSession>,
+        mut time: ResMut<SimulationTime>,
+    ) {
+        if session.running {
+            session.reset();
+            time.set_tick(0);
+        }
+    }
+
+    /// The session lasts a single run; later ones are played alone.
+    fn end_run(
+        mut session: ResMut<NetSession>,
+        mut mode: ResMut<GameMode>,
+        mut time: ResMut<SimulationTime>,
+    ) {
+        if session.running {
+            session.running = false;
+            *mode = GameMode::Solo;
+            time.set_stalled(false);
+        }
+    }
+
+    /// Exchanges packets, rolls back on a wrong guess, and holds the
+    /// simulation when it gets too far ahead of the peer. The run ends as
+    /// soon as both ends are found to disagree on a settled tick.
+    fn network(world: &mut World) {
+        let mut session = match world.remove_resource::<NetSession>() {
+            Some(session) => session,
+            None => return,
+        };
+
+        session.transport.flush();
+        session.receive(world);
+
+        if let (Role::Join(addr), None) = (session.role, session.seed) {
+            let due = session
+                .last_join
+                .is_none_or(|at| at.elapsed() >= JOIN_INTERVAL);
+
+            if due {
+                session.last_join = Some(Instant::now());
+                session.transport.send(addr, &Packet::Join);
+            }
+        }
+
+        if session.running && session.last_heard.elapsed() > PEER_TIMEOUT {
+            warn!("lost connection to the peer");
+            session.abandon(world);
+        }
+
+        let mispredicted = session.mispredicted.take();
+        let running = session.running;
+        world.insert_resource(session);
+
+        if !running {
+            world.resource_mut::<NetSession>().send_inputs();
+            return;
+        }
+
+        if let Some(tick) = mispredicted {
+            Self::roll_back(world, tick);
+        }
+
+        let local = world.resource::<NetSession>().local;
+        let mut input_query = world.query::<(&Player, &PlayerInput)>();
+        let sampled = input_query
+            .iter(world)
+            .find(|(player, _)| player.id == local)
+            .map(|(_, input)| quantize(*input));
+
+        let tick = world.resource::<SimulationTime>().tick();
+        let mut session = world.resource_mut::<NetSession>();
+        if let Some(sampled) = sampled {
+            session.sampled = sampled;
+        }
+        let stalled = tick >= session.confirmed + MAX_ROLLBACK;
+        session.settle(tick);
+        let desynced = session.compare_checksums();
+        session.send_inputs();
+        session.prune();
+
+        world.resource_mut::<SimulationTime>().set_stalled(stalled);
+
+        if let Some(tick) = desynced {
+            error!("out of sync with the peer from tick {}", tick);
+            world.resource_scope(|world, mut session: Mut<NetSession>| {
+                session.desynced = Some(tick);
+                session.abandon(world);
+            });
+        }
+    }
+
+    /// Goes back to the start of `tick` and replays up to where the
+    /// simulation was, with the inputs known now. The replayed ticks' events
+    /// were presented on their first run, so they are dropped.
+    fn roll_back(world: &mut World, tick: u64) {
+        let current = world.resource::<SimulationTime>().tick();
+        if tick >= current {
+            return;
+        }
+
+        let restored = world.resource_scope(
+            |world, session: Mut<NetSession>| match session.snapshot(tick) {
+                Some(snapshot) => {
+                    snapshot.restore(world);
+                    true
+                }
+                None => false,
+            },
+        );
+
+        if !restored {
+            warn!("no snapshot to roll back to tick {}", tick);
+            return;
+        }
+
+        simulation::run_ticks(world, current - tick);
+        events::clear(world);
+    }
+
+    /// Records the state the tick starts from and feeds every ship the
+    /// input it runs with, the peer's being guessed when still unknown.
+    fn prepare_tick(world: &mut World) {
+        if *world.resource::<State<GameState>>().current() != GameState::Level {
+            return;
+        }
+
+        let mut session = match world.remove_resource::<NetSession>() {
+            Some(session) if session.running => session,
+            Some(session) => {
+                world.insert_resource(session);
+                return;
+            }
+            None => return,
+        };
+
+        let tick = world.resource::<SimulationTime>().tick();
+        let local = session.local;
+        let sampled = session.sampled;
+        session.inputs[local]
+            .entry(tick + INPUT_DELAY)
+            .or_insert(sampled);
+
+        session.record_snapshot(world, tick);
+
+        let inputs = [session.input(0, tick), session.input(1, tick)];
+        session.used.insert(tick, inputs);
+        world.insert_resource(session);
+
+        let mut input_query = world.query::<(&Player, &mut PlayerInput)>();
+        for (player, mut input) in input_query.iter_mut(world) {
+            if let Some(used) = inputs.get(player.id) {
+                *input = *used;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, TAU};
+
+    use super::*;
+
+    fn session() -> NetSession {
+        let mut session = NetSession::open(NetConfig {
+            role: Role::Host(0),
+            latency: Duration::ZERO,
+            loss: 0.,
+        })
+        .unwrap();
+
+        session.running = true;
+        session.reset();
+        session
+    }
+
+    fn moving() -> PlayerInput {
+        PlayerInput {
+            movement: Vec2::X,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn quantize_is_stable() {
+        let input = PlayerInput {
+            movement: Vec2::new(0.3, -0.71),
+            aim: 1.234,
+            fire: true,
+        };
+
+        let quantized = quantize(input);
+        assert_eq!(quantize(quantized), quantized);
+        assert!(quantized.fire);
+        assert!((quantized.aim - input.aim).abs() <= TAU / AIM_STEPS);
+        assert!(
+            (quantized.movement - input.movement).abs().max_element()
+                <= 1. / MOVEMENT_STEPS
+        );
+    }
+
+    #[test]
+    fn quantize_wraps_aim_into_a_turn() {
+        let quantized = quantize(PlayerInput {
+            aim: -FRAC_PI_2,
+            ..Default::default()
+        });
+
+        assert!((quantized.aim - 3. * FRAC_PI_2).abs() <= TAU / AIM_STEPS);
+    }
+
+    #[test]
+    fn packets_survive_encoding() {
+        let inputs = vec![
+            PlayerInput::default(),
+            quantize(PlayerInput {
+                movement: Vec2::new(-1., 0.37),
+                aim: TAU - 0.0001,
+                fire: true,
+            }),
+            quantize(PlayerInput {
+                movement: Vec2::new(0.7, -0.7),
+                aim: 2.5,
+                fire: false,
+            }),
+        ];
+
+        for packet in [
+            Packet::Join,
+            Packet::Welcome { seed: u64::MAX },
+            Packet::Inputs {
+                start: 1 << 40,
+                inputs,
+                ack: 7,
+                checksum: Some((5, 0xdead_beef)),
+            },
+            Packet::Inputs {
+                start: 0,
+                inputs: Vec::new(),
+                ack: 0,
+                checksum: None,
+            },
+        ] {
+            assert_eq!(Packet::decode(&packet.encode()), Some(packet));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let bytes = Packet::Inputs {
+            start: 3,
+            inputs: vec![moving()],
+            ack: 2,
+            checksum: None,
+        }
+        .encode();
+
+        assert_eq!(Packet::decode(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Packet::decode(&[&bytes[..], &[0]].concat()), None);
+        assert_eq!(Packet::decode(&[3]), None);
+        assert_eq!(Packet::decode(&[]), None);
+    }
+
+    #[test]
+    fn flags_first_differing_checksum() {
+        let mut session = session();
+        session.checksums[0] = BTreeMap::from([(5, 1), (6, 2), (7, 3)]);
+        session.checksums[1] = BTreeMap::from([(6, 2), (9, 9)]);
+        assert_eq!(session.compare_checksums(), None);
+
+        // Compared checksums are dropped, the others wait for ours.
+        assert_eq!(session.checksums[1], BTreeMap::from([(9, 9)]));
+
+        session.checksums[1].insert(7, 4);
+        assert_eq!(session.compare_checksums(), Some(7));
+    }
+
+    #[test]
+    fn confirms_contiguous_inputs_only() {
+        let mut session = session();
+
+        session.record_remote_inputs(INPUT_DELAY + 1, vec![moving()]);
+        assert_eq!(session.confirmed, INPUT_DELAY);
+
+        session.record_remote_inputs(INPUT_DELAY, vec![moving()]);
+        assert_eq!(session.confirmed, INPUT_DELAY + 2);
+        assert_eq!(session.mispredicted, None);
+    }
+
+    #[test]
+    fn flags_earliest_wrong_guess() {
+        let mut session = session();
+        for tick in INPUT_DELAY..INPUT_DELAY + 4 {
+            session.used.insert(tick, [PlayerInput::default(); 2]);
+        }
+
+        let start = INPUT_DELAY;
+        let inputs =
+            vec![PlayerInput::default(), PlayerInput::default(), moving()];
+        session.record_remote_inputs(start + 1, inputs[1..].to_vec());
+        assert_eq!(session.mispredicted, Some(start + 2));
+
+        session.record_remote_inputs(start, inputs);
+        assert_eq!(session.mispredicted, Some(start + 2));
+        assert_eq!(session.confirmed, start + 3);
+    }
+
+    #[test]
+    fn ignores_inputs_already_confirmed() {
+        let mut session = session();
+        session.used.insert(0, [PlayerInput::default(); 2]);
+
+        session.record_remote_inputs(0, vec![moving()]);
+        assert_eq!(session.mispredicted, None);
+        assert_eq!(session.input(1, 0), PlayerInput::default());
+    }
+}
@@ -1,13 +1,37 @@
-use bevy::{prelude::*, sprite::collide_aabb::collide};
+use bevy::{
+    ecs::system::SystemParam, prelude::*, sprite::collide_aabb::collide,
+};
 use rand::Rng;
 
 use crate::{
-    common::{self, Collider, Velocity},
-    graphics::TexturesSheets,
-    state::GameState,
-    HEIGHT, WIDTH,
+    boss::{self, Boss},
+    camera::VIEW_SIZE,
+    common::{self, Collider, Playfield, Threat, Velocity},
+    enemies,
+    graphics::{self, TexturesSheets},
+    minimap::MinimapBlip,
+    player::{self, Player},
+    pool::{Pooled, Pools},
+    run::{GameMode, RunSystem, Wave},
+    simulation::{GameRng, Interpolated, SimulationApp, SimulationTime},
+    snapshot::MotionState,
+    stage::Campaign,
+    state::{GameState, LevelScoped},
 };
 
+/// What it takes to spawn obstacles and enemies, break them apart and
+/// release them.
+#[derive(SystemParam)]
+pub struct Spawner<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    pub pools: ResMut<'w, Pools>,
+    pub rng: ResMut<'w, GameRng>,
+    pub ts: Res<'w, TexturesSheets>,
+}
+
+/// Anything holding back the next wave.
+type WaveRemaining = Or<((With<Obstacle>, Without<Pooled>), With<Boss>)>;
+
 #[derive(Component)]
 pub struct Obstacle {
     pub can_split: bool,
@@ -20,25 +44,155 @@ pub struct MovingObstacle {
     angle: f32,
 }
 
+impl MovingObstacle {
+    pub fn is_moving(&self) -> bool {
+        !self.duration.finished()
+    }
+}
+
+impl From<&MovingObstacle> for MotionState {
+    fn from(moving: &MovingObstacle) -> Self {
+        Self {
+            direction: moving.direction,
+            duration: (&moving.duration).into(),
+            angle: moving.angle,
+        }
+    }
+}
+
+impl From<&MotionState> for MovingObstacle {
+    fn from(state: &MotionState) -> Self {
+        Self {
+            direction: state.direction,
+            duration: (&state.duration).into(),
+            angle: state.angle,
+        }
+    }
+}
+
+const DEBRIS_SPEED: f32 = 90.;
+
+#[derive(Bundle)]
+struct DebrisBundle {
+    #[bundle]
+    sprite: SpriteSheetBundle,
+    moving: MovingObstacle,
+    obstacle: Obstacle,
+    threat: Threat,
+    blip: MinimapBlip,
+    velocity: Velocity,
+    collider: Collider,
+    interpolated: Interpolated,
+}
+
 pub struct ObstaclePlugin;
 
 impl Plugin for ObstaclePlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_enter(GameState::Level)
-                .with_system(Self::setup_obstacles),
+                .with_system(Self::setup_obstacles.after(RunSystem::Reset)),
         )
         .add_system_set(
+            SystemSet::on_exit(GameState::Level)
+                .with_system(Self::release_debris),
+        )
+        .add_simulation_system_set(
             SystemSet::on_update(GameState::Level)
-                .with_system(Self::move_obstacles),
+                .with_system(Self::move_obstacles)
+                .with_system(Self::next_wave),
         );
     }
 }
 
 impl ObstaclePlugin {
-    fn setup_obstacles(mut commands: Commands, ts: Res<TexturesSheets>) {
-        let mut rng = rand::thread_rng();
-        let total: u32 = rng.gen_range(5..=15);
+    /// Only the endless waves start with a random field; the campaign's
+    /// stages place everything themselves.
+    fn setup_obstacles(
+        mut commands: Commands,
+        mut rng: ResMut<GameRng>,
+        mode: Res<GameMode>,
+        campaign: Res<Campaign>,
+        playfield: Res<Playfield>,
+        ts: Res<TexturesSheets>,
+    ) {
+        if campaign.is_active() {
+            return;
+        }
+
+        let players = mode.players();
+        let seats: Vec<Vec2> = (0..players)
+            .map(|id| player::seat(id, players, &playfield))
+            .collect();
+
+        Self::spawn_field(&mut commands, &mut rng, &playfield, &ts, &seats);
+        enemies::spawn_squad(
+            &mut commands,
+            &mut rng,
+            &playfield,
+            &ts,
+            enemies::squad_size(Wave::default().0, *mode),
+            &seats,
+        );
+    }
+
+    /// Starts the next wave once the last obstacle of the current one is
+    /// gone, and the boss, if any, is defeated. The campaign has no waves.
+    fn next_wave(
+        mut spawner: Spawner,
+        mut wave: ResMut<Wave>,
+        mode: Res<GameMode>,
+        campaign: Res<Campaign>,
+        playfield: Res<Playfield>,
+        remaining_query: Query<(), WaveRemaining>,
+        player_query: Query<&Transform, With<Player>>,
+    ) {
+        if campaign.is_active() || !remaining_query.is_empty() {
+            return;
+        }
+
+        wave.0 += 1;
+
+        let avoid: Vec<Vec2> = player_query
+            .iter()
+            .map(|transform| transform.translation.truncate())
+            .collect();
+
+        let Spawner {
+            commands, rng, ts, ..
+        } = &mut spawner;
+
+        Self::spawn_field(commands, rng, &playfield, ts, &avoid);
+
+        match boss::for_wave(wave.0, *mode) {
+            Some(def) => {
+                let position = boss::spawn_point(def, &playfield, &avoid);
+                boss::spawn_boss(commands, ts, def, position);
+            }
+            None => enemies::spawn_squad(
+                commands,
+                rng,
+                &playfield,
+                ts,
+                enemies::squad_size(wave.0, *mode),
+                &avoid,
+            ),
+        }
+    }
+
+    /// Scatters large obstacles across the world, keeping clear of the
+    /// positions in `avoid`.
+    fn spawn_field(
+        commands: &mut Commands,
+        rng: &mut GameRng,
+        playfield: &Playfield,
+        ts: &TexturesSheets,
+        avoid: &[Vec2],
+    ) {
+        // Keep roughly the same density of obstacles however big the world.
+        let screens = (playfield.size() / VIEW_SIZE).max(Vec2::ONE);
+        let total =
+            (rng.gen_range(5..=15) as f32 * screens.x * screens.y) as u32;
 
         for _ in 0..total {
             let sprite_index = rng.gen_range(0..=3);
@@ -48,49 +202,52 @@ impl ObstaclePlugin {
             let width = collider.width as u32;
             let height = collider.height as u32;
 
-            let sprite = TextureAtlasSprite::new(sprite_index);
-
-            let x = rng.gen_range(width..((WIDTH as u32) - width)) as f32;
-            let y = rng.gen_range(height..((HEIGHT as u32) - height)) as f32;
+            let x =
+                rng.gen_range(width..((playfield.width as u32) - width)) as f32;
+            let y = rng.gen_range(height..((playfield.height as u32) - height))
+                as f32;
 
             let mut transform = Transform::from_xyz(x, y, 1.);
 
-            let collision = collide(
-                Vec3::new(WIDTH / 2., HEIGHT / 2., 1.),
-                Vec2::new(99., 75.),
-                transform.translation,
-                collider.clone().into(),
-            )
-            .is_some();
+            let collision = avoid.iter().any(|position| {
+                collide(
+                    position.extend(1.),
+                    Vec2::new(99., 75.),
+                    transform.translation,
+                    collider.into(),
+                )
+                .is_some()
+            });
 
             if collision {
                 transform.translation.x -= (width + 10) as f32;
                 transform.translation.y -= (height + 10) as f32;
             }
 
-            commands
-                .spawn_bundle(SpriteSheetBundle {
-                    sprite,
-                    texture_atlas: ts.obstacles.clone(),
-                    transform,
-                    ..Default::default()
-                })
-                .insert(Obstacle { can_split: true })
-                .insert(collider);
+            spawn_obstacle(commands, ts, sprite_index, transform, collider);
+        }
+    }
+
+    fn release_debris(
+        mut commands: Commands,
+        mut pools: ResMut<Pools>,
+        debris_query: Query<Entity, (With<MovingObstacle>, Without<Pooled>)>,
+    ) {
+        for e in debris_query.iter() {
+            pools.debris.release(&mut commands, e);
         }
     }
 
     fn move_obstacles(
-        mut obstacles_query: Query<(
-            &mut Transform,
-            &mut MovingObstacle,
-            &Velocity,
-        )>,
-        time: Res<Time>,
+        mut obstacles_query: Query<
+            (&mut Transform, &mut MovingObstacle, &Velocity),
+            Without<Pooled>,
+        >,
+        time: Res<SimulationTime>,
     ) {
         for (mut transform, mut mv_obs, velocity) in obstacles_query
             .iter_mut()
-            .filter(|(_, mv_obs, _)| !mv_obs.duration.finished())
+            .filter(|(_, mv_obs, _)| mv_obs.is_moving())
         {
             let MovingObstacle {
                 direction,
@@ -114,7 +271,7 @@ impl ObstaclePlugin {
         }
     }
 
-    fn for_large_obstacle(sprite_index: usize) -> Option<Collider> {
+    pub fn for_large_obstacle(sprite_index: usize) -> Option<Collider> {
         match sprite_index {
             0 => Some(Collider {
                 height: 98.0,
@@ -189,12 +346,12 @@ impl ObstaclePlugin {
 
 pub fn spawn_small_obstacles(
     commands: &mut Commands,
+    pools: &mut Pools,
+    rng: &mut GameRng,
     ts: &TexturesSheets,
     initial_pos: Vec2,
     angle: f32,
 ) {
-    let mut rng = rand::thread_rng();
-
     let total: u32 = rng.gen_range(3..=6);
 
     for _ in 0..total {
@@ -203,8 +360,6 @@ pub fn spawn_small_obstacles(
         let collider =
             ObstaclePlugin::for_small_obstacle(sprite_index).unwrap();
 
-        let sprite = TextureAtlasSprite::new(sprite_index);
-
         let mut offset = rng.gen_range(1..=100) as f32 / 100.;
 
         let angle = angle + offset;
@@ -219,20 +374,74 @@ pub fn spawn_small_obstacles(
 
         offset += 1.;
 
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                sprite,
-                texture_atlas: ts.obstacles2.clone(),
-                transform,
-                ..Default::default()
-            })
-            .insert(MovingObstacle {
+        spawn_debris(
+            commands,
+            pools,
+            ts,
+            sprite_index,
+            transform,
+            MovingObstacle {
                 direction,
                 duration: Timer::from_seconds(offset, false),
                 angle: offset,
-            })
-            .insert(Obstacle { can_split: false })
-            .insert(Velocity { vx: 90., vy: 90. })
-            .insert(collider);
+            },
+            collider,
+        );
     }
 }
+
+/// A large obstacle, which breaks apart into debris when shot.
+pub fn spawn_obstacle(
+    commands: &mut Commands,
+    ts: &TexturesSheets,
+    sprite_index: usize,
+    transform: Transform,
+    collider: Collider,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite::new(sprite_index),
+            texture_atlas: ts.obstacles.clone(),
+            transform,
+            ..Default::default()
+        })
+        .insert(Obstacle { can_split: true })
+        .insert(LevelScoped)
+        .insert(Threat)
+        .insert(MinimapBlip::new(graphics::ASTEROID_GREY, 4.))
+        .insert(collider)
+        .id()
+}
+
+/// A piece of debris drifting away from a broken obstacle.
+pub fn spawn_debris(
+    commands: &mut Commands,
+    pools: &mut Pools,
+    ts: &TexturesSheets,
+    sprite_index: usize,
+    transform: Transform,
+    moving: MovingObstacle,
+    collider: Collider,
+) -> Entity {
+    pools.debris.spawn(
+        commands,
+        DebrisBundle {
+            sprite: SpriteSheetBundle {
+                sprite: TextureAtlasSprite::new(sprite_index),
+                texture_atlas: ts.obstacles2.clone(),
+                transform,
+                ..Default::default()
+            },
+            moving,
+            obstacle: Obstacle { can_split: false },
+            threat: Threat,
+            blip: MinimapBlip::new(graphics::ASTEROID_GREY, 2.),
+            velocity: Velocity {
+                vx: DEBRIS_SPEED,
+                vy: DEBRIS_SPEED,
+            },
+            collider,
+            interpolated: Interpolated::default(),
+        },
+    )
+}
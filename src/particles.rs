@@ -0,0 +1,322 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    events::{EnemyDestroyed, ObstacleDestroyed},
+    graphics,
+    player::Player,
+    pool::{Pooled, Pools},
+    state::GameState,
+};
+
+#[derive(Clone, Copy)]
+pub enum EmitterMode {
+    /// Emits `count` particles once, then despawns the emitter.
+    Burst { count: u32 },
+    /// Emits `rate` particles per second for as long as it is enabled.
+    Continuous { rate: f32 },
+}
+
+/// Spawns short-lived sprites around its own position. Particles are purely
+/// cosmetic, so they live outside the simulation and use their own RNG.
+#[derive(Component, Clone)]
+pub struct ParticleEmitter {
+    pub mode: EmitterMode,
+    pub enabled: bool,
+    pub lifetime: f32,
+    pub speed: (f32, f32),
+    /// Emission angle in the emitter's local space, in radians.
+    pub direction: f32,
+    pub spread: f32,
+    pub drag: f32,
+    pub size: f32,
+    pub color: (Color, Color),
+    pub scale: (f32, f32),
+    pub z: f32,
+    accumulator: f32,
+}
+
+impl ParticleEmitter {
+    pub fn explosion() -> Self {
+        Self {
+            mode: EmitterMode::Burst { count: 28 },
+            enabled: true,
+            lifetime: 0.7,
+            speed: (60., 220.),
+            direction: 0.,
+            spread: PI,
+            drag: 3.,
+            size: 7.,
+            color: (
+                Color::hex(graphics::EXPLOSION_ORANGE).unwrap(),
+                transparent(graphics::BG_COLOR),
+            ),
+            scale: (1.2, 0.2),
+            z: 1.5,
+            accumulator: 0.,
+        }
+    }
+
+    pub fn impact(direction: f32) -> Self {
+        Self {
+            mode: EmitterMode::Burst { count: 8 },
+            lifetime: 0.25,
+            speed: (80., 180.),
+            direction: direction + PI,
+            spread: PI / 4.,
+            drag: 6.,
+            size: 4.,
+            color: (
+                Color::hex(graphics::WHITE).unwrap(),
+                transparent(graphics::EXPLOSION_ORANGE),
+            ),
+            scale: (1., 0.3),
+            ..Self::explosion()
+        }
+    }
+
+    pub fn thruster() -> Self {
+        Self {
+            mode: EmitterMode::Continuous { rate: 60. },
+            enabled: false,
+            lifetime: 0.35,
+            speed: (90., 140.),
+            direction: -PI / 2.,
+            spread: PI / 10.,
+            drag: 2.,
+            size: 6.,
+            color: (
+                Color::hex(graphics::SPACESHIP_RED).unwrap(),
+                transparent(graphics::BG_COLOR),
+            ),
+            scale: (1., 0.1),
+            z: 0.9,
+            accumulator: 0.,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Particle {
+    velocity: Vec2,
+    drag: f32,
+    age: f32,
+    lifetime: f32,
+    color: (Color, Color),
+    scale: (f32, f32),
+}
+
+#[derive(Bundle)]
+struct ParticleBundle {
+    #[bundle]
+    sprite: SpriteBundle,
+    particle: Particle,
+}
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::attach_thruster)
+                .with_system(Self::spawn_explosions)
+                .with_system(Self::toggle_thruster)
+                .with_system(
+                    Self::emit_particles
+                        .after(Self::spawn_explosions)
+                        .after(Self::toggle_thruster),
+                )
+                .with_system(Self::update_particles),
+        );
+    }
+}
+
+impl ParticlesPlugin {
+    fn attach_thruster(
+        mut commands: Commands,
+        player_query: Query<Entity, Added<Player>>,
+    ) {
+        for player in player_query.iter() {
+            let thruster = commands
+                .spawn_bundle(SpatialBundle::from_transform(
+                    Transform::from_xyz(0., -40., 0.),
+                ))
+                .insert(ParticleEmitter::thruster())
+                .insert(Name::new("Thruster"))
+                .id();
+
+            commands.entity(player).add_child(thruster);
+        }
+    }
+
+    fn toggle_thruster(
+        player_query: Query<(&Player, &Children)>,
+        mut emitter_query: Query<&mut ParticleEmitter>,
+    ) {
+        for (player, children) in player_query.iter() {
+            for &child in children.iter() {
+                if let Ok(mut emitter) = emitter_query.get_mut(child) {
+                    emitter.enabled = player.just_moved;
+                }
+            }
+        }
+    }
+
+    fn spawn_explosions(
+        mut commands: Commands,
+        mut destroyed_evr: EventReader<ObstacleDestroyed>,
+        mut enemy_destroyed_evr: EventReader<EnemyDestroyed>,
+    ) {
+        for ev in enemy_destroyed_evr.iter() {
+            commands
+                .spawn_bundle(SpatialBundle::from_transform(
+                    Transform::from_translation(ev.position.extend(0.)),
+                ))
+                .insert(ParticleEmitter::explosion());
+        }
+
+        for ev in destroyed_evr.iter() {
+            let mut explosion = ParticleEmitter::explosion();
+            if !ev.split {
+                explosion.mode = EmitterMode::Burst { count: 12 };
+                explosion.speed = (40., 120.);
+            }
+
+            commands
+                .spawn_bundle(SpatialBundle::from_transform(
+                    Transform::from_translation(ev.position.extend(0.)),
+                ))
+                .insert(explosion);
+
+            commands
+                .spawn_bundle(SpatialBundle::from_transform(
+                    Transform::from_translation(ev.impact.extend(0.)),
+                ))
+                .insert(ParticleEmitter::impact(ev.direction));
+        }
+    }
+
+    fn emit_particles(
+        mut commands: Commands,
+        mut pools: ResMut<Pools>,
+        mut emitter_query: Query<(
+            Entity,
+            &GlobalTransform,
+            &mut ParticleEmitter,
+        )>,
+        time: Res<Time>,
+    ) {
+        let mut rng = rand::thread_rng();
+
+        for (e, transform, mut emitter) in emitter_query.iter_mut() {
+            let count = match emitter.mode {
+                EmitterMode::Burst { count } => {
+                    commands.entity(e).despawn_recursive();
+                    count
+                }
+
+                EmitterMode::Continuous { rate } => {
+                    if !emitter.enabled {
+                        emitter.accumulator = 0.;
+                        continue;
+                    }
+
+                    emitter.accumulator += rate * time.delta_seconds();
+                    let count = emitter.accumulator.floor();
+                    emitter.accumulator -= count;
+                    count as u32
+                }
+            };
+
+            let (_, rotation, position) =
+                transform.to_scale_rotation_translation();
+            let (_, _, heading) = rotation.to_euler(EulerRot::XYZ);
+
+            for _ in 0..count {
+                let angle = heading
+                    + emitter.direction
+                    + rng.gen_range(-emitter.spread..=emitter.spread);
+                let speed = rng.gen_range(emitter.speed.0..=emitter.speed.1);
+
+                pools.particles.spawn(
+                    &mut commands,
+                    ParticleBundle {
+                        sprite: SpriteBundle {
+                            sprite: Sprite {
+                                color: emitter.color.0,
+                                custom_size: Some(Vec2::splat(emitter.size)),
+                                ..Default::default()
+                            },
+                            transform: Transform::from_translation(
+                                position.truncate().extend(emitter.z),
+                            )
+                            .with_scale(Vec3::splat(emitter.scale.0)),
+                            ..Default::default()
+                        },
+                        particle: Particle {
+                            velocity: Vec2::new(angle.cos(), angle.sin())
+                                * speed,
+                            drag: emitter.drag,
+                            age: 0.,
+                            lifetime: emitter.lifetime,
+                            color: emitter.color,
+                            scale: emitter.scale,
+                        },
+                    },
+                );
+            }
+        }
+    }
+
+    fn update_particles(
+        mut commands: Commands,
+        mut pools: ResMut<Pools>,
+        mut particle_query: Query<
+            (Entity, &mut Transform, &mut Sprite, &mut Particle),
+            Without<Pooled>,
+        >,
+        time: Res<Time>,
+    ) {
+        let dt = time.delta_seconds();
+
+        for (e, mut transform, mut sprite, mut particle) in
+            particle_query.iter_mut()
+        {
+            particle.age += dt;
+
+            if particle.age >= particle.lifetime {
+                pools.particles.release(&mut commands, e);
+                continue;
+            }
+
+            let t = particle.age / particle.lifetime;
+
+            let drag = (1. - particle.drag * dt).max(0.);
+            particle.velocity *= drag;
+            transform.translation += particle.velocity.extend(0.) * dt;
+
+            let scale =
+                particle.scale.0 + (particle.scale.1 - particle.scale.0) * t;
+            transform.scale = Vec3::splat(scale);
+
+            sprite.color = lerp_color(particle.color.0, particle.color.1, t);
+        }
+    }
+}
+
+fn transparent(hex: &str) -> Color {
+    let mut color = Color::hex(hex).unwrap();
+    color.set_a(0.);
+    color
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let from = Vec4::from(from.as_rgba_f32());
+    let to = Vec4::from(to.as_rgba_f32());
+
+    let [r, g, b, a] = from.lerp(to, t).to_array();
+    Color::rgba(r, g, b, a)
+}
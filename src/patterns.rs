@@ -0,0 +1,511 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::Collider,
+    obstacles::Spawner,
+    projectiles::{self, Bullet},
+};
+
+/// Patterns bundled with the game, each loaded from
+/// `patterns/<name>.pattern.ron`.
+const BUNDLED: &[&str] = &[
+    "blaster",
+    "enemy_shot",
+    "ring",
+    "aimed_fan",
+    "wide_fan",
+    "spiral",
+    "reverse_spiral",
+    "flower",
+];
+
+/// Size of the bullet sprite.
+const BULLET_SIZE: Vec2 = Vec2::new(9., 37.);
+
+/// What every bullet of a pattern does once fired.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BulletDef {
+    pub damage: f32,
+    /// Seconds in flight.
+    pub lifetime: f32,
+}
+
+/// Fires bullets in a number of directions, any number of times. Angles are
+/// in degrees, counterclockwise.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Emitter {
+    pub count: u32,
+    /// Angle the directions are spread across, centered on the heading. At
+    /// 360 they go all around, evenly spaced.
+    pub spread: f32,
+    /// Turns the heading.
+    pub angle: f32,
+    /// Heads towards the target rather than along the parent's direction.
+    pub aim: bool,
+    /// How fast the heading turns, in degrees per second.
+    pub rotation: f32,
+    /// Seconds after the parent fired before the first volley.
+    pub delay: f32,
+    pub volleys: u32,
+    /// Seconds between two volleys.
+    pub interval: f32,
+    /// Fired along each direction instead of bullets, when there are any.
+    pub emitters: Vec<Emitter>,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            spread: 0.,
+            angle: 0.,
+            aim: false,
+            rotation: 0.,
+            delay: 0.,
+            volleys: 1,
+            interval: 0.,
+            emitters: Vec::new(),
+        }
+    }
+}
+
+impl Emitter {
+    /// Appends the angle of each bullet due at or after `from` and before
+    /// `to` seconds into the pattern, for volleys starting at `start`.
+    fn emit(
+        &self,
+        start: f32,
+        aim: Aim,
+        from: f32,
+        to: f32,
+        out: &mut Vec<f32>,
+    ) {
+        for volley in 0..self.volleys {
+            let time =
+                start + self.delay.max(0.) + self.interval * volley as f32;
+
+            // Everything after this volley, nested ones included, only
+            // comes later.
+            if time >= to {
+                break;
+            }
+
+            let heading = if self.aim { aim.target } else { aim.heading };
+            let heading = heading
+                + self.angle.to_radians()
+                + self.rotation.to_radians() * time;
+
+            for direction in self.directions(heading) {
+                if self.emitters.is_empty() {
+                    if time >= from {
+                        out.push(direction);
+                    }
+                    continue;
+                }
+
+                for emitter in self.emitters.iter() {
+                    let aim = Aim {
+                        heading: direction,
+                        ..aim
+                    };
+                    emitter.emit(time, aim, from, to, out);
+                }
+            }
+        }
+    }
+
+    /// Seconds after its parent fired that the last of its bullets is due.
+    fn duration(&self) -> f32 {
+        if self.volleys == 0 {
+            return 0.;
+        }
+
+        let last =
+            self.delay.max(0.) + self.interval * (self.volleys - 1) as f32;
+        let nested = self.emitters.iter().map(Emitter::duration);
+
+        last + nested.fold(0., f32::max)
+    }
+
+    fn directions(&self, heading: f32) -> impl Iterator<Item = f32> {
+        let spread = self.spread.to_radians();
+
+        let (first, step) = match self.count {
+            0 | 1 => (heading, 0.),
+            count if spread >= TAU => (heading, TAU / count as f32),
+            count => (heading - spread / 2., spread / (count - 1) as f32),
+        };
+
+        (0..self.count).map(move |i| first + step * i as f32)
+    }
+}
+
+/// Where an emitter points: along its parent's direction, or at the target.
+#[derive(Clone, Copy)]
+struct Aim {
+    heading: f32,
+    target: f32,
+}
+
+/// An emitter and the bullets it fires, starting over every `period`
+/// seconds. It must be done firing by then, or loading it fails.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "b3a4e0d2-7c5f-4f1e-8d36-9e2a61c4f5b8"]
+pub struct Pattern {
+    pub period: f32,
+    pub bullet: BulletDef,
+    pub emitter: Emitter,
+}
+
+impl Pattern {
+    /// Angles of the bullets due from `from` to `to` seconds into the
+    /// pattern, heading along `heading` or aiming at `target`, in radians.
+    pub fn shots(
+        &self,
+        from: f32,
+        to: f32,
+        heading: f32,
+        target: f32,
+    ) -> Vec<f32> {
+        let mut out = Vec::new();
+        let aim = Aim { heading, target };
+        self.emitter.emit(0., aim, from, to, &mut out);
+        out
+    }
+
+    /// Whether the pattern has fired everything before it starts over, as
+    /// nothing left past its period is ever fired.
+    fn check(&self) -> Result<(), String> {
+        let duration = self.emitter.duration();
+
+        if self.period <= 0. {
+            Err("the period must be positive".to_owned())
+        } else if duration >= self.period {
+            Err(format!(
+                "bullets are due {}s in, past the {}s period",
+                duration, self.period
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Angles of the bullets a weapon fires each time its trigger is
+    /// pulled, those due the moment the pattern starts.
+    pub fn trigger(&self, heading: f32, target: f32) -> Vec<f32> {
+        self.shots(0., f32::MIN_POSITIVE, heading, target)
+    }
+}
+
+/// How far into its pattern something firing one is.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Emission {
+    pub elapsed: f32,
+}
+
+impl Emission {
+    /// Moves `dt` seconds forward through `pattern`, starting it over as
+    /// many times as needed, and returns the angles of the bullets due.
+    /// Loaded patterns are done firing within their period, so starting
+    /// over never cuts one short.
+    pub fn advance(
+        &mut self,
+        pattern: &Pattern,
+        dt: f32,
+        heading: f32,
+        target: f32,
+    ) -> Vec<f32> {
+        let mut shots = Vec::new();
+        let mut from = self.elapsed;
+        let mut to = self.elapsed + dt;
+
+        while to >= pattern.period {
+            shots.extend(pattern.shots(from, pattern.period, heading, target));
+            from = 0.;
+            to -= pattern.period;
+        }
+
+        shots.extend(pattern.shots(from, to, heading, target));
+        self.elapsed = to;
+
+        shots
+    }
+}
+
+#[derive(Default)]
+struct PatternLoader;
+
+impl AssetLoader for PatternLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let pattern: Pattern = ron::de::from_bytes(bytes)?;
+            pattern.check().map_err(bevy::asset::Error::msg)?;
+
+            load_context.set_default_asset(LoadedAsset::new(pattern));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pattern.ron"]
+    }
+}
+
+struct Entry {
+    name: &'static str,
+    handle: Handle<Pattern>,
+    /// Copied out of the assets once loaded, so firing needs nothing else.
+    pattern: Option<Pattern>,
+}
+
+/// Every bundled pattern by name, in the order they're listed.
+pub struct Patterns(Vec<Entry>);
+
+impl FromWorld for Patterns {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+
+        let entries = BUNDLED
+            .iter()
+            .map(|name| Entry {
+                name,
+                handle: asset_server
+                    .load(&format!("patterns/{}.pattern.ron", name)),
+                pattern: None,
+            })
+            .collect();
+
+        Self(entries)
+    }
+}
+
+impl Patterns {
+    /// The pattern called `name`, unless it isn't loaded yet or there is no
+    /// such pattern.
+    pub fn get(&self, name: &str) -> Option<&Pattern> {
+        let entry = self.0.iter().find(|entry| entry.name == name);
+        if entry.is_none() {
+            warn!("unknown pattern {}", name);
+        }
+
+        entry?.pattern.as_ref()
+    }
+
+    /// Every pattern loaded so far.
+    #[cfg(debug_assertions)]
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Pattern)> {
+        self.0.iter().filter_map(|entry| {
+            entry.pattern.as_ref().map(|pattern| (entry.name, pattern))
+        })
+    }
+}
+
+/// Puts a bullet of `pattern` in flight along each of `angles`, leaving
+/// from `muzzle` away from `origin` on either axis.
+pub fn fire(
+    spawner: &mut Spawner,
+    pattern: &Pattern,
+    player: usize,
+    origin: Vec2,
+    muzzle: Vec2,
+    angles: &[f32],
+) {
+    for &angle in angles {
+        let direction = Vec2::from_angle(angle);
+        let position = origin + direction * muzzle;
+
+        let mut transform = Transform::from_translation(position.extend(1.));
+        transform.rotation = Quat::from_rotation_z(angle - FRAC_PI_2);
+
+        projectiles::spawn_bullet(
+            &mut spawner.commands,
+            &mut spawner.pools,
+            &spawner.ts,
+            transform,
+            Bullet {
+                player,
+                damage: pattern.bullet.damage,
+                direction,
+                duration: Timer::from_seconds(pattern.bullet.lifetime, false),
+                previous_position: position,
+            },
+            Collider {
+                width: BULLET_SIZE.x,
+                height: BULLET_SIZE.y,
+                offset: Some(BULLET_SIZE / 2.),
+            },
+        );
+    }
+}
+
+/// Loads the bullet patterns fired by the players, enemies and bosses.
+pub struct PatternsPlugin;
+
+impl Plugin for PatternsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Pattern>()
+            .init_asset_loader::<PatternLoader>()
+            .init_resource::<Patterns>()
+            .add_system(Self::collect);
+    }
+}
+
+impl PatternsPlugin {
+    /// Picks up patterns as they load, or reload.
+    fn collect(
+        mut patterns: ResMut<Patterns>,
+        assets: Res<Assets<Pattern>>,
+        mut asset_evr: EventReader<AssetEvent<Pattern>>,
+    ) {
+        for ev in asset_evr.iter() {
+            let handle = match ev {
+                AssetEvent::Created { handle }
+                | AssetEvent::Modified { handle } => handle,
+                AssetEvent::Removed { .. } => continue,
+            };
+
+            for entry in patterns.0.iter_mut() {
+                if entry.handle == *handle {
+                    entry.pattern = assets.get(handle).cloned();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_4, PI};
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_angles(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < EPSILON, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn pattern(period: f32, emitter: Emitter) -> Pattern {
+        Pattern {
+            period,
+            bullet: BulletDef {
+                damage: 1.,
+                lifetime: 1.,
+            },
+            emitter,
+        }
+    }
+
+    #[test]
+    fn ring_is_evenly_spaced() {
+        let ring = Emitter {
+            count: 4,
+            spread: 360.,
+            ..Default::default()
+        };
+
+        let directions: Vec<f32> = ring.directions(0.).collect();
+        assert_angles(&directions, &[0., FRAC_PI_2, PI, PI + FRAC_PI_2]);
+    }
+
+    #[test]
+    fn fan_is_centered_on_heading() {
+        let fan = Emitter {
+            count: 3,
+            spread: 90.,
+            ..Default::default()
+        };
+
+        let directions: Vec<f32> = fan.directions(FRAC_PI_2).collect();
+        assert_angles(&directions, &[FRAC_PI_4, FRAC_PI_2, 3. * FRAC_PI_4]);
+    }
+
+    #[test]
+    fn emission_wraps_around_period() {
+        let pattern = pattern(
+            1.,
+            Emitter {
+                delay: 0.5,
+                ..Default::default()
+            },
+        );
+
+        let mut emission = Emission { elapsed: 0.9 };
+        let shots = emission.advance(&pattern, 0.7, 0., 0.);
+        assert_eq!(shots.len(), 1);
+        assert!((emission.elapsed - 0.6).abs() < EPSILON);
+
+        let mut emission = Emission::default();
+        let shots = emission.advance(&pattern, 2., 0., 0.);
+        assert_eq!(shots.len(), 2);
+    }
+
+    #[test]
+    fn bundled_patterns_load() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+
+        for name in BUNDLED {
+            let path =
+                dir.join(format!("assets/patterns/{}.pattern.ron", name));
+            let source = std::fs::read_to_string(path).unwrap();
+            let pattern: Pattern = ron::from_str(&source).unwrap();
+
+            assert_eq!(pattern.check(), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn patterns_outlasting_their_period_are_rejected() {
+        let burst = Emitter {
+            volleys: 3,
+            interval: 0.25,
+            ..Default::default()
+        };
+        assert!(pattern(1., burst.clone()).check().is_ok());
+
+        let nested = Emitter {
+            delay: 0.5,
+            emitters: vec![burst],
+            ..Default::default()
+        };
+        assert!(pattern(1., nested).check().is_err());
+        assert!(pattern(0., Emitter::default()).check().is_err());
+    }
+
+    #[test]
+    fn nested_emitters_start_from_their_volley() {
+        let pattern = pattern(
+            10.,
+            Emitter {
+                angle: 90.,
+                volleys: 2,
+                interval: 1.,
+                emitters: vec![Emitter {
+                    delay: 0.25,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        assert!(pattern.shots(0., 0.25, 0., 0.).is_empty());
+        assert_angles(&pattern.shots(0.25, 0.5, 0., 0.), &[FRAC_PI_2]);
+        assert!(pattern.shots(0.5, 1.25, 0., 0.).is_empty());
+        assert_angles(&pattern.shots(1.25, 1.5, 0., 0.), &[FRAC_PI_2]);
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Collider, Health},
+    events::PickupCollected,
+    graphics::{self, TexturesSheets},
+    minimap::MinimapBlip,
+    player::Player,
+    run::Lives,
+    simulation::{SimulationApp, SimulationTime},
+    state::{GameState, LevelScoped},
+};
+
+/// Health given back by a repair kit.
+const REPAIR: f32 = 50.;
+/// Size pickups are drawn and collected at.
+pub const SIZE: Vec2 = Vec2::new(40., 32.);
+/// Turns per second pickups spin at, and how much they grow and shrink.
+const SPIN: f32 = 0.25;
+const PULSE: f32 = 0.1;
+
+const SHIP_ICON: usize = 10;
+const CROSS_ICON: usize = 11;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PickupKind {
+    /// Heals the ship that picks it up.
+    Repair,
+    /// One more life for the whole run.
+    ExtraLife,
+}
+
+impl PickupKind {
+    #[cfg(debug_assertions)]
+    pub const ALL: [Self; 2] = [Self::Repair, Self::ExtraLife];
+
+    /// Sprite of the ui sheet it's drawn with, and its color.
+    pub fn icon(self) -> (usize, &'static str) {
+        match self {
+            Self::Repair => (CROSS_ICON, graphics::PICKUP_GREEN),
+            Self::ExtraLife => (SHIP_ICON, graphics::WHITE),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    /// Seconds since it appeared, for its animation.
+    pub age: f32,
+}
+
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    ts: &TexturesSheets,
+    pickup: Pickup,
+    position: Vec2,
+) -> Entity {
+    let (index, color) = pickup.kind.icon();
+
+    let mut sprite = TextureAtlasSprite::new(index);
+    sprite.color = Color::hex(color).unwrap();
+    sprite.custom_size = Some(SIZE);
+
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas: ts.ui.clone(),
+            transform: Transform::from_translation(position.extend(1.)),
+            ..Default::default()
+        })
+        .insert(pickup)
+        .insert(Collider {
+            width: SIZE.x,
+            height: SIZE.y,
+            ..Default::default()
+        })
+        .insert(MinimapBlip::new(color, 3.))
+        .insert(Name::new("Pickup"))
+        .insert(LevelScoped)
+        .id()
+}
+
+/// Repair kits and extra lives, collected by flying into them.
+pub struct PickupsPlugin;
+
+impl Plugin for PickupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_simulation_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::collect)
+                .with_system(Self::animate),
+        );
+    }
+}
+
+impl PickupsPlugin {
+    /// The first living ship touching a pickup gets it.
+    fn collect(
+        mut commands: Commands,
+        mut lives: ResMut<Lives>,
+        pickup_query: Query<(Entity, &Pickup, &Transform, &Collider)>,
+        mut player_query: Query<
+            (&Player, &Transform, &Collider, &mut Health),
+            Without<Pickup>,
+        >,
+        mut collected_evw: EventWriter<PickupCollected>,
+    ) {
+        for (e, pickup, transform, collider) in pickup_query.iter() {
+            let picker = player_query.iter_mut().find(
+                |(_, player_transform, player_collider, health)| {
+                    health.current > 0.
+                        && collide(
+                            transform.translation,
+                            Vec2::from(*collider),
+                            player_transform.translation,
+                            Vec2::from(**player_collider),
+                        )
+                        .is_some()
+                },
+            );
+
+            let (player, _, _, mut health) = match picker {
+                Some(picker) => picker,
+                None => continue,
+            };
+
+            match pickup.kind {
+                PickupKind::Repair => {
+                    health.current = (health.current + REPAIR).min(health.max);
+                }
+                PickupKind::ExtraLife => lives.0 += 1,
+            }
+
+            commands.entity(e).despawn();
+            collected_evw.send(PickupCollected {
+                player: player.id,
+                kind: pickup.kind,
+                position: transform.translation.truncate(),
+            });
+        }
+    }
+
+    fn animate(
+        time: Res<SimulationTime>,
+        mut pickup_query: Query<(&mut Pickup, &mut Transform)>,
+    ) {
+        for (mut pickup, mut transform) in pickup_query.iter_mut() {
+            pickup.age += time.delta_seconds();
+
+            let turn = pickup.age * SPIN * std::f32::consts::TAU;
+            let scale = 1. + turn.sin() * PULSE;
+
+            transform.rotation = Quat::from_rotation_z(turn.sin() * 0.3);
+            transform.scale = Vec3::new(scale, scale, 1.);
+        }
+    }
+}
@@ -1,24 +1,42 @@
+use std::f32::consts::FRAC_PI_2;
+
 use bevy::prelude::*;
 use bevy::sprite::collide_aabb::collide;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    common::{out_of_bounds_x, out_of_bounds_y, Collider, Velocity},
+    camera::{self, MainCamera},
+    common::{Collider, Health, Playfield, Velocity},
+    events::{PlayerDestroyed, PlayerHit, ShotFired},
     graphics::{self, TexturesSheets},
-    obstacles::Obstacle,
-    projectiles::Bullet,
-    state::GameState,
-    HEIGHT, WIDTH,
+    minimap::MinimapBlip,
+    obstacles::{MovingObstacle, Obstacle, Spawner},
+    patterns::{self, Patterns},
+    pool::{Pooled, Pools},
+    projectiles::ProjectileSystem,
+    run::{GameMode, Lives},
+    settings::Settings,
+    simulation::{GameRng, Interpolated, SimulationApp, SimulationTime},
+    snapshot::{CursorState, TimerState},
+    state::{GameState, LevelScoped},
 };
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct Player {
+    /// Seat of the player, `0` being the one on keyboard and mouse.
+    pub id: usize,
     pub just_moved: bool,
+    /// Points earned by this player's own shots.
+    pub score: u32,
 }
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct Cursor {
+    /// Seat of the player aiming with this cursor.
+    pub player: usize,
     actual_angle: f32,
     pub computed_angle: f32,
     fired: bool,
@@ -26,147 +44,323 @@ pub struct Cursor {
     rate: Timer,
 }
 
+/// Where a player's input comes from.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum Controls {
+    KeyboardMouse,
+    /// One of the gamepads connected, counting from the lowest id.
+    Gamepad(usize),
+    /// Another machine, through the netcode.
+    Remote,
+}
+
+impl Controls {
+    fn for_seat(mode: GameMode, id: usize) -> Self {
+        match mode {
+            GameMode::Online { local } if id == local => {
+                Controls::KeyboardMouse
+            }
+            GameMode::Online { .. } => Controls::Remote,
+            _ if id == 0 => Controls::KeyboardMouse,
+            _ => Controls::Gamepad(id - 1),
+        }
+    }
+}
+
+/// What a player wants their ship to do, sampled every frame and acted upon
+/// by the simulation.
+#[derive(
+    Component, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize,
+)]
+pub struct PlayerInput {
+    /// Each axis in `[-1, 1]`.
+    pub movement: Vec2,
+    /// Angle the ship is aiming at, counterclockwise from the x axis.
+    pub aim: f32,
+    pub fire: bool,
+}
+
+/// Keeps the player from taking damage until the timer runs out.
+#[derive(Component)]
+pub struct Invulnerable {
+    timer: Timer,
+}
+
+impl Invulnerable {
+    fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, false),
+        }
+    }
+}
+
+impl From<&Invulnerable> for TimerState {
+    fn from(invulnerable: &Invulnerable) -> Self {
+        (&invulnerable.timer).into()
+    }
+}
+
+impl From<&TimerState> for Invulnerable {
+    fn from(state: &TimerState) -> Self {
+        Self {
+            timer: state.into(),
+        }
+    }
+}
+
+impl From<&Cursor> for CursorState {
+    fn from(cursor: &Cursor) -> Self {
+        Self {
+            player: cursor.player,
+            actual_angle: cursor.actual_angle,
+            computed_angle: cursor.computed_angle,
+            fired: cursor.fired,
+            last_target_pos: cursor.last_target_pos,
+            rate: (&cursor.rate).into(),
+        }
+    }
+}
+
+impl From<&CursorState> for Cursor {
+    fn from(state: &CursorState) -> Self {
+        Self {
+            player: state.player,
+            actual_angle: state.actual_angle,
+            computed_angle: state.computed_angle,
+            fired: state.fired,
+            last_target_pos: state.last_target_pos,
+            rate: (&state.rate).into(),
+        }
+    }
+}
+
+impl Cursor {
+    /// Rotation of the ship's sprite, a quarter turn behind the aim as the
+    /// sprite points up.
+    #[cfg(debug_assertions)]
+    pub fn actual_angle(&self) -> f32 {
+        self.actual_angle
+    }
+
+    /// How far along the cooldown between two shots is, `1` being ready.
+    pub fn readiness(&self) -> f32 {
+        if self.fired {
+            self.rate.percent()
+        } else {
+            1.
+        }
+    }
+}
+
+/// Tint telling the ships, cursors and HUD of each player apart.
+pub fn color(id: usize) -> &'static str {
+    match id {
+        0 => graphics::WHITE,
+        1 => graphics::PLAYER_TWO_BLUE,
+        2 => graphics::PLAYER_THREE_YELLOW,
+        _ => graphics::PLAYER_FOUR_PINK,
+    }
+}
+
+/// Where player `id` out of `players` starts a run, side by side around
+/// the middle of the world.
+pub fn seat(id: usize, players: usize, playfield: &Playfield) -> Vec2 {
+    let offset = (id as f32 - (players - 1) as f32 / 2.) * SEAT_SPACING;
+    playfield.center() + Vec2::new(offset, 0.)
+}
+
+type RespawnQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static Collider,
+        &'static mut Health,
+    ),
+    With<Player>,
+>;
+
+type PlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static Velocity,
+        &'static Collider,
+        &'static mut Player,
+        &'static PlayerInput,
+    ),
+>;
+
+type ObstacleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static Collider),
+    (Without<Player>, With<Obstacle>, Without<Pooled>),
+>;
+
+type InputQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Player,
+        &'static Controls,
+        &'static Transform,
+        &'static mut PlayerInput,
+    ),
+>;
+
+type CursorTransformQuery<'w, 's> =
+    Query<'w, 's, (&'static mut Transform, &'static Cursor), Without<Player>>;
+
+const DEBRIS_DAMAGE: f32 = 10.;
+/// Pattern fired each time the trigger is pulled.
+const WEAPON: &str = "blaster";
+
+/// Seconds the player can't be damaged for after respawning.
+const INVULNERABILITY: f32 = 3.;
+/// Times per second the player flickers while invulnerable.
+const BLINK_RATE: f32 = 10.;
+/// Extra room kept between a respawned player and the nearest obstacle.
+const RESPAWN_CLEARANCE: f32 = 60.;
+const RESPAWN_ATTEMPTS: usize = 64;
+
+/// Horizontal distance between ships at the start of a run.
+const SEAT_SPACING: f32 = 160.;
+/// How far in front of a gamepad player their cursor is drawn.
+const GAMEPAD_AIM_DISTANCE: f32 = 120.;
+/// Right stick deflection under which the previous aim is kept.
+const AIM_DEADZONE: f32 = 0.3;
+
+/// Lets other systems order themselves after ships have taken their hits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PlayerSystem {
+    Hits,
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_enter(GameState::Level)
-                .with_system(Self::setup_hud)
-                .with_system(Self::spawn_cursor)
-                .with_system(Self::spawn_player),
+                .with_system(Self::spawn_players),
         );
 
         app.add_system_set(
+            SystemSet::on_update(GameState::Level)
+                .with_system(Self::read_keyboard_mouse)
+                .with_system(Self::read_gamepad)
+                .with_system(
+                    Self::aim_ships
+                        .after(Self::read_keyboard_mouse)
+                        .after(Self::read_gamepad),
+                )
+                .with_system(Self::blink),
+        );
+
+        app.add_simulation_system_set(
             SystemSet::on_update(GameState::Level)
                 .with_system(Self::process_input)
-                .with_system(Self::process_mouse_movement)
-                .with_system(Self::process_mouse_input)
                 .with_system(
-                    Self::cursor_fire_cooldown.after(Self::process_mouse_input),
-                ),
+                    Self::process_hits
+                        .label(PlayerSystem::Hits)
+                        .after(Self::process_input)
+                        .after(ProjectileSystem::Collisions),
+                )
+                .with_system(Self::respawn.after(PlayerSystem::Hits))
+                .with_system(Self::tick_invulnerability)
+                .with_system(Self::fire.after(Self::process_input))
+                .with_system(Self::cursor_fire_cooldown.after(Self::fire)),
         );
     }
 }
 
 impl PlayerPlugin {
-    fn setup_hud(mut commands: Commands, ts: Res<TexturesSheets>) {
-        const SPRITE_SIZE_X: f32 = 26. + 12.;
-        const HUD_Y: f32 = HEIGHT - SPRITE_SIZE_X;
-        const SPRITE_SIZE_Y: f32 = 33. + 5.;
-        const HUD_X: f32 = SPRITE_SIZE_Y / 2. + 10.;
-
-        let mut sprite = TextureAtlasSprite::new(10);
-        sprite.custom_size = Some(Vec2::new(SPRITE_SIZE_X, SPRITE_SIZE_Y));
-        commands.spawn_bundle(SpriteSheetBundle {
-            sprite,
-            texture_atlas: ts.ui.clone(),
-            transform: Transform::from_xyz(HUD_X, HUD_Y, 2.),
-            ..Default::default()
-        });
-
-        let mut sprite = TextureAtlasSprite::new(0);
-        sprite.custom_size = Some(Vec2::new(SPRITE_SIZE_X, SPRITE_SIZE_Y));
-        commands.spawn_bundle(SpriteSheetBundle {
-            sprite,
-            texture_atlas: ts.ui.clone(),
-            transform: Transform::from_xyz(
-                HUD_X + SPRITE_SIZE_X + 3.5,
-                HUD_Y,
-                2.,
-            ),
-            ..Default::default()
-        });
-
-        let mut sprite = TextureAtlasSprite::new(1);
-        sprite.custom_size = Some(Vec2::new(SPRITE_SIZE_X, SPRITE_SIZE_Y));
-        commands.spawn_bundle(SpriteSheetBundle {
-            sprite,
-            texture_atlas: ts.ui.clone(),
-            transform: Transform::from_xyz(
-                HUD_X + SPRITE_SIZE_X * 2.,
-                HUD_Y,
-                2.,
-            ),
-            ..Default::default()
-        });
-
-        const RECT_SIZE: f32 = 5.;
-        const HEALTH_BAR_X: f32 = HUD_X + (SPRITE_SIZE_X * 5.);
-        const HEALTH_BAR_Y: f32 = HUD_Y;
-
-        commands.spawn_bundle(SpriteBundle {
-            sprite: Sprite {
-                color: Color::hex(graphics::WHITE).unwrap(),
-                custom_size: Some(Vec2::new(
-                    SPRITE_SIZE_X * RECT_SIZE,
-                    SPRITE_SIZE_Y,
-                )),
-                ..Default::default()
-            },
-            transform: Transform::from_xyz(HEALTH_BAR_X, HEALTH_BAR_Y, 2.),
-            ..Default::default()
-        });
-
-        commands.spawn_bundle(SpriteBundle {
-            sprite: Sprite {
-                color: Color::hex(graphics::SPACESHIP_RED).unwrap(),
-                custom_size: Some(Vec2::new(
-                    SPRITE_SIZE_X * RECT_SIZE * 0.90,
-                    SPRITE_SIZE_Y * 0.65,
-                )),
-                ..Default::default()
-            },
-            transform: Transform::from_xyz(
-                HEALTH_BAR_X + 0.80,
-                HEALTH_BAR_Y,
-                3.,
-            ),
-            ..Default::default()
-        });
-    }
-
-    fn spawn_cursor(
+    fn spawn_players(
         mut commands: Commands,
         mut windows: ResMut<Windows>,
+        mode: Res<GameMode>,
+        playfield: Res<Playfield>,
         ts: Res<TexturesSheets>,
     ) {
         let window = windows.get_primary_mut().unwrap();
 
         window.set_cursor_visibility(false);
 
-        let cursor_pos = Vec2::new(WIDTH / 2., HEIGHT / 2.);
-
-        window.set_cursor_position(cursor_pos);
+        window.set_cursor_position(Vec2::new(
+            window.width() / 2.,
+            window.height() / 2.,
+        ));
+
+        let players = mode.players();
+
+        for id in 0..players {
+            let position = seat(id, players, &playfield);
+            let color = Color::hex(color(id)).unwrap();
+            let controls = Controls::for_seat(*mode, id);
+
+            Self::spawn_cursor(&mut commands, &ts, id, position, color);
+            Self::spawn_player(
+                &mut commands,
+                &ts,
+                id,
+                position,
+                color,
+                controls,
+            );
+        }
+    }
 
-        let sprite = TextureAtlasSprite::new(11);
+    fn spawn_cursor(
+        commands: &mut Commands,
+        ts: &TexturesSheets,
+        id: usize,
+        position: Vec2,
+        color: Color,
+    ) {
+        let mut sprite = TextureAtlasSprite::new(11);
+        sprite.color = color;
 
         commands
             .spawn_bundle(SpriteSheetBundle {
                 sprite,
                 texture_atlas: ts.ui.clone(),
                 transform: Transform::from_xyz(
-                    cursor_pos.x,
-                    cursor_pos.y + 95.,
+                    position.x,
+                    position.y + 95.,
                     2.,
                 ),
                 ..Default::default()
             })
             .insert(Name::new("Cursor"))
+            .insert(LevelScoped)
             .insert(Cursor {
-                last_target_pos: cursor_pos,
+                player: id,
+                computed_angle: FRAC_PI_2,
+                last_target_pos: position,
                 rate: Timer::from_seconds(0.48, true),
                 ..Default::default()
             });
     }
 
-    fn spawn_player(mut commands: Commands, ts: Res<TexturesSheets>) {
-        let sprite = TextureAtlasSprite::new(8);
+    fn spawn_player(
+        commands: &mut Commands,
+        ts: &TexturesSheets,
+        id: usize,
+        position: Vec2,
+        color: Color,
+        controls: Controls,
+    ) {
+        let mut sprite = TextureAtlasSprite::new(8);
+        sprite.color = color;
+
         commands
             .spawn_bundle(SpriteSheetBundle {
                 sprite,
                 texture_atlas: ts.entities.clone(),
-                transform: Transform::from_xyz(WIDTH / 2., HEIGHT / 2., 1.),
+                transform: Transform::from_translation(position.extend(1.)),
                 ..Default::default()
             })
             .insert(Velocity { vx: 325., vy: 325. })
@@ -175,95 +369,220 @@ impl PlayerPlugin {
                 height: 75.,
                 ..Default::default()
             })
-            .insert(Player { just_moved: true })
-            .insert(Name::new("Player"));
+            .insert(Player {
+                id,
+                just_moved: true,
+                score: 0,
+            })
+            .insert(controls)
+            .insert(PlayerInput {
+                aim: FRAC_PI_2,
+                ..Default::default()
+            })
+            .insert(Health::new(100.))
+            .insert(MinimapBlip { color, size: 4. })
+            .insert(Interpolated::default())
+            .insert(Name::new("Player"))
+            .insert(LevelScoped);
     }
 
-    fn cursor_fire_cooldown(
-        mut cursor_query: Query<&mut Cursor>,
-        time: Res<Time>,
+    fn read_keyboard_mouse(
+        keyboard: Res<Input<KeyCode>>,
+        buttons: Res<Input<MouseButton>>,
+        settings: Res<Settings>,
+        windows: Res<Windows>,
+        camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+        mut player_query: InputQuery,
+        mut cursor_query: CursorTransformQuery,
     ) {
-        let mut cursor = cursor_query.single_mut();
-        if cursor.fired {
-            cursor.rate.tick(time.delta());
-            if cursor.rate.finished() {
-                cursor.fired = false;
+        let (camera, camera_transform) = camera_query.single();
+        let window = windows.get_primary().unwrap();
+        let bindings = &settings.bindings;
+
+        // Polled rather than driven by `CursorMoved`, as the camera can move
+        // under a mouse that stays still.
+        let mouse_pos = window.cursor_position().and_then(|position| {
+            camera::window_to_world(camera, camera_transform, window, position)
+        });
+
+        for (player, controls, transform, mut input) in player_query.iter_mut()
+        {
+            if *controls != Controls::KeyboardMouse {
+                continue;
             }
-        }
-    }
 
-    fn process_input(
-        keyboard: Res<Input<KeyCode>>,
-        time: Res<Time>,
-        mut cursor_query: Query<&mut Cursor>,
-        mut player_query: Query<(
-            &mut Transform,
-            &Velocity,
-            &Collider,
-            &mut Player,
-        )>,
-        obstacles_query: Query<
-            (&Transform, &Collider),
-            (Without<Player>, With<Obstacle>),
-        >,
-    ) {
-        Self::handle_input(
-            &keyboard,
-            time.delta_seconds(),
-            &mut player_query,
-            &obstacles_query,
-        );
+            let axis = |negative: bool, positive: bool| {
+                positive as i32 as f32 - negative as i32 as f32
+            };
 
-        let (transform, ..) = player_query.single();
+            input.movement = Vec2::new(
+                axis(
+                    bindings.left.pressed(&keyboard),
+                    bindings.right.pressed(&keyboard),
+                ),
+                axis(
+                    bindings.down.pressed(&keyboard),
+                    bindings.up.pressed(&keyboard),
+                ),
+            );
+            input.fire = buttons.pressed(MouseButton::Left);
 
-        cursor_query.single_mut().last_target_pos =
-            transform.translation.truncate();
+            let mouse_pos = match mouse_pos {
+                Some(mouse_pos) => mouse_pos,
+                None => continue,
+            };
+
+            let diff = mouse_pos - transform.translation.truncate();
+            input.aim = diff.y.atan2(diff.x);
+
+            for (mut cursor_transform, cursor) in cursor_query.iter_mut() {
+                if cursor.player == player.id {
+                    cursor_transform.translation = mouse_pos.extend(2.);
+                }
+            }
+        }
     }
 
-    fn handle_input(
-        keyboard: &Input<KeyCode>,
-        dt: f32,
-        player_query: &mut Query<(
-            &mut Transform,
-            &Velocity,
-            &Collider,
-            &mut Player,
-        )>,
-        obstacles_query: &Query<
-            (&Transform, &Collider),
-            (Without<Player>, With<Obstacle>),
-        >,
+    fn read_gamepad(
+        gamepads: Res<Gamepads>,
+        axes: Res<Axis<GamepadAxis>>,
+        buttons: Res<Input<GamepadButton>>,
+        mut player_query: InputQuery,
+        mut cursor_query: CursorTransformQuery,
     ) {
-        let (mut transform, velocity, collider, mut player) =
-            player_query.single_mut();
+        // Iterated in no particular order, so sort them consistently.
+        let mut gamepads: Vec<Gamepad> = gamepads.iter().copied().collect();
+        gamepads.sort_by_key(|gamepad| gamepad.id);
 
-        player.just_moved = false;
+        for (player, controls, transform, mut input) in player_query.iter_mut()
+        {
+            let index = match *controls {
+                Controls::Gamepad(index) => index,
+                _ => continue,
+            };
+
+            let gamepad = match gamepads.get(index) {
+                Some(&gamepad) => gamepad,
+                None => {
+                    *input = PlayerInput {
+                        aim: input.aim,
+                        ..Default::default()
+                    };
+                    continue;
+                }
+            };
+
+            let axis = |axis_type| {
+                axes.get(GamepadAxis::new(gamepad, axis_type))
+                    .unwrap_or_default()
+            };
+            let pressed = |button_type| {
+                buttons.pressed(GamepadButton::new(gamepad, button_type))
+            };
+
+            input.movement = Vec2::new(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            )
+            .clamp(Vec2::NEG_ONE, Vec2::ONE);
 
-        let mut offset_y = 0.;
-        let mut offset_x = 0.;
+            let aim = Vec2::new(
+                axis(GamepadAxisType::RightStickX),
+                axis(GamepadAxisType::RightStickY),
+            );
+            if aim.length() > AIM_DEADZONE {
+                input.aim = aim.y.atan2(aim.x);
+            }
+
+            input.fire = pressed(GamepadButtonType::RightTrigger2)
+                || pressed(GamepadButtonType::RightTrigger)
+                || pressed(GamepadButtonType::South);
 
-        let distance_y = velocity.vy * dt;
-        let distance_x = velocity.vx * dt;
+            let direction = Vec2::new(input.aim.cos(), input.aim.sin());
+            let position = transform.translation.truncate()
+                + direction * GAMEPAD_AIM_DISTANCE;
 
-        if keyboard.pressed(KeyCode::W) || keyboard.pressed(KeyCode::Up) {
-            offset_y += distance_y
+            for (mut cursor_transform, cursor) in cursor_query.iter_mut() {
+                if cursor.player == player.id {
+                    cursor_transform.translation = position.extend(2.);
+                }
+            }
         }
+    }
 
-        if keyboard.pressed(KeyCode::S) || keyboard.pressed(KeyCode::Down) {
-            offset_y -= distance_y
+    /// Turns every ship towards where its player is aiming.
+    fn aim_ships(mut player_query: Query<(&mut Transform, &PlayerInput)>) {
+        for (mut transform, input) in player_query.iter_mut() {
+            transform.rotation = Quat::from_rotation_z(input.aim - FRAC_PI_2);
         }
+    }
 
-        if keyboard.pressed(KeyCode::A) || keyboard.pressed(KeyCode::Left) {
-            offset_x -= distance_x;
+    fn cursor_fire_cooldown(
+        mut cursor_query: Query<&mut Cursor>,
+        time: Res<SimulationTime>,
+    ) {
+        for mut cursor in cursor_query.iter_mut() {
+            if cursor.fired {
+                cursor.rate.tick(time.delta());
+                if cursor.rate.finished() {
+                    cursor.fired = false;
+                }
+            }
         }
+    }
 
-        if keyboard.pressed(KeyCode::D) || keyboard.pressed(KeyCode::Right) {
-            offset_x += distance_x;
+    fn process_input(
+        time: Res<SimulationTime>,
+        playfield: Res<Playfield>,
+        mut cursor_query: Query<&mut Cursor>,
+        mut player_query: PlayerQuery,
+        obstacles_query: ObstacleQuery,
+    ) {
+        let dt = time.delta_seconds();
+
+        for (mut transform, velocity, collider, mut player, input) in
+            player_query.iter_mut()
+        {
+            let movement = input.movement.clamp(Vec2::NEG_ONE, Vec2::ONE);
+            let offset = movement * Vec2::new(velocity.vx, velocity.vy) * dt;
+
+            Self::move_player(
+                &mut transform,
+                collider,
+                &mut player,
+                offset,
+                &playfield,
+                &obstacles_query,
+            );
+
+            for mut cursor in cursor_query.iter_mut() {
+                if cursor.player == player.id {
+                    cursor.last_target_pos = transform.translation.truncate();
+                    cursor.computed_angle = input.aim;
+                    cursor.actual_angle = input.aim - FRAC_PI_2;
+                }
+            }
         }
+    }
+
+    fn move_player(
+        transform: &mut Transform,
+        collider: &Collider,
+        player: &mut Player,
+        offset: Vec2,
+        playfield: &Playfield,
+        obstacles_query: &ObstacleQuery,
+    ) {
+        player.just_moved = false;
+
+        let Vec2 {
+            x: offset_x,
+            y: offset_y,
+        } = offset;
 
         let target = transform.translation + Vec3::new(offset_x, 0., 0.);
-        if !(Self::check_collisions(target, *collider, &obstacles_query)
-            || out_of_bounds_x(target.x, collider.width / 2.))
+        if !(Self::check_collisions(target, *collider, obstacles_query)
+            || playfield.out_of_bounds_x(target.x, collider.width / 2.))
         {
             transform.translation = target;
             if offset_x != 0. {
@@ -272,8 +591,8 @@ impl PlayerPlugin {
         }
 
         let target = transform.translation + Vec3::new(0., offset_y, 0.);
-        if !(Self::check_collisions(target, *collider, &obstacles_query)
-            || out_of_bounds_y(target.y, collider.height / 2.))
+        if !(Self::check_collisions(target, *collider, obstacles_query)
+            || playfield.out_of_bounds_y(target.y, collider.height / 2.))
         {
             transform.translation = target;
             if offset_y != 0. {
@@ -285,17 +604,14 @@ impl PlayerPlugin {
     fn check_collisions(
         target_pos: Vec3,
         target_collider: Collider,
-        obstacles_query: &Query<
-            (&Transform, &Collider),
-            (Without<Player>, With<Obstacle>),
-        >,
+        obstacles_query: &ObstacleQuery,
     ) -> bool {
         for (transform, collider) in obstacles_query.iter() {
             let collision = collide(
                 target_pos,
                 target_collider.into(),
                 transform.translation,
-                collider.clone().into(),
+                (*collider).into(),
             )
             .is_some();
 
@@ -306,105 +622,207 @@ impl PlayerPlugin {
         false
     }
 
-    fn process_mouse_input(
-        buttons: Res<Input<MouseButton>>,
-        ts: Res<TexturesSheets>,
+    fn fire(
+        mut spawner: Spawner,
+        patterns: Res<Patterns>,
         mut cursor_query: Query<&mut Cursor>,
-        input_target_query: Query<&Collider, With<Player>>,
-        mut commands: Commands,
+        player_query: Query<(&Player, &Collider, &PlayerInput)>,
+        mut shot_evw: EventWriter<ShotFired>,
     ) {
-        let mut cursor = cursor_query.single_mut();
+        let pattern = match patterns.get(WEAPON) {
+            Some(pattern) => pattern,
+            None => return,
+        };
+
+        for mut cursor in cursor_query.iter_mut() {
+            let shooter = player_query
+                .iter()
+                .find(|(player, ..)| player.id == cursor.player);
+
+            let (target_collider, input) = match shooter {
+                Some((_, collider, input)) => (collider, input),
+                None => continue,
+            };
+
+            if cursor.fired || !input.fire {
+                continue;
+            }
 
-        if !cursor.fired && buttons.pressed(MouseButton::Left) {
             cursor.fired = true;
 
-            let damage = 10.;
+            let angle = cursor.computed_angle;
+            patterns::fire(
+                &mut spawner,
+                pattern,
+                cursor.player,
+                cursor.last_target_pos,
+                Vec2::from(*target_collider) / 2.,
+                &pattern.trigger(angle, angle),
+            );
 
-            let sprite = TextureAtlasSprite::new(0);
+            shot_evw.send(ShotFired);
+        }
+    }
 
-            let target_collider = input_target_query.single();
+    fn process_hits(
+        mut commands: Commands,
+        mut pools: ResMut<Pools>,
+        mut player_query: Query<(
+            &Player,
+            &Transform,
+            &Collider,
+            &mut Health,
+            Option<&Invulnerable>,
+        )>,
+        debris_query: Query<
+            (Entity, &Transform, &Collider, &MovingObstacle),
+            Without<Pooled>,
+        >,
+        mut hit_evw: EventWriter<PlayerHit>,
+        mut destroyed_evw: EventWriter<PlayerDestroyed>,
+    ) {
+        for (
+            player,
+            player_transform,
+            player_collider,
+            mut health,
+            invulnerable,
+        ) in player_query.iter_mut()
+        {
+            if invulnerable.is_some() || health.current <= 0. {
+                continue;
+            }
 
-            let direction = Vec2::new(
-                cursor.computed_angle.cos(),
-                cursor.computed_angle.sin(),
-            );
+            for (e, transform, collider, debris) in debris_query.iter() {
+                // Debris is gone once it hits someone or is shot this tick,
+                // even if it overlaps both ships.
+                if !debris.is_moving() || pools.debris.is_parked(e) {
+                    continue;
+                }
+
+                let collision = collide(
+                    player_transform.translation,
+                    (*player_collider).into(),
+                    transform.translation,
+                    (*collider).into(),
+                )
+                .is_some();
+
+                if collision {
+                    health.damage(DEBRIS_DAMAGE);
+                    pools.debris.release(&mut commands, e);
+
+                    hit_evw.send(PlayerHit {
+                        position: player_transform.translation.truncate(),
+                        damage: DEBRIS_DAMAGE,
+                    });
+
+                    if health.current <= 0. {
+                        destroyed_evw.send(PlayerDestroyed {
+                            player: player.id,
+                            by: None,
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+    }
 
-            let mut transform: Transform = Transform::from_xyz(
-                cursor.last_target_pos.x
-                    + direction.x * (target_collider.width / 2.),
-                cursor.last_target_pos.y
-                    + direction.y * (target_collider.height / 2.),
-                1.,
+    /// Spends a life from the shared pool to bring a destroyed player back
+    /// somewhere safe. With no lives left, the player stays down and the run
+    /// is over. Versus ships come back for free.
+    fn respawn(
+        mut commands: Commands,
+        mut rng: ResMut<GameRng>,
+        mut lives: ResMut<Lives>,
+        mode: Res<GameMode>,
+        playfield: Res<Playfield>,
+        mut player_query: RespawnQuery,
+        obstacles_query: ObstacleQuery,
+    ) {
+        for (e, mut transform, collider, mut health) in player_query.iter_mut()
+        {
+            if health.current > 0. || lives.0 == 0 {
+                continue;
+            }
+
+            if !mode.is_versus() {
+                lives.0 -= 1;
+                if lives.0 == 0 {
+                    return;
+                }
+            }
+
+            let clearance = Collider {
+                width: collider.width + RESPAWN_CLEARANCE * 2.,
+                height: collider.height + RESPAWN_CLEARANCE * 2.,
+                ..*collider
+            };
+            let margin = Vec2::new(collider.width, collider.height);
+
+            let candidates = std::iter::once(playfield.center()).chain(
+                (0..RESPAWN_ATTEMPTS).map(|_| {
+                    Vec2::new(
+                        rng.gen_range(margin.x..playfield.width - margin.x),
+                        rng.gen_range(margin.y..playfield.height - margin.y),
+                    )
+                }),
             );
 
-            transform.rotation = Quat::from_rotation_z(cursor.actual_angle);
+            let mut position = transform.translation;
+            for candidate in candidates {
+                let candidate = candidate.extend(transform.translation.z);
+
+                if !Self::check_collisions(
+                    candidate,
+                    clearance,
+                    &obstacles_query,
+                ) {
+                    position = candidate;
+                    break;
+                }
+            }
 
-            let width = 9.;
-            let height = 37.;
+            transform.translation = position;
+            *health = Health::new(health.max);
 
             commands
-                .spawn_bundle(SpriteSheetBundle {
-                    sprite,
-                    texture_atlas: ts.projectiles.clone(),
-                    transform,
-                    ..Default::default()
-                })
-                .insert(Bullet {
-                    damage,
-                    direction,
-                    duration: Timer::from_seconds(1.7, false),
-                })
-                .insert(Collider {
-                    height,
-                    width,
-                    offset: Some(Vec2::new(width / 2., height / 2.)),
-                })
-                .insert(Velocity { vx: 200., vy: 200. })
-                .insert(Name::new("Bullet"));
+                .entity(e)
+                .insert(Invulnerable::new(INVULNERABILITY))
+                // Don't smooth the jump to the new position.
+                .insert(Interpolated::default());
         }
     }
 
-    fn process_mouse_movement(
-        mut cursor_query: Query<(&mut Transform, &mut Cursor)>,
-        mut input_target_query: Query<
-            &mut Transform,
-            (Without<Cursor>, With<Player>),
-        >,
-        mut cursor_evr: EventReader<CursorMoved>,
+    fn tick_invulnerability(
+        mut commands: Commands,
+        time: Res<SimulationTime>,
+        mut player_query: Query<(Entity, &mut Invulnerable)>,
     ) {
-        for ev in cursor_evr.iter() {
-            Self::handle_mouse_movement(
-                &mut cursor_query,
-                &mut input_target_query,
-                ev.position,
-            );
+        for (e, mut invulnerable) in player_query.iter_mut() {
+            invulnerable.timer.tick(time.delta());
+
+            if invulnerable.timer.finished() {
+                commands.entity(e).remove::<Invulnerable>();
+            }
         }
     }
 
-    fn handle_mouse_movement(
-        cursor_query: &mut Query<(&mut Transform, &mut Cursor)>,
-        input_target_query: &mut Query<
-            &mut Transform,
-            (Without<Cursor>, With<Player>),
+    fn blink(
+        mut player_query: Query<
+            (&mut Visibility, Option<&Invulnerable>),
+            With<Player>,
         >,
-        mouse_pos: Vec2,
     ) {
-        let (mut pos, mut cursor) = cursor_query.single_mut();
-        pos.translation = mouse_pos.extend(2.);
-
-        let mut target = input_target_query.single_mut();
-
-        let target_pos = target.translation.truncate();
-        cursor.last_target_pos = target_pos;
-
-        cursor.computed_angle =
-            f32::atan2(mouse_pos.y - target_pos.y, mouse_pos.x - target_pos.x);
-
-        let diff = mouse_pos - target_pos;
-        let y_axis = Vec2::new(0.0, 1.0);
-        let angle = y_axis.angle_between(diff);
-        cursor.actual_angle = angle;
-
-        target.rotation = Quat::from_rotation_z(angle);
+        for (mut visibility, invulnerable) in player_query.iter_mut() {
+            visibility.is_visible = match invulnerable {
+                Some(invulnerable) => {
+                    let elapsed = invulnerable.timer.elapsed_secs();
+                    (elapsed * BLINK_RATE).fract() < 0.5
+                }
+                None => true,
+            };
+        }
     }
 }
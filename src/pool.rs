@@ -0,0 +1,199 @@
+use bevy::{prelude::*, utils::HashSet};
+
+/// Marks an entity that is parked in a pool. Gameplay queries over pooled
+/// archetypes filter it out with `Without<Pooled>`.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Pooled;
+
+#[derive(Clone, Copy, Default)]
+pub struct PoolMetrics {
+    pub size: usize,
+    pub free: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl PoolMetrics {
+    #[cfg(debug_assertions)]
+    pub fn hit_rate(&self) -> f32 {
+        let requests = self.hits + self.misses;
+        if requests == 0 {
+            return 0.;
+        }
+        self.hits as f32 / requests as f32
+    }
+}
+
+#[derive(Default)]
+pub struct EntityPool {
+    free: Vec<Entity>,
+    parked: HashSet<Entity>,
+    metrics: PoolMetrics,
+}
+
+impl EntityPool {
+    #[cfg(debug_assertions)]
+    pub fn metrics(&self) -> PoolMetrics {
+        self.metrics
+    }
+
+    /// Reuses a parked entity when one is available, otherwise spawns a new
+    /// one. Either way the entity ends up with exactly `bundle`'s values.
+    pub fn spawn<B: Bundle>(
+        &mut self,
+        commands: &mut Commands,
+        bundle: B,
+    ) -> Entity {
+        match self.free.pop() {
+            Some(e) => {
+                self.parked.remove(&e);
+                self.metrics.hits += 1;
+                self.metrics.free = self.free.len();
+                commands.entity(e).remove::<Pooled>().insert_bundle(bundle);
+                e
+            }
+
+            None => {
+                self.metrics.misses += 1;
+                self.metrics.size += 1;
+                commands.spawn_bundle(bundle).id()
+            }
+        }
+    }
+
+    /// Hides `entity` and hands it back to the pool. Releasing an entity
+    /// that is already parked does nothing, so systems racing to remove the
+    /// same entity within a tick don't hand it out twice.
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        if !self.parked.insert(entity) {
+            return;
+        }
+
+        commands
+            .entity(entity)
+            .insert(Pooled)
+            .insert(Visibility { is_visible: false });
+
+        self.free.push(entity);
+        self.metrics.free = self.free.len();
+    }
+
+    /// Whether `entity` was released, even if its `Pooled` marker is still
+    /// waiting in a command queue.
+    pub fn is_parked(&self, entity: Entity) -> bool {
+        self.parked.contains(&entity)
+    }
+}
+
+#[derive(Default)]
+pub struct Pools {
+    pub bullets: EntityPool,
+    pub debris: EntityPool,
+    pub particles: EntityPool,
+}
+
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pools>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    #[derive(Component, PartialEq, Debug)]
+    struct Marker(u32);
+
+    /// Runs `f` with commands on `world`, applying them afterwards.
+    fn with_commands(world: &mut World, f: impl FnOnce(&mut Commands)) {
+        let mut queue = CommandQueue::default();
+        f(&mut Commands::new(&mut queue, world));
+        queue.apply(world);
+    }
+
+    #[test]
+    fn spawns_new_entities_while_empty() {
+        let mut world = World::new();
+        let mut pool = EntityPool::default();
+
+        let mut spawned = Vec::new();
+        with_commands(&mut world, |commands| {
+            spawned.push(pool.spawn(commands, (Marker(1),)));
+            spawned.push(pool.spawn(commands, (Marker(2),)));
+        });
+
+        assert_ne!(spawned[0], spawned[1]);
+        assert_eq!(pool.metrics.misses, 2);
+        assert_eq!(pool.metrics.size, 2);
+        assert_eq!(world.get::<Marker>(spawned[1]), Some(&Marker(2)));
+    }
+
+    #[test]
+    fn release_parks_and_hides() {
+        let mut world = World::new();
+        let mut pool = EntityPool::default();
+
+        let mut e = None;
+        with_commands(&mut world, |commands| {
+            e = Some(pool.spawn(commands, (Marker(1),)));
+        });
+        let e = e.unwrap();
+        with_commands(&mut world, |commands| pool.release(commands, e));
+
+        assert!(pool.is_parked(e));
+        assert!(world.get::<Pooled>(e).is_some());
+        assert_eq!(
+            world.get::<Visibility>(e).map(|v| v.is_visible),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn release_is_idempotent() {
+        let mut world = World::new();
+        let mut pool = EntityPool::default();
+
+        let mut e = None;
+        with_commands(&mut world, |commands| {
+            let spawned = pool.spawn(commands, (Marker(1),));
+            pool.release(commands, spawned);
+            pool.release(commands, spawned);
+            e = Some(spawned);
+        });
+
+        assert_eq!(pool.free, [e.unwrap()]);
+        assert_eq!(pool.metrics.free, 1);
+    }
+
+    #[test]
+    fn spawn_reuses_released_entities() {
+        let mut world = World::new();
+        let mut pool = EntityPool::default();
+
+        let mut first = None;
+        with_commands(&mut world, |commands| {
+            let e = pool.spawn(commands, (Marker(1),));
+            pool.release(commands, e);
+            first = Some(e);
+        });
+        let first = first.unwrap();
+
+        let mut second = None;
+        with_commands(&mut world, |commands| {
+            second = Some(pool.spawn(commands, (Marker(2),)));
+        });
+
+        assert_eq!(second, Some(first));
+        assert!(!pool.is_parked(first));
+        assert!(world.get::<Pooled>(first).is_none());
+        assert_eq!(world.get::<Marker>(first), Some(&Marker(2)));
+        assert_eq!(pool.metrics.hits, 1);
+        assert_eq!(pool.metrics.size, 1);
+    }
+}
@@ -1,47 +1,158 @@
-use bevy::{prelude::*, sprite::collide_aabb::collide};
+use std::cmp::Ordering;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashSet};
 
 use crate::{
-    common::{self, Collider},
-    graphics::TexturesSheets,
-    obstacles::{spawn_small_obstacles, Obstacle},
-    player::Cursor,
+    boss::BossPart,
+    common::{self, Collider, Health, Velocity},
+    enemies::Enemy,
+    events::{
+        BossDefeated, EnemyDestroyed, ObstacleDestroyed, PlayerDestroyed,
+        PlayerHit,
+    },
+    graphics::{self, TexturesSheets},
+    obstacles::{spawn_small_obstacles, Obstacle, Spawner},
+    player::{Invulnerable, Player},
+    pool::{Pooled, Pools},
+    run::GameMode,
+    settings::Settings,
+    simulation::{Interpolated, SimulationApp, SimulationTime},
     state::GameState,
 };
 
+/// Stands in for a seat in the bullets fired by enemies.
+pub const ENEMY: usize = usize::MAX;
+
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct Bullet {
+    /// Seat of the player who fired it, credited with what it destroys, or
+    /// `ENEMY`.
+    pub player: usize,
     pub damage: f32,
     pub direction: Vec2,
     pub duration: Timer,
+    /// Where the bullet was at the start of the current tick; collisions are
+    /// swept from here to its current position.
+    pub previous_position: Vec2,
+}
+
+impl Bullet {
+    /// Orders bullets by their state alone. Bullets it can't tell apart
+    /// behave the same, so which of them goes first doesn't matter.
+    fn stable_cmp(
+        (a, a_transform): (&Self, &Transform),
+        (b, b_transform): (&Self, &Transform),
+    ) -> Ordering {
+        let (a_position, b_position) =
+            (a_transform.translation, b_transform.translation);
+
+        a.player
+            .cmp(&b.player)
+            .then(a_position.x.total_cmp(&b_position.x))
+            .then(a_position.y.total_cmp(&b_position.y))
+            .then(a.direction.x.total_cmp(&b.direction.x))
+            .then(a.direction.y.total_cmp(&b.direction.y))
+    }
+}
+
+#[derive(Bundle)]
+struct BulletBundle {
+    #[bundle]
+    sprite: SpriteSheetBundle,
+    bullet: Bullet,
+    collider: Collider,
+    velocity: Velocity,
+    interpolated: Interpolated,
+    name: Name,
+}
+
+pub const BULLET_SPEED: f32 = 200.;
+
+type BulletQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Bullet,
+        &'static Collider,
+        &'static Transform,
+    ),
+    Without<Pooled>,
+>;
+
+type TargetQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Option<&'static Player>,
+        Option<&'static Invulnerable>,
+        &'static Collider,
+        &'static Transform,
+        &'static mut Health,
+        Option<&'static BossPart>,
+    ),
+    Or<(With<Player>, With<Enemy>, With<BossPart>)>,
+>;
+
+type ObstacleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Collider,
+        &'static Transform,
+        &'static Obstacle,
+    ),
+    Without<Pooled>,
+>;
+
+/// Lets other systems order themselves after bullets have hit this tick.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum ProjectileSystem {
+    Collisions,
+}
+
+#[derive(SystemParam)]
+struct CollisionEvents<'w, 's> {
+    destroyed: EventWriter<'w, 's, ObstacleDestroyed>,
+    hit: EventWriter<'w, 's, PlayerHit>,
+    player_destroyed: EventWriter<'w, 's, PlayerDestroyed>,
+    enemy_destroyed: EventWriter<'w, 's, EnemyDestroyed>,
+    boss_defeated: EventWriter<'w, 's, BossDefeated>,
 }
 
 pub struct ProjectilesPlugin;
 
 impl Plugin for ProjectilesPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
+        app.add_simulation_system_set(
             SystemSet::on_update(GameState::Level)
                 .with_system(Self::move_bullets)
-                .with_system(Self::process_collisions.after(Self::move_bullets))
-                .with_system(Self::remove_bullets.after(Self::move_bullets)),
+                .with_system(
+                    Self::process_collisions
+                        .label(ProjectileSystem::Collisions)
+                        .after(Self::move_bullets),
+                )
+                .with_system(
+                    Self::remove_bullets.after(ProjectileSystem::Collisions),
+                ),
         )
         .add_system_set(
             SystemSet::on_exit(GameState::Level)
-                .with_system(Self::remove_bullets),
+                .with_system(Self::release_bullets),
         );
     }
 }
 
 impl ProjectilesPlugin {
     fn move_bullets(
-        mut bullet_query: Query<(
-            &mut Transform,
-            &mut Bullet,
-            &mut Collider,
-            &common::Velocity,
-        )>,
-        time: Res<Time>,
+        mut bullet_query: Query<
+            (&mut Transform, &mut Bullet, &mut Collider, &Velocity),
+            Without<Pooled>,
+        >,
+        time: Res<SimulationTime>,
     ) {
         for (mut transform, mut bullet, mut collider, velocity) in
             bullet_query.iter_mut()
@@ -49,6 +160,7 @@ impl ProjectilesPlugin {
             let dt = time.delta_seconds();
 
             bullet.duration.tick(time.delta());
+            bullet.previous_position = transform.translation.truncate();
 
             transform.translation.x += bullet.direction.x * velocity.vx * dt;
             transform.translation.y += bullet.direction.y * velocity.vy * dt;
@@ -66,73 +178,286 @@ impl ProjectilesPlugin {
     }
 
     fn process_collisions(
-        bullet_query: Query<(Entity, &Collider, &Transform), With<Bullet>>,
-        obstacle_query: Query<(Entity, &Collider, &Transform, &Obstacle)>,
-        cursor_query: Query<&Cursor>,
-        mut commands: Commands,
-        ts: Res<TexturesSheets>,
+        bullet_query: BulletQuery,
+        obstacle_query: ObstacleQuery,
+        mut target_query: TargetQuery,
+        mut spawner: Spawner,
+        mut events: CollisionEvents,
+        mode: Res<GameMode>,
+        settings: Res<Settings>,
     ) {
-        let cursor = cursor_query.single();
-        for (e, collider, transform) in bullet_query
-            .iter()
-            .filter(|(_, _, transform)| transform.scale.x >= 0.15)
-        {
-            Self::handle_collisions(
-                e,
-                transform,
-                collider,
-                &obstacle_query,
-                &mut commands,
-                &ts,
-                cursor.computed_angle,
-            );
+        let friendly_fire = mode.friendly_fire(&settings.rules);
+
+        // Query order follows where entities are stored, which differs
+        // between peers and after a restore. Splitting obstacles draws from
+        // the shared rng, so bullets are handled in an order both agree on.
+        let mut bullets: Vec<_> = bullet_query.iter().collect();
+        bullets.sort_by(|(_, a, _, at), (_, b, _, bt)| {
+            Bullet::stable_cmp((a, at), (b, bt))
+        });
+
+        let mut destroyed = HashSet::new();
+        for (e, bullet, collider, bullet_transform) in bullets {
+            let sweep = Sweep::new(bullet, bullet_transform, collider);
+
+            let obstacle_hit =
+                sweep.first_obstacle(&obstacle_query, &destroyed);
+            let ship_hit =
+                sweep.first_target(bullet, &target_query, friendly_fire);
+
+            // Whichever the bullet reached first takes the hit.
+            match (obstacle_hit, ship_hit) {
+                (_, Some((toi, target)))
+                    if obstacle_hit.is_none_or(|(t, ..)| toi < t) =>
+                {
+                    spawner.pools.bullets.release(&mut spawner.commands, e);
+                    Self::hit_target(
+                        bullet,
+                        target,
+                        &mut target_query,
+                        &mut spawner.commands,
+                        &mut events,
+                    );
+                }
+
+                // Enemy fire is stopped by obstacles, without harming them.
+                (Some(_), _) if bullet.player == ENEMY => {
+                    spawner.pools.bullets.release(&mut spawner.commands, e);
+                }
+
+                (Some((_, obstacle_entity, transform, obstacle)), _) => {
+                    destroyed.insert(obstacle_entity);
+                    let Spawner {
+                        commands,
+                        pools,
+                        rng,
+                        ts,
+                    } = &mut spawner;
+                    pools.bullets.release(commands, e);
+
+                    events.destroyed.send(ObstacleDestroyed {
+                        player: bullet.player,
+                        position: transform.translation.truncate(),
+                        impact: bullet_transform.translation.truncate(),
+                        direction: bullet.direction.y.atan2(bullet.direction.x),
+                        split: obstacle.can_split,
+                    });
+
+                    if obstacle.can_split {
+                        commands.entity(obstacle_entity).despawn_recursive();
+                        spawn_small_obstacles(
+                            commands,
+                            pools,
+                            rng,
+                            ts,
+                            transform.translation.truncate(),
+                            bullet.direction.y.atan2(bullet.direction.x),
+                        );
+                    } else {
+                        pools.debris.release(commands, obstacle_entity);
+                    }
+                }
+
+                _ => {}
+            }
         }
     }
 
-    fn handle_collisions(
-        bullet: Entity,
-        bullet_transform: &Transform,
-        bullet_collider: &Collider,
-        obstacle_query: &Query<(Entity, &Collider, &Transform, &Obstacle)>,
+    fn hit_target(
+        bullet: &Bullet,
+        target: Entity,
+        target_query: &mut TargetQuery,
         commands: &mut Commands,
-        ts: &TexturesSheets,
-        angle: f32,
+        events: &mut CollisionEvents,
     ) {
-        for (e, collider, transform, obstacle) in obstacle_query.iter() {
-            let collision = collide(
-                bullet_transform.translation
-                    + bullet_collider.offset.unwrap().extend(0.),
-                (*bullet_collider).into(),
-                transform.translation,
-                collider.clone().into(),
-            )
-            .is_some();
-
-            if collision {
+        let (e, player, _, _, transform, mut health, part) =
+            match target_query.get_mut(target) {
+                Ok(target) => target,
+                Err(_) => return,
+            };
+
+        health.damage(bullet.damage);
+        let position = transform.translation.truncate();
+        let destroyed = health.current <= 0.;
+
+        match player {
+            Some(player) => {
+                events.hit.send(PlayerHit {
+                    position,
+                    damage: bullet.damage,
+                });
+
+                if destroyed {
+                    events.player_destroyed.send(PlayerDestroyed {
+                        player: player.id,
+                        by: (bullet.player != ENEMY).then_some(bullet.player),
+                    });
+                }
+            }
+
+            None if destroyed => {
                 commands.entity(e).despawn_recursive();
-                commands.entity(bullet).despawn_recursive();
+                events.enemy_destroyed.send(EnemyDestroyed {
+                    player: bullet.player,
+                    position,
+                });
 
-                if obstacle.can_split {
-                    spawn_small_obstacles(
-                        commands,
-                        ts,
-                        transform.translation.truncate(),
-                        angle,
-                    );
+                if part.is_some_and(BossPart::is_core) {
+                    events.boss_defeated.send(BossDefeated {
+                        player: bullet.player,
+                        position,
+                    });
                 }
-                break;
             }
+
+            None => {}
+        }
+    }
+
+    fn release_bullets(
+        mut commands: Commands,
+        mut pools: ResMut<Pools>,
+        bullets_query: Query<Entity, (With<Bullet>, Without<Pooled>)>,
+    ) {
+        for e in bullets_query.iter() {
+            pools.bullets.release(&mut commands, e);
         }
     }
 
     fn remove_bullets(
         mut commands: Commands,
-        bullets_query: Query<(Entity, &Bullet)>,
+        mut pools: ResMut<Pools>,
+        bullets_query: Query<(Entity, &Bullet), Without<Pooled>>,
     ) {
         for (e, bullet) in bullets_query.iter() {
             if bullet.duration.finished() {
-                commands.entity(e).despawn_recursive();
+                pools.bullets.release(&mut commands, e);
             }
         }
     }
 }
+
+/// The path a bullet travelled from its previous position to its current
+/// one, checked as a whole so fast bullets can't skip over small debris
+/// between ticks.
+struct Sweep {
+    start: Vec2,
+    end: Vec2,
+    size: Vec2,
+}
+
+impl Sweep {
+    fn new(
+        bullet: &Bullet,
+        transform: &Transform,
+        collider: &Collider,
+    ) -> Self {
+        let offset = collider.offset.unwrap_or_default();
+
+        Self {
+            start: bullet.previous_position + offset,
+            end: transform.translation.truncate() + offset,
+            size: Vec2::from(*collider) * transform.scale.truncate(),
+        }
+    }
+
+    fn toi(&self, transform: &Transform, collider: &Collider) -> Option<f32> {
+        common::sweep_aabb(
+            self.start,
+            self.end,
+            self.size,
+            transform.translation.truncate(),
+            (*collider).into(),
+        )
+    }
+
+    /// The first obstacle touched, with the time of impact.
+    fn first_obstacle<'a>(
+        &self,
+        obstacle_query: &'a ObstacleQuery,
+        destroyed: &HashSet<Entity>,
+    ) -> Option<(f32, Entity, &'a Transform, &'a Obstacle)> {
+        obstacle_query
+            .iter()
+            .filter(|(e, ..)| !destroyed.contains(e))
+            .filter_map(|(e, collider, transform, obstacle)| {
+                self.toi(transform, collider)
+                    .map(|toi| (toi, e, transform, obstacle))
+            })
+            // Overlapping obstacles can be reached at the same time; pick
+            // between them by position rather than by query order.
+            .min_by(|(a, _, a_transform, _), (b, _, b_transform, _)| {
+                let (a_position, b_position) =
+                    (a_transform.translation, b_transform.translation);
+
+                a.total_cmp(b)
+                    .then(a_position.x.total_cmp(&b_position.x))
+                    .then(a_position.y.total_cmp(&b_position.y))
+            })
+    }
+
+    /// The first ship the bullet can hurt: enemies for the players' shots,
+    /// and players for the enemies' or, with friendly fire, each other's.
+    /// Ships that are down or can't be damaged are skipped.
+    fn first_target(
+        &self,
+        bullet: &Bullet,
+        target_query: &TargetQuery,
+        friendly_fire: bool,
+    ) -> Option<(f32, Entity)> {
+        let from_enemy = bullet.player == ENEMY;
+
+        target_query
+            .iter()
+            .filter(|(_, player, invulnerable, _, _, health, _)| {
+                let hostile = match player {
+                    Some(player) => {
+                        player.id != bullet.player
+                            && (from_enemy || friendly_fire)
+                    }
+                    None => !from_enemy,
+                };
+
+                hostile && health.current > 0. && invulnerable.is_none()
+            })
+            .filter_map(|(e, _, _, collider, transform, ..)| {
+                self.toi(transform, collider).map(|toi| (toi, e))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+    }
+}
+
+/// Puts `bullet` in flight, reusing a pooled entity when possible.
+pub fn spawn_bullet(
+    commands: &mut Commands,
+    pools: &mut Pools,
+    ts: &TexturesSheets,
+    transform: Transform,
+    bullet: Bullet,
+    collider: Collider,
+) -> Entity {
+    let mut sprite = TextureAtlasSprite::new(0);
+    if bullet.player == ENEMY {
+        sprite.color = Color::hex(graphics::SPACESHIP_RED).unwrap();
+    }
+
+    pools.bullets.spawn(
+        commands,
+        BulletBundle {
+            sprite: SpriteSheetBundle {
+                sprite,
+                texture_atlas: ts.projectiles.clone(),
+                transform,
+                ..Default::default()
+            },
+            bullet,
+            collider,
+            velocity: Velocity {
+                vx: BULLET_SPEED,
+                vy: BULLET_SPEED,
+            },
+            interpolated: Interpolated::default(),
+            name: Name::new("Bullet"),
+        },
+    )
+}
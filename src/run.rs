@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{
+        BossDefeated, EnemyDestroyed, ObstacleDestroyed, PlayerDestroyed,
+    },
+    player::{Player, PlayerSystem},
+    projectiles::ProjectileSystem,
+    settings::MatchRules,
+    simulation::{GameRng, SimulationApp, SimulationTime},
+    state::{self, GameState, LevelScoped},
+};
+
+pub const STARTING_LIVES: u32 = 3;
+
+/// Points for an obstacle that broke apart, and for a piece of debris.
+const OBSTACLE_POINTS: u32 = 100;
+const DEBRIS_POINTS: u32 = 25;
+pub const ENEMY_POINTS: u32 = 250;
+pub const BOSS_POINTS: u32 = 5000;
+
+/// Most ships in a versus round: keyboard and mouse, then a gamepad each.
+pub const MAX_PLAYERS: usize = 4;
+
+/// How many ships take part in a run, picked from the main menu.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum GameMode {
+    #[default]
+    Solo,
+    /// A second ship on the first gamepad, sharing lives and score.
+    Coop,
+    /// Two ships on two machines, `local` being the seat played on this one.
+    Online { local: usize },
+    /// Ships shooting at each other for frags, respawning without limit.
+    Versus { players: usize },
+}
+
+impl GameMode {
+    /// A versus round with as many seats as the rules allow, the ones past
+    /// the first only while there's a gamepad to play them with.
+    pub fn versus(rules: &MatchRules, gamepads: usize) -> Self {
+        let players = rules.players.min(gamepads + 1).clamp(2, MAX_PLAYERS);
+        GameMode::Versus { players }
+    }
+
+    pub fn players(self) -> usize {
+        match self {
+            GameMode::Solo => 1,
+            GameMode::Coop | GameMode::Online { .. } => 2,
+            GameMode::Versus { players } => players,
+        }
+    }
+
+    pub fn is_versus(self) -> bool {
+        matches!(self, GameMode::Versus { .. })
+    }
+
+    /// Whether bullets hurt ships other than the one that fired them.
+    pub fn friendly_fire(self, rules: &MatchRules) -> bool {
+        match self {
+            GameMode::Solo | GameMode::Online { .. } => false,
+            GameMode::Coop => rules.coop_friendly_fire,
+            GameMode::Versus { .. } => rules.versus_friendly_fire,
+        }
+    }
+}
+
+/// Points scored by everyone in the run; each player's share is kept on
+/// their `Player`.
+#[derive(Default)]
+pub struct Score(pub u32);
+
+/// Current wave, starting at 1. A new one begins once every obstacle of the
+/// previous one has been cleared.
+pub struct Wave(pub u32);
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+pub struct Lives(pub u32);
+
+/// Standing of a versus round.
+#[derive(Default)]
+pub struct Round {
+    /// Other ships destroyed, per seat.
+    pub frags: Vec<u32>,
+    pub deaths: Vec<u32>,
+    pub elapsed: Duration,
+}
+
+impl Round {
+    fn new(players: usize) -> Self {
+        Self {
+            frags: vec![0; players],
+            deaths: vec![0; players],
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Time left before the round ends, if it is timed.
+    pub fn remaining(&self, rules: &MatchRules) -> Option<Duration> {
+        (rules.time_limit > 0).then(|| {
+            Duration::from_secs(u64::from(rules.time_limit) * 60)
+                .saturating_sub(self.elapsed)
+        })
+    }
+
+    pub fn is_over(&self, rules: &MatchRules) -> bool {
+        let frag_limit_reached = rules.frag_limit > 0
+            && self.frags.iter().any(|&frags| frags >= rules.frag_limit);
+
+        frag_limit_reached || self.remaining(rules) == Some(Duration::ZERO)
+    }
+
+    /// The seat with the most frags, `None` on a draw.
+    pub fn winner(&self) -> Option<usize> {
+        let best = self.frags.iter().max()?;
+        let mut leaders =
+            (0..self.frags.len()).filter(|&seat| self.frags[seat] == *best);
+
+        match (leaders.next(), leaders.next()) {
+            (Some(seat), None) => Some(seat),
+            _ => None,
+        }
+    }
+}
+
+/// Seed the next run is played with instead of a fresh one, when it has to
+/// match a run elsewhere.
+#[derive(Default)]
+pub struct NextSeed(pub Option<u64>);
+
+impl Default for Lives {
+    fn default() -> Self {
+        Self(STARTING_LIVES)
+    }
+}
+
+pub fn points(ev: &ObstacleDestroyed) -> u32 {
+    if ev.split {
+        OBSTACLE_POINTS
+    } else {
+        DEBRIS_POINTS
+    }
+}
+
+/// Systems that need the fresh seed of a new run go after `Reset`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum RunSystem {
+    Reset,
+}
+
+/// Tracks the progress of a single run: score, wave and lives left.
+pub struct RunPlugin;
+
+impl Plugin for RunPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameMode>()
+            .init_resource::<Score>()
+            .init_resource::<Wave>()
+            .init_resource::<Lives>()
+            .init_resource::<NextSeed>()
+            .init_resource::<Round>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Level)
+                    .with_system(Self::reset_run.label(RunSystem::Reset)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Level)
+                    .with_system(state::despawn_with::<LevelScoped>),
+            )
+            .add_simulation_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(
+                        Self::award_points
+                            .after(ProjectileSystem::Collisions)
+                            .after(PlayerSystem::Hits),
+                    )
+                    .with_system(
+                        Self::award_frags
+                            .after(ProjectileSystem::Collisions)
+                            .after(PlayerSystem::Hits),
+                    )
+                    .with_system(
+                        Self::tick_round
+                            .after(ProjectileSystem::Collisions)
+                            .after(PlayerSystem::Hits),
+                    ),
+            );
+    }
+}
+
+impl RunPlugin {
+    /// Resets in place rather than through commands, so systems running
+    /// right after entering the level already see the fresh values.
+    fn reset_run(
+        mut rng: ResMut<GameRng>,
+        mut next_seed: ResMut<NextSeed>,
+        mode: Res<GameMode>,
+        mut round: ResMut<Round>,
+        mut score: ResMut<Score>,
+        mut wave: ResMut<Wave>,
+        mut lives: ResMut<Lives>,
+    ) {
+        // Every run gets its own seed, so it can be told apart and replayed.
+        *rng = match next_seed.0.take() {
+            Some(seed) => GameRng::from_seed(seed),
+            None => GameRng::default(),
+        };
+        *score = Score::default();
+        *wave = Wave::default();
+        *lives = Lives::default();
+        *round = Round::new(mode.players());
+    }
+
+    fn award_frags(
+        mut round: ResMut<Round>,
+        mut destroyed_evr: EventReader<PlayerDestroyed>,
+    ) {
+        for ev in destroyed_evr.iter() {
+            round.deaths[ev.player] += 1;
+
+            if let Some(by) = ev.by.filter(|&by| by != ev.player) {
+                round.frags[by] += 1;
+            }
+        }
+    }
+
+    fn tick_round(
+        mode: Res<GameMode>,
+        time: Res<SimulationTime>,
+        mut round: ResMut<Round>,
+    ) {
+        if mode.is_versus() {
+            round.elapsed += time.delta();
+        }
+    }
+
+    fn award_points(
+        mut score: ResMut<Score>,
+        mut destroyed_evr: EventReader<ObstacleDestroyed>,
+        mut enemy_destroyed_evr: EventReader<EnemyDestroyed>,
+        mut boss_defeated_evr: EventReader<BossDefeated>,
+        mut player_query: Query<&mut Player>,
+    ) {
+        let obstacles = destroyed_evr.iter().map(|ev| (ev.player, points(ev)));
+        let enemies = enemy_destroyed_evr
+            .iter()
+            .map(|ev| (ev.player, ENEMY_POINTS));
+        let bosses =
+            boss_defeated_evr.iter().map(|ev| (ev.player, BOSS_POINTS));
+
+        for (seat, points) in obstacles.chain(enemies).chain(bosses) {
+            score.0 += points;
+
+            for mut player in player_query.iter_mut() {
+                if player.id == seat {
+                    player.score += points;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(frag_limit: u32, time_limit: u32) -> MatchRules {
+        MatchRules {
+            frag_limit,
+            time_limit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn winner_has_the_most_frags() {
+        let mut round = Round::new(3);
+        round.frags = vec![2, 5, 1];
+
+        assert_eq!(round.winner(), Some(1));
+    }
+
+    #[test]
+    fn tied_leaders_draw() {
+        let mut round = Round::new(3);
+        round.frags = vec![4, 1, 4];
+
+        assert_eq!(round.winner(), None);
+        assert_eq!(Round::new(2).winner(), None);
+    }
+
+    #[test]
+    fn round_ends_at_frag_limit() {
+        let mut round = Round::new(2);
+        round.frags = vec![9, 3];
+        assert!(!round.is_over(&rules(10, 0)));
+
+        round.frags[0] = 10;
+        assert!(round.is_over(&rules(10, 0)));
+        assert!(!round.is_over(&rules(0, 0)));
+    }
+
+    #[test]
+    fn round_ends_at_time_limit() {
+        let mut round = Round::new(2);
+        round.elapsed = Duration::from_secs(119);
+        assert!(!round.is_over(&rules(0, 2)));
+        assert_eq!(round.remaining(&rules(0, 2)), Some(Duration::from_secs(1)));
+
+        round.elapsed = Duration::from_secs(120);
+        assert!(round.is_over(&rules(0, 2)));
+        assert_eq!(round.remaining(&rules(0, 0)), None);
+    }
+
+    #[test]
+    fn versus_seats_a_ship_per_gamepad() {
+        let rules = MatchRules::default();
+
+        assert_eq!(GameMode::versus(&rules, 0).players(), 2);
+        assert_eq!(GameMode::versus(&rules, 1).players(), 2);
+        assert_eq!(GameMode::versus(&rules, 2).players(), 3);
+        assert_eq!(GameMode::versus(&rules, 8).players(), MAX_PLAYERS);
+    }
+
+    #[test]
+    fn versus_seats_are_capped_by_the_rules() {
+        let rules = MatchRules {
+            players: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(GameMode::versus(&rules, 8).players(), 3);
+    }
+
+    #[test]
+    fn friendly_fire_follows_the_mode() {
+        let rules = MatchRules {
+            coop_friendly_fire: true,
+            versus_friendly_fire: false,
+            ..Default::default()
+        };
+
+        assert!(GameMode::Coop.friendly_fire(&rules));
+        assert!(!GameMode::versus(&rules, 1).friendly_fire(&rules));
+        assert!(!GameMode::Online { local: 0 }.friendly_fire(&rules));
+    }
+}
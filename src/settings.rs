@@ -0,0 +1,692 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    prelude::*,
+    window::{PresentMode, WindowMode},
+};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::{CameraSettings, ScalingMode, VIEW_SIZE},
+    common::Playfield,
+    run::{GameMode, MAX_PLAYERS},
+    state::GameState,
+};
+
+const SETTINGS_FILE: &str = "settings.ron";
+
+/// Room kept past a screenful of world on each side, enough for the widest
+/// obstacle or ship to be placed away from the edges.
+const WORLD_MARGIN: f32 = 120.;
+
+pub const RESOLUTIONS: [(u32, u32); 4] =
+    [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisplayMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    const ALL: [DisplayMode; 3] = [
+        DisplayMode::Windowed,
+        DisplayMode::Borderless,
+        DisplayMode::Fullscreen,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "Windowed",
+            DisplayMode::Borderless => "Borderless",
+            DisplayMode::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+impl From<DisplayMode> for WindowMode {
+    fn from(mode: DisplayMode) -> Self {
+        match mode {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub primary: KeyCode,
+    pub secondary: Option<KeyCode>,
+}
+
+impl KeyBinding {
+    const fn new(primary: KeyCode, secondary: Option<KeyCode>) -> Self {
+        Self { primary, secondary }
+    }
+
+    pub fn pressed(&self, keyboard: &Input<KeyCode>) -> bool {
+        keyboard.pressed(self.primary)
+            || self.secondary.is_some_and(|key| keyboard.pressed(key))
+    }
+
+    pub fn just_pressed(&self, keyboard: &Input<KeyCode>) -> bool {
+        keyboard.just_pressed(self.primary)
+            || self.secondary.is_some_and(|key| keyboard.just_pressed(key))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Fullscreen,
+    Minimap,
+}
+
+impl Action {
+    const ALL: [Action; 7] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Pause,
+        Action::Fullscreen,
+        Action::Minimap,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Pause => "Pause",
+            Action::Fullscreen => "Fullscreen",
+            Action::Minimap => "Minimap",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: KeyBinding,
+    pub down: KeyBinding,
+    pub left: KeyBinding,
+    pub right: KeyBinding,
+    pub pause: KeyBinding,
+    pub fullscreen: KeyBinding,
+    pub minimap: KeyBinding,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: KeyBinding::new(KeyCode::W, Some(KeyCode::Up)),
+            down: KeyBinding::new(KeyCode::S, Some(KeyCode::Down)),
+            left: KeyBinding::new(KeyCode::A, Some(KeyCode::Left)),
+            right: KeyBinding::new(KeyCode::D, Some(KeyCode::Right)),
+            pause: KeyBinding::new(KeyCode::Escape, None),
+            fullscreen: KeyBinding::new(KeyCode::F11, None),
+            minimap: KeyBinding::new(KeyCode::M, Some(KeyCode::Tab)),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn get(&self, action: Action) -> &KeyBinding {
+        match action {
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::Pause => &self.pause,
+            Action::Fullscreen => &self.fullscreen,
+            Action::Minimap => &self.minimap,
+        }
+    }
+
+    fn get_mut(&mut self, action: Action) -> &mut KeyBinding {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Pause => &mut self.pause,
+            Action::Fullscreen => &mut self.fullscreen,
+            Action::Minimap => &mut self.minimap,
+        }
+    }
+}
+
+/// How runs with more than one ship are played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchRules {
+    /// Whether co-op shots hurt the other ship. Always off online, where
+    /// both ends must agree on it.
+    pub coop_friendly_fire: bool,
+    /// Whether versus shots hurt the other ships. Off, only debris can take
+    /// a ship down.
+    pub versus_friendly_fire: bool,
+    /// Most ships in a versus round, as long as there are gamepads for them.
+    pub players: usize,
+    /// Frags that win a versus round, `0` for no limit.
+    pub frag_limit: u32,
+    /// Minutes a versus round lasts, `0` for no limit.
+    pub time_limit: u32,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            coop_friendly_fire: false,
+            versus_friendly_fire: true,
+            players: MAX_PLAYERS,
+            frag_limit: 10,
+            time_limit: 5,
+        }
+    }
+}
+
+/// Everything the player can tweak from the settings screen. Fields missing
+/// from an older settings file fall back to their defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub display_mode: DisplayMode,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    pub scaling: ScalingMode,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+    pub camera_lead: bool,
+    /// Size of the world in units. Only read at startup.
+    pub world_size: (u32, u32),
+    pub bindings: KeyBindings,
+    pub rules: MatchRules,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            display_mode: DisplayMode::Windowed,
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            scaling: ScalingMode::default(),
+            master_volume: 1.,
+            music_volume: 0.6,
+            sfx_volume: 0.8,
+            screen_shake: true,
+            hit_stop: true,
+            camera_lead: false,
+            world_size: (2560, 1440),
+            bindings: KeyBindings::default(),
+            rules: MatchRules::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir()
+            .map(|dir| dir.join("space-shooter").join(SETTINGS_FILE))
+    }
+
+    /// Reads the settings file, falling back to the defaults when it is
+    /// missing or can't be parsed.
+    pub fn load() -> Self {
+        Self::path().map_or_else(Self::default, |path| Self::load_from(&path))
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        ron::from_str(&contents).unwrap_or_else(|err| {
+            warn!("ignoring invalid settings in {}: {}", path.display(), err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let contents =
+            match ron::ser::to_string_pretty(self, Default::default()) {
+                Ok(contents) => contents,
+                Err(err) => {
+                    warn!("failed to serialize settings: {}", err);
+                    return;
+                }
+            };
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, contents));
+
+        if let Err(err) = written {
+            warn!("failed to save settings to {}: {}", path.display(), err);
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    /// The world as configured, grown to at least a screenful plus margins
+    /// so spawning inside it always has room.
+    pub fn playfield(&self) -> Playfield {
+        let min = VIEW_SIZE + WORLD_MARGIN * 2.;
+
+        Playfield {
+            width: (self.world_size.0 as f32).max(min.x),
+            height: (self.world_size.1 as f32).max(min.y),
+        }
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            width: self.resolution.0 as f32,
+            height: self.resolution.1 as f32,
+            title: "Shooter".to_string(),
+            resizable: true,
+            mode: self.display_mode.into(),
+            present_mode: self.present_mode(),
+            scale_factor_override: Some(1.0),
+            ..Default::default()
+        }
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .add_system(Self::toggle_fullscreen)
+            .add_system(Self::apply_settings.after(Self::toggle_fullscreen))
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(Self::open_settings),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Settings)
+                    .with_system(Self::show_cursor),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(Self::settings_menu),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Settings)
+                    .with_system(Self::hide_cursor)
+                    .with_system(Self::save_settings),
+            );
+    }
+}
+
+impl SettingsPlugin {
+    fn toggle_fullscreen(
+        keyboard: Res<Input<KeyCode>>,
+        mut settings: ResMut<Settings>,
+    ) {
+        if !settings.bindings.fullscreen.just_pressed(&keyboard) {
+            return;
+        }
+
+        settings.display_mode = match settings.display_mode {
+            DisplayMode::Windowed => DisplayMode::Borderless,
+            _ => DisplayMode::Windowed,
+        };
+    }
+
+    fn apply_settings(
+        settings: Res<Settings>,
+        mut camera_settings: ResMut<CameraSettings>,
+        mut windows: ResMut<Windows>,
+    ) {
+        if !settings.is_changed() {
+            return;
+        }
+
+        let window = windows.get_primary_mut().unwrap();
+
+        let mode = settings.display_mode.into();
+        if window.mode() != mode {
+            window.set_mode(mode);
+        }
+
+        let (width, height) = settings.resolution;
+        if mode == WindowMode::Windowed
+            && (window.requested_width() != width as f32
+                || window.requested_height() != height as f32)
+        {
+            window.set_resolution(width as f32, height as f32);
+        }
+
+        if window.present_mode() != settings.present_mode() {
+            window.set_present_mode(settings.present_mode());
+        }
+
+        let camera = CameraSettings {
+            scaling: settings.scaling,
+            screen_shake: settings.screen_shake,
+            hit_stop: settings.hit_stop,
+            camera_lead: settings.camera_lead,
+        };
+
+        if *camera_settings != camera {
+            *camera_settings = camera;
+        }
+    }
+
+    /// An online run keeps going on the other end, so it can't be paused.
+    fn open_settings(
+        mut keyboard: ResMut<Input<KeyCode>>,
+        settings: Res<Settings>,
+        mode: Res<GameMode>,
+        mut state: ResMut<State<GameState>>,
+    ) {
+        if matches!(*mode, GameMode::Online { .. }) {
+            return;
+        }
+
+        if settings.bindings.pause.just_pressed(&keyboard) {
+            // The settings screen closes on the same key, and runs within
+            // this very frame.
+            keyboard.clear();
+            state.push(GameState::Settings).unwrap();
+        }
+    }
+
+    fn show_cursor(mut windows: ResMut<Windows>) {
+        windows
+            .get_primary_mut()
+            .unwrap()
+            .set_cursor_visibility(true);
+    }
+
+    fn hide_cursor(mut windows: ResMut<Windows>) {
+        windows
+            .get_primary_mut()
+            .unwrap()
+            .set_cursor_visibility(false);
+    }
+
+    fn save_settings(settings: Res<Settings>) {
+        settings.save();
+    }
+
+    fn settings_menu(
+        mut egui_context: ResMut<EguiContext>,
+        mut settings: ResMut<Settings>,
+        mut state: ResMut<State<GameState>>,
+        mut keyboard: ResMut<Input<KeyCode>>,
+        mut rebinding: Local<Option<(Action, bool)>>,
+    ) {
+        let mut edited = settings.clone();
+        let mut back = false;
+        let mut quit = false;
+        let in_run = state.inactives().contains(&GameState::Level);
+
+        if let Some((action, primary)) = *rebinding {
+            let pressed = keyboard.get_just_pressed().next().copied();
+            if let Some(key) = pressed {
+                if key != KeyCode::Escape {
+                    let binding = edited.bindings.get_mut(action);
+                    if primary {
+                        binding.primary = key;
+                    } else {
+                        binding.secondary = Some(key);
+                    }
+                }
+
+                *rebinding = None;
+                keyboard.clear();
+            }
+        } else if settings.bindings.pause.just_pressed(&keyboard) {
+            back = true;
+        }
+
+        egui::Window::new("Settings")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(egui_context.ctx_mut(), |ui| {
+                egui::Grid::new("display").num_columns(2).show(ui, |ui| {
+                    ui.label("Window mode");
+                    egui::ComboBox::from_id_source("display_mode")
+                        .selected_text(edited.display_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in DisplayMode::ALL {
+                                ui.selectable_value(
+                                    &mut edited.display_mode,
+                                    mode,
+                                    mode.label(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Resolution");
+                    egui::ComboBox::from_id_source("resolution")
+                        .selected_text(format!(
+                            "{}x{}",
+                            edited.resolution.0, edited.resolution.1
+                        ))
+                        .show_ui(ui, |ui| {
+                            for resolution in RESOLUTIONS {
+                                ui.selectable_value(
+                                    &mut edited.resolution,
+                                    resolution,
+                                    format!(
+                                        "{}x{}",
+                                        resolution.0, resolution.1
+                                    ),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Scaling");
+                    egui::ComboBox::from_id_source("scaling")
+                        .selected_text(format!("{:?}", edited.scaling))
+                        .show_ui(ui, |ui| {
+                            for scaling in [
+                                ScalingMode::Letterbox,
+                                ScalingMode::Fit,
+                                ScalingMode::Expand,
+                            ] {
+                                ui.selectable_value(
+                                    &mut edited.scaling,
+                                    scaling,
+                                    format!("{:?}", scaling),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("VSync");
+                    ui.checkbox(&mut edited.vsync, "");
+                    ui.end_row();
+
+                    for (label, volume) in [
+                        ("Master volume", &mut edited.master_volume),
+                        ("Music volume", &mut edited.music_volume),
+                        ("Effects volume", &mut edited.sfx_volume),
+                    ] {
+                        ui.label(label);
+                        ui.add(egui::Slider::new(volume, 0.0..=1.0));
+                        ui.end_row();
+                    }
+
+                    for (label, enabled) in [
+                        ("Screen shake", &mut edited.screen_shake),
+                        ("Hit stop", &mut edited.hit_stop),
+                        ("Camera lead", &mut edited.camera_lead),
+                    ] {
+                        ui.label(label);
+                        ui.checkbox(enabled, "");
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+
+                egui::Grid::new("rules").num_columns(2).show(ui, |ui| {
+                    ui.label("Co-op friendly fire");
+                    ui.checkbox(&mut edited.rules.coop_friendly_fire, "");
+                    ui.end_row();
+
+                    ui.label("Versus friendly fire");
+                    ui.checkbox(&mut edited.rules.versus_friendly_fire, "");
+                    ui.end_row();
+
+                    ui.label("Versus players");
+                    ui.add(egui::Slider::new(
+                        &mut edited.rules.players,
+                        2..=MAX_PLAYERS,
+                    ));
+                    ui.end_row();
+
+                    ui.label("Versus frag limit");
+                    ui.add(egui::Slider::new(
+                        &mut edited.rules.frag_limit,
+                        0..=50,
+                    ));
+                    ui.end_row();
+
+                    ui.label("Versus time limit (min)");
+                    ui.add(egui::Slider::new(
+                        &mut edited.rules.time_limit,
+                        0..=30,
+                    ));
+                    ui.end_row();
+                });
+
+                ui.separator();
+
+                egui::Grid::new("bindings").num_columns(3).show(ui, |ui| {
+                    for action in Action::ALL {
+                        let binding = *edited.bindings.get(action);
+
+                        ui.label(action.label());
+
+                        for (primary, key) in [
+                            (true, Some(binding.primary)),
+                            (false, binding.secondary),
+                        ] {
+                            let text = if *rebinding == Some((action, primary))
+                            {
+                                "press a key...".to_string()
+                            } else {
+                                key.map_or("-".to_string(), |key| {
+                                    format!("{:?}", key)
+                                })
+                            };
+
+                            if ui.button(text).clicked() {
+                                *rebinding = Some((action, primary));
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Reset").clicked() {
+                        edited = Settings::default();
+                    }
+
+                    if ui.button("Back").clicked() {
+                        back = true;
+                    }
+
+                    // Leaving the level saves the run for later.
+                    if in_run && ui.button("Save and quit").clicked() {
+                        quit = true;
+                    }
+                });
+            });
+
+        if edited != *settings {
+            *settings = edited;
+        }
+
+        if quit {
+            keyboard.clear();
+            state.replace(GameState::MainMenu).unwrap();
+        } else if back {
+            keyboard.clear();
+            state.pop().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path of its own in the temp directory, with nothing there yet.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("space-shooter-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join(name).with_extension("ron");
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_defaults() {
+        let path = scratch("settings_missing");
+
+        assert_eq!(Settings::load_from(&path), Settings::default());
+    }
+
+    #[test]
+    fn invalid_file_falls_back_to_defaults() {
+        let path = scratch("settings_invalid");
+        fs::write(&path, "(vsync: maybe").unwrap();
+
+        assert_eq!(Settings::load_from(&path), Settings::default());
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let path = scratch("settings_partial");
+        fs::write(&path, "(vsync: false, rules: (frag_limit: 3))").unwrap();
+
+        let settings = Settings::load_from(&path);
+        assert!(!settings.vsync);
+        assert_eq!(settings.rules.frag_limit, 3);
+        assert_eq!(settings.rules.time_limit, MatchRules::default().time_limit);
+        assert_eq!(settings.bindings, KeyBindings::default());
+    }
+}
@@ -0,0 +1,324 @@
+use std::time::Duration;
+
+use bevy::{ecs::schedule::IntoSystemDescriptor, prelude::*};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub const TICK_RATE: f64 = 60.;
+
+/// Upper bound on ticks run in a single frame, so a long stall doesn't
+/// snowball into an ever growing backlog of ticks.
+pub const MAX_TICKS_PER_FRAME: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct FixedUpdate;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub enum SimulationStage {
+    PreTick,
+    Tick,
+    PostTick,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum SimulationSystem {
+    Run,
+}
+
+/// Gameplay systems run inside their own schedule, driven a whole number of
+/// times per frame by `SimulationPlugin::run_simulation`.
+pub struct Simulation(Schedule);
+
+impl Default for Simulation {
+    fn default() -> Self {
+        let mut schedule = Schedule::default();
+
+        schedule
+            .add_stage(
+                SimulationStage::PreTick,
+                SystemStage::single_threaded()
+                    .with_system(SimulationPlugin::record_previous),
+            )
+            .add_stage(SimulationStage::Tick, SystemStage::single_threaded())
+            .add_stage(
+                SimulationStage::PostTick,
+                SystemStage::single_threaded()
+                    .with_system(SimulationPlugin::record_current)
+                    .with_system(SimulationPlugin::advance_tick),
+            );
+
+        Self(schedule)
+    }
+}
+
+pub struct SimulationTime {
+    step: Duration,
+    accumulator: Duration,
+    tick: u64,
+    scale: f32,
+    stalled: bool,
+}
+
+impl Default for SimulationTime {
+    fn default() -> Self {
+        Self {
+            step: Duration::from_secs_f64(1. / TICK_RATE),
+            accumulator: Duration::ZERO,
+            tick: 0,
+            scale: 1.,
+            stalled: false,
+        }
+    }
+}
+
+impl SimulationTime {
+    pub fn delta(&self) -> Duration {
+        self.step
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// How far the frame is between the last tick and the next one.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    /// Carries on counting from a restored run.
+    pub fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+    }
+
+    /// Slows down (or speeds up) how fast ticks are accumulated, without
+    /// changing the length of a tick.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Holds the simulation on its current tick, dropping the time that
+    /// passes meanwhile.
+    pub fn set_stalled(&mut self, stalled: bool) {
+        self.stalled = stalled;
+    }
+
+    fn accumulate(&mut self, delta: Duration) -> u32 {
+        if self.stalled {
+            self.accumulator = Duration::ZERO;
+            return 0;
+        }
+
+        self.accumulator += delta.mul_f32(self.scale);
+
+        let mut ticks = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            ticks += 1;
+        }
+
+        if ticks > MAX_TICKS_PER_FRAME {
+            ticks = MAX_TICKS_PER_FRAME;
+        }
+
+        ticks
+    }
+}
+
+/// Seeded RNG used by every gameplay system, so a run is reproducible from
+/// its seed.
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Picks a generator back up `word_pos` words into the stream of
+    /// `seed`, as returned by `word_pos`.
+    pub fn from_position(seed: u64, word_pos: u128) -> Self {
+        let mut rng = Self::from_seed(seed);
+        rng.rng.set_word_pos(word_pos);
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// How far into its stream the generator is.
+    pub fn word_pos(&self) -> u128 {
+        self.rng.get_word_pos()
+    }
+
+    /// An independent generator derived from the seed, for things that
+    /// should be reproducible without drawing from the gameplay stream.
+    pub fn fork(&self, stream: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(stream);
+        rng
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::from_seed(rand::thread_rng().next_u64())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Smooths the rendered translation of an entity moved by the simulation
+/// between the two most recent ticks.
+#[derive(Component, Clone, Copy, Default)]
+pub struct Interpolated {
+    previous: Option<Vec3>,
+    current: Option<Vec3>,
+}
+
+impl Interpolated {
+    /// Translation as of the latest tick, which the rendered one lags
+    /// behind.
+    pub fn current(&self) -> Option<Vec3> {
+        self.current
+    }
+
+    /// How far the entity moved over the latest tick.
+    #[cfg(debug_assertions)]
+    pub fn step(&self) -> Option<Vec2> {
+        Some((self.current? - self.previous?).truncate())
+    }
+}
+
+pub trait SimulationApp {
+    fn add_simulation_system_set(&mut self, set: SystemSet) -> &mut Self;
+
+    fn add_simulation_system_to_stage<Params>(
+        &mut self,
+        stage: SimulationStage,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self;
+}
+
+impl SimulationApp for App {
+    fn add_simulation_system_set(&mut self, set: SystemSet) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(Simulation::default)
+            .0
+            .add_system_set_to_stage(SimulationStage::Tick, set);
+        self
+    }
+
+    fn add_simulation_system_to_stage<Params>(
+        &mut self,
+        stage: SimulationStage,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(Simulation::default)
+            .0
+            .add_system_to_stage(stage, system);
+        self
+    }
+}
+
+/// Runs `ticks` ticks of the simulation right away, regardless of how much
+/// time has passed.
+pub fn run_ticks(world: &mut World, ticks: u64) {
+    world.resource_scope(|world, mut simulation: Mut<Simulation>| {
+        for _ in 0..ticks {
+            simulation.0.run(world);
+        }
+    });
+}
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Simulation>()
+            .init_resource::<SimulationTime>()
+            .init_resource::<GameRng>()
+            .add_stage_after(
+                CoreStage::Update,
+                FixedUpdate,
+                SystemStage::single_threaded().with_system(
+                    Self::run_simulation
+                        .exclusive_system()
+                        .label(SimulationSystem::Run),
+                ),
+            );
+    }
+}
+
+impl SimulationPlugin {
+    fn run_simulation(world: &mut World) {
+        let delta = world.resource::<Time>().delta();
+        let ticks = world.resource_mut::<SimulationTime>().accumulate(delta);
+
+        let mut query = world.query::<(&mut Transform, &Interpolated)>();
+
+        if ticks > 0 {
+            for (mut transform, interpolated) in query.iter_mut(world) {
+                if let Some(current) = interpolated.current {
+                    transform.translation = current;
+                }
+            }
+        }
+
+        run_ticks(world, ticks.into());
+
+        let alpha = world.resource::<SimulationTime>().alpha();
+
+        for (mut transform, interpolated) in query.iter_mut(world) {
+            if let Interpolated {
+                previous: Some(previous),
+                current: Some(current),
+            } = *interpolated
+            {
+                transform.translation = previous.lerp(current, alpha);
+            }
+        }
+    }
+
+    fn record_previous(mut query: Query<(&Transform, &mut Interpolated)>) {
+        for (transform, mut interpolated) in query.iter_mut() {
+            interpolated.previous = Some(transform.translation);
+        }
+    }
+
+    fn record_current(mut query: Query<(&Transform, &mut Interpolated)>) {
+        for (transform, mut interpolated) in query.iter_mut() {
+            interpolated.current = Some(transform.translation);
+        }
+    }
+
+    fn advance_tick(mut time: ResMut<SimulationTime>) {
+        time.tick += 1;
+    }
+}
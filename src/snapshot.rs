@@ -0,0 +1,983 @@
+use std::{fs, hash::Hasher, path::PathBuf, time::Duration};
+
+use bevy::{app::AppExit, ecs::system::CommandQueue, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{Brain, Steering},
+    boss::{self, Boss, BossPart},
+    common::{Collider, Health, Velocity},
+    enemies::{self, Enemy},
+    graphics::TexturesSheets,
+    obstacles::{self, MovingObstacle, Obstacle},
+    patterns::Emission,
+    pickups::{self, Pickup, PickupKind},
+    player::{Cursor, Invulnerable, Player},
+    pool::{Pooled, Pools},
+    projectiles::{self, Bullet},
+    run::{GameMode, Lives, Score, Wave},
+    simulation::{
+        FixedUpdate, GameRng, Interpolated, SimulationSystem, SimulationTime,
+    },
+    stage::{Campaign, StageProgress},
+    state::GameState,
+};
+
+const SAVE_FILE: &str = "save.ron";
+
+/// Bumped whenever `Snapshot` changes in a way older saves can't be read
+/// into.
+const FORMAT_VERSION: u32 = 7;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TimerState {
+    pub elapsed: Duration,
+    pub duration: Duration,
+    pub repeating: bool,
+}
+
+impl From<&Timer> for TimerState {
+    fn from(timer: &Timer) -> Self {
+        Self {
+            elapsed: timer.elapsed(),
+            duration: timer.duration(),
+            repeating: timer.repeating(),
+        }
+    }
+}
+
+impl From<&TimerState> for Timer {
+    fn from(state: &TimerState) -> Self {
+        let mut timer = Timer::new(state.duration, state.repeating);
+        timer.set_elapsed(state.elapsed);
+        // Catches up on whether the timer is finished.
+        timer.tick(Duration::ZERO);
+        timer
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TransformState {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl From<&Transform> for TransformState {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+}
+
+impl From<&TransformState> for Transform {
+    fn from(state: &TransformState) -> Self {
+        Self {
+            translation: state.translation,
+            rotation: state.rotation,
+            scale: state.scale,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub id: usize,
+    pub score: u32,
+    pub transform: TransformState,
+    pub velocity: Velocity,
+    pub collider: Collider,
+    pub health: Health,
+    pub just_moved: bool,
+    pub invulnerable: Option<TimerState>,
+    pub cursor: CursorState,
+    pub cursor_transform: TransformState,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CursorState {
+    pub player: usize,
+    pub actual_angle: f32,
+    pub computed_angle: f32,
+    pub fired: bool,
+    pub last_target_pos: Vec2,
+    pub rate: TimerState,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObstacleState {
+    pub sprite: usize,
+    pub transform: TransformState,
+    pub collider: Collider,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MotionState {
+    pub direction: Vec2,
+    pub duration: TimerState,
+    pub angle: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DebrisState {
+    pub sprite: usize,
+    pub transform: TransformState,
+    pub collider: Collider,
+    pub motion: MotionState,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BulletState {
+    pub player: usize,
+    pub transform: TransformState,
+    pub collider: Collider,
+    pub damage: f32,
+    pub direction: Vec2,
+    pub duration: TimerState,
+    pub previous_position: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnemyState {
+    pub sprite: usize,
+    pub transform: TransformState,
+    pub health: Health,
+    pub steering: Steering,
+    pub brain: Brain,
+    pub gun: Emission,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BossState {
+    pub def: usize,
+    pub phase: usize,
+    pub home: Vec2,
+    pub elapsed: f32,
+    pub emissions: Vec<Emission>,
+    pub position: Vec2,
+    /// Health of the parts still standing, by index.
+    pub parts: Vec<(usize, Health)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PickupState {
+    pub kind: PickupKind,
+    pub age: f32,
+    pub position: Vec2,
+}
+
+impl From<&BossState> for Boss {
+    fn from(state: &BossState) -> Self {
+        Self {
+            def: state.def,
+            phase: state.phase,
+            home: state.home,
+            elapsed: state.elapsed,
+            emissions: state.emissions.clone(),
+        }
+    }
+}
+
+impl From<&BulletState> for Bullet {
+    fn from(state: &BulletState) -> Self {
+        Self {
+            player: state.player,
+            damage: state.damage,
+            direction: state.direction,
+            duration: (&state.duration).into(),
+            previous_position: state.previous_position,
+        }
+    }
+}
+
+type PlayerData = (
+    &'static Transform,
+    &'static Velocity,
+    &'static Collider,
+    &'static Health,
+    &'static Player,
+    Option<&'static Invulnerable>,
+    Option<&'static Interpolated>,
+);
+
+type ObstacleData = (
+    &'static TextureAtlasSprite,
+    &'static Transform,
+    &'static Collider,
+);
+
+type DebrisData = (
+    &'static TextureAtlasSprite,
+    &'static Transform,
+    &'static Collider,
+    &'static MovingObstacle,
+    Option<&'static Interpolated>,
+);
+
+type BulletData = (
+    &'static Transform,
+    &'static Collider,
+    &'static Bullet,
+    Option<&'static Interpolated>,
+);
+
+type EnemyData = (
+    &'static Transform,
+    &'static Health,
+    &'static Steering,
+    &'static Brain,
+    &'static Enemy,
+    Option<&'static Interpolated>,
+);
+
+type BossData = (
+    &'static Boss,
+    &'static Transform,
+    &'static Health,
+    Option<&'static Interpolated>,
+);
+
+/// The transform as the simulation left it, rather than as last rendered.
+fn simulated(
+    transform: &Transform,
+    interpolated: Option<&Interpolated>,
+) -> TransformState {
+    let mut state = TransformState::from(transform);
+
+    if let Some(current) = interpolated.and_then(Interpolated::current) {
+        state.translation = current;
+    }
+
+    state
+}
+
+/// FNV-1a, which unlike the standard library's hasher is the same on every
+/// platform and build.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Fnv {
+    fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    fn write_vec3(&mut self, value: Vec3) {
+        for axis in value.to_array() {
+            self.write_f32(axis);
+        }
+    }
+}
+
+/// Obstacles that haven't broken apart yet.
+type LargeObstacle = (With<Obstacle>, Without<MovingObstacle>, Without<Pooled>);
+
+/// Ships and items that restore despawns and spawns again.
+type Replaced = Or<(With<Enemy>, With<BossPart>, With<Pickup>)>;
+
+/// Everything needed to pick a run back up exactly where it was left.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    pub tick: u64,
+    pub seed: u64,
+    pub word_pos: u128,
+    pub mode: GameMode,
+    pub score: u32,
+    pub wave: u32,
+    pub lives: u32,
+    pub players: Vec<PlayerState>,
+    pub obstacles: Vec<ObstacleState>,
+    pub debris: Vec<DebrisState>,
+    pub bullets: Vec<BulletState>,
+    pub enemies: Vec<EnemyState>,
+    pub boss: Option<BossState>,
+    pub pickups: Vec<PickupState>,
+    /// Progress through the campaign, for a campaign run.
+    pub campaign: Option<StageProgress>,
+}
+
+impl Snapshot {
+    /// Records the level as it stands. `None` when there is no level to
+    /// record.
+    pub fn capture(world: &mut World) -> Option<Self> {
+        let mut snapshot = Self::default();
+        snapshot.record(world).then_some(snapshot)
+    }
+
+    /// Records the level over this snapshot, reusing what it already holds.
+    /// `false`, leaving it half overwritten, when there is no level to
+    /// record.
+    pub fn record(&mut self, world: &mut World) -> bool {
+        let mut player_query = world.query::<PlayerData>();
+        let mut cursor_query = world.query::<(&Transform, &Cursor)>();
+        let mut obstacle_query =
+            world.query_filtered::<ObstacleData, LargeObstacle>();
+        let mut debris_query =
+            world.query_filtered::<DebrisData, Without<Pooled>>();
+        let mut bullet_query =
+            world.query_filtered::<BulletData, Without<Pooled>>();
+        let mut enemy_query = world.query::<EnemyData>();
+        let mut boss_query = world.query::<BossData>();
+        let mut pickup_query = world.query::<(&Pickup, &Transform)>();
+        let mut part_query =
+            world.query_filtered::<(&BossPart, &Health), Without<Boss>>();
+
+        let cursors: Vec<(&Transform, &Cursor)> =
+            cursor_query.iter(world).collect();
+
+        self.players.clear();
+        for (
+            transform,
+            velocity,
+            collider,
+            health,
+            player,
+            invulnerable,
+            interpolated,
+        ) in player_query.iter(world)
+        {
+            let (cursor_transform, cursor) = match cursors
+                .iter()
+                .find(|(_, cursor)| cursor.player == player.id)
+            {
+                Some(cursor) => cursor,
+                None => return false,
+            };
+
+            self.players.push(PlayerState {
+                id: player.id,
+                score: player.score,
+                transform: simulated(transform, interpolated),
+                velocity: *velocity,
+                collider: *collider,
+                health: *health,
+                just_moved: player.just_moved,
+                invulnerable: invulnerable.map(TimerState::from),
+                cursor: (*cursor).into(),
+                cursor_transform: (*cursor_transform).into(),
+            });
+        }
+
+        if self.players.is_empty() {
+            return false;
+        }
+
+        self.obstacles.clear();
+        self.obstacles.extend(obstacle_query.iter(world).map(
+            |(sprite, transform, collider)| ObstacleState {
+                sprite: sprite.index,
+                transform: transform.into(),
+                collider: *collider,
+            },
+        ));
+
+        self.debris.clear();
+        self.debris.extend(debris_query.iter(world).map(
+            |(sprite, transform, collider, moving, interpolated)| DebrisState {
+                sprite: sprite.index,
+                transform: simulated(transform, interpolated),
+                collider: *collider,
+                motion: moving.into(),
+            },
+        ));
+
+        self.bullets.clear();
+        self.bullets.extend(bullet_query.iter(world).map(
+            |(transform, collider, bullet, interpolated)| BulletState {
+                player: bullet.player,
+                transform: simulated(transform, interpolated),
+                collider: *collider,
+                damage: bullet.damage,
+                direction: bullet.direction,
+                duration: (&bullet.duration).into(),
+                previous_position: bullet.previous_position,
+            },
+        ));
+
+        self.enemies.clear();
+        self.enemies.extend(enemy_query.iter(world).map(
+            |(transform, health, steering, brain, enemy, interpolated)| {
+                EnemyState {
+                    sprite: enemy.sprite_index,
+                    transform: simulated(transform, interpolated),
+                    health: *health,
+                    steering: *steering,
+                    brain: *brain,
+                    gun: enemy.gun,
+                }
+            },
+        ));
+
+        let parts: Vec<(usize, Health)> = part_query
+            .iter(world)
+            .map(|(part, health)| (part.index, *health))
+            .collect();
+
+        self.boss = boss_query.iter(world).next().map(
+            |(boss, transform, health, interpolated)| BossState {
+                def: boss.def,
+                phase: boss.phase,
+                home: boss.home,
+                elapsed: boss.elapsed,
+                emissions: boss.emissions.clone(),
+                position: simulated(transform, interpolated)
+                    .translation
+                    .truncate(),
+                parts: std::iter::once((0, *health)).chain(parts).collect(),
+            },
+        );
+
+        self.pickups.clear();
+        self.pickups.extend(pickup_query.iter(world).map(
+            |(pickup, transform)| PickupState {
+                kind: pickup.kind,
+                age: pickup.age,
+                position: transform.translation.truncate(),
+            },
+        ));
+
+        let rng = world.resource::<GameRng>();
+
+        self.version = FORMAT_VERSION;
+        self.tick = world.resource::<SimulationTime>().tick();
+        self.seed = rng.seed();
+        self.word_pos = rng.word_pos();
+        self.mode = *world.resource::<GameMode>();
+        self.score = world.resource::<Score>().0;
+        self.wave = world.resource::<Wave>().0;
+        self.lives = world.resource::<Lives>().0;
+        self.campaign = world.resource::<Campaign>().progress.clone();
+
+        true
+    }
+
+    /// A hash of where ships, shots and obstacles stand and of the run's
+    /// progress, the same on every peer that simulated the same ticks,
+    /// whatever order their entities were spawned in.
+    pub fn checksum(&self) -> u64 {
+        /// Hashes each item on its own and adds them up, so their order
+        /// doesn't matter.
+        fn unordered<T>(items: &[T], hash: impl Fn(&T, &mut Fnv)) -> u64 {
+            items.iter().fold(0, |sum: u64, item| {
+                let mut hasher = Fnv::default();
+                hash(item, &mut hasher);
+                sum.wrapping_add(hasher.finish())
+            })
+        }
+
+        let mut hasher = Fnv::default();
+        hasher.write_u64(self.tick);
+        hasher.write_u128(self.word_pos);
+        hasher.write_u32(self.score);
+        hasher.write_u32(self.wave);
+        hasher.write_u32(self.lives);
+
+        hasher.write_u64(unordered(&self.players, |player, hasher| {
+            hasher.write_u64(player.id as u64);
+            hasher.write_u32(player.score);
+            hasher.write_vec3(player.transform.translation);
+            hasher.write_f32(player.health.current);
+        }));
+        hasher.write_u64(unordered(&self.obstacles, |obstacle, hasher| {
+            hasher.write_vec3(obstacle.transform.translation);
+        }));
+        hasher.write_u64(unordered(&self.debris, |debris, hasher| {
+            hasher.write_vec3(debris.transform.translation);
+        }));
+        hasher.write_u64(unordered(&self.bullets, |bullet, hasher| {
+            hasher.write_u64(bullet.player as u64);
+            hasher.write_vec3(bullet.transform.translation);
+        }));
+        hasher.write_u64(unordered(&self.enemies, |enemy, hasher| {
+            hasher.write_vec3(enemy.transform.translation);
+            hasher.write_f32(enemy.health.current);
+            hasher.write_u8(enemy.brain.state as u8);
+        }));
+        hasher.write_u64(unordered(&self.pickups, |pickup, hasher| {
+            hasher.write_f32(pickup.position.x);
+            hasher.write_f32(pickup.position.y);
+        }));
+
+        if let Some(boss) = &self.boss {
+            hasher.write_u64(boss.phase as u64);
+            hasher.write_f32(boss.position.x);
+            hasher.write_f32(boss.position.y);
+            hasher.write_u64(unordered(
+                &boss.parts,
+                |(index, health), hasher| {
+                    hasher.write_u64(*index as u64);
+                    hasher.write_f32(health.current);
+                },
+            ));
+        }
+
+        hasher.finish()
+    }
+
+    /// Puts the level back the way it was captured, replacing whatever
+    /// obstacles, enemies, bosses, pickups and bullets it currently holds.
+    /// The level must have been set up for the same `mode`.
+    pub fn restore(&self, world: &mut World) {
+        world.resource_mut::<SimulationTime>().set_tick(self.tick);
+        *world.resource_mut::<GameRng>() =
+            GameRng::from_position(self.seed, self.word_pos);
+        world.resource_mut::<Score>().0 = self.score;
+        world.resource_mut::<Wave>().0 = self.wave;
+        world.resource_mut::<Lives>().0 = self.lives;
+        world.resource_mut::<Campaign>().progress = self.campaign.clone();
+
+        let mut player_query = world.query::<(Entity, &Player)>();
+        let mut cursor_query = world.query::<(Entity, &Cursor)>();
+
+        for state in self.players.iter() {
+            let player = player_query
+                .iter(world)
+                .find(|(_, player)| player.id == state.id)
+                .map(|(e, _)| e);
+            let cursor = cursor_query
+                .iter(world)
+                .find(|(_, cursor)| cursor.player == state.id)
+                .map(|(e, _)| e);
+
+            if let Some(e) = player {
+                let mut player = world.entity_mut(e);
+
+                player
+                    .insert(Transform::from(&state.transform))
+                    .insert(state.velocity)
+                    .insert(state.collider)
+                    .insert(state.health)
+                    .insert(Player {
+                        id: state.id,
+                        just_moved: state.just_moved,
+                        score: state.score,
+                    })
+                    // Don't smooth the jump to the restored position.
+                    .insert(Interpolated::default());
+
+                match &state.invulnerable {
+                    Some(timer) => {
+                        player.insert(Invulnerable::from(timer));
+                    }
+                    None => {
+                        player.remove::<Invulnerable>();
+                    }
+                }
+            }
+
+            if let Some(e) = cursor {
+                world
+                    .entity_mut(e)
+                    .insert(Transform::from(&state.cursor_transform))
+                    .insert(Cursor::from(&state.cursor));
+            }
+        }
+
+        let obstacles: Vec<Entity> = world
+            .query_filtered::<Entity, LargeObstacle>()
+            .iter(world)
+            .collect();
+        let debris: Vec<Entity> = world
+            .query_filtered::<Entity, (With<MovingObstacle>, Without<Pooled>)>()
+            .iter(world)
+            .collect();
+        let bullets: Vec<Entity> = world
+            .query_filtered::<Entity, (With<Bullet>, Without<Pooled>)>()
+            .iter(world)
+            .collect();
+        let enemies: Vec<Entity> = world
+            .query_filtered::<Entity, Replaced>()
+            .iter(world)
+            .collect();
+
+        world.resource_scope(|world, mut pools: Mut<Pools>| {
+            let mut queue = CommandQueue::default();
+            let mut commands = Commands::new(&mut queue, world);
+            let ts = world.resource::<TexturesSheets>();
+
+            for e in obstacles.into_iter().chain(enemies) {
+                commands.entity(e).despawn_recursive();
+            }
+
+            for e in debris {
+                pools.debris.release(&mut commands, e);
+            }
+
+            for e in bullets {
+                pools.bullets.release(&mut commands, e);
+            }
+
+            for obstacle in self.obstacles.iter() {
+                obstacles::spawn_obstacle(
+                    &mut commands,
+                    ts,
+                    obstacle.sprite,
+                    (&obstacle.transform).into(),
+                    obstacle.collider,
+                );
+            }
+
+            for debris in self.debris.iter() {
+                obstacles::spawn_debris(
+                    &mut commands,
+                    &mut pools,
+                    ts,
+                    debris.sprite,
+                    (&debris.transform).into(),
+                    (&debris.motion).into(),
+                    debris.collider,
+                );
+            }
+
+            for bullet in self.bullets.iter() {
+                projectiles::spawn_bullet(
+                    &mut commands,
+                    &mut pools,
+                    ts,
+                    (&bullet.transform).into(),
+                    bullet.into(),
+                    bullet.collider,
+                );
+            }
+
+            for enemy in self.enemies.iter() {
+                let transform: Transform = (&enemy.transform).into();
+                enemies::spawn_enemy(
+                    &mut commands,
+                    ts,
+                    Enemy {
+                        sprite_index: enemy.sprite,
+                        gun: enemy.gun,
+                    },
+                    transform,
+                    enemy.health,
+                    enemy.steering,
+                    enemy.brain,
+                );
+            }
+
+            for pickup in self.pickups.iter() {
+                pickups::spawn_pickup(
+                    &mut commands,
+                    ts,
+                    Pickup {
+                        kind: pickup.kind,
+                        age: pickup.age,
+                    },
+                    pickup.position,
+                );
+            }
+
+            if let Some(state) = &self.boss {
+                boss::restore_boss(
+                    &mut commands,
+                    ts,
+                    state.into(),
+                    state.position,
+                    &state.parts,
+                );
+            }
+
+            queue.apply(world);
+        });
+    }
+
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("space-shooter").join(SAVE_FILE))
+    }
+
+    pub fn exists() -> bool {
+        Self::path().is_some_and(|path| path.exists())
+    }
+
+    /// Reads the saved run, if any. An unreadable or outdated save is set
+    /// aside rather than left to fail again.
+    pub fn load() -> Option<Self> {
+        let path = Self::path()?;
+        let contents = fs::read_to_string(&path).ok()?;
+
+        match ron::from_str::<Self>(&contents) {
+            Ok(snapshot) if snapshot.version == FORMAT_VERSION => {
+                Some(snapshot)
+            }
+
+            Ok(snapshot) => {
+                warn!(
+                    "saved run in {} is version {}, expected {}",
+                    path.display(),
+                    snapshot.version,
+                    FORMAT_VERSION
+                );
+                Self::set_aside(&path);
+                None
+            }
+
+            Err(err) => {
+                warn!("corrupted saved run in {}: {}", path.display(), err);
+                Self::set_aside(&path);
+                None
+            }
+        }
+    }
+
+    fn set_aside(path: &PathBuf) {
+        let backup = path.with_extension("ron.bak");
+
+        if let Err(err) = fs::rename(path, &backup) {
+            warn!("failed to back up {}: {}", path.display(), err);
+        }
+    }
+
+    pub fn save(&self) {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let contents =
+            match ron::ser::to_string_pretty(self, Default::default()) {
+                Ok(contents) => contents,
+                Err(err) => {
+                    warn!("failed to serialize saved run: {}", err);
+                    return;
+                }
+            };
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, contents));
+
+        if let Err(err) = written {
+            warn!("failed to save run to {}: {}", path.display(), err);
+        }
+    }
+
+    /// Deletes the saved run, so it can only be continued once.
+    pub fn remove() {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("failed to remove {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// A saved run waiting for the level to be set up before it's restored.
+#[derive(Default)]
+pub struct PendingRestore(pub Option<Snapshot>);
+
+/// Saves a run left before it's over, and restores it on "Continue".
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingRestore>()
+            .add_system_set(
+                SystemSet::on_exit(GameState::Level)
+                    .with_system(Self::save_run.exclusive_system()),
+            )
+            .add_system_to_stage(
+                FixedUpdate,
+                Self::restore_run
+                    .exclusive_system()
+                    .before(SimulationSystem::Run),
+            )
+            .add_system_to_stage(
+                CoreStage::Last,
+                Self::save_on_exit.exclusive_system(),
+            );
+    }
+}
+
+impl SnapshotPlugin {
+    /// Runs before anything of the level is torn down. A run that ended
+    /// with the last life or the last stage has nothing left to save, an
+    /// online one can't be picked back up alone, and neither a versus round
+    /// nor a stage being tried out is worth resuming.
+    fn save_run(world: &mut World) {
+        let resumable = matches!(
+            world.resource::<GameMode>(),
+            GameMode::Solo | GameMode::Coop
+        ) && !world.resource::<Campaign>().is_trial();
+        let over = world.resource::<Lives>().0 == 0
+            || world.resource::<Campaign>().is_complete();
+
+        if over || !resumable {
+            return;
+        }
+
+        if let Some(snapshot) = Snapshot::capture(world) {
+            snapshot.save();
+        }
+    }
+
+    /// Closing the window in the middle of a run saves it too.
+    fn save_on_exit(world: &mut World) {
+        if world.resource::<Events<AppExit>>().is_empty() {
+            return;
+        }
+
+        let state = world.resource::<State<GameState>>();
+        let in_level = *state.current() == GameState::Level
+            || state.inactives().contains(&GameState::Level);
+
+        if in_level {
+            Self::save_run(world);
+        }
+    }
+
+    fn restore_run(world: &mut World) {
+        if *world.resource::<State<GameState>>().current() != GameState::Level {
+            return;
+        }
+
+        if let Some(snapshot) = world.resource_mut::<PendingRestore>().0.take()
+        {
+            snapshot.restore(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::AiState;
+
+    /// A level with a single ship and an enemy chasing it.
+    fn level() -> (World, Entity) {
+        let mut world = World::new();
+        world.insert_resource(SimulationTime::default());
+        world.insert_resource(GameRng::default());
+        world.insert_resource(GameMode::Solo);
+        world.insert_resource(Score::default());
+        world.insert_resource(Wave::default());
+        world.insert_resource(Lives::default());
+        world.insert_resource(Campaign::empty());
+        world.insert_resource(Pools::default());
+        world.insert_resource(TexturesSheets {
+            entities: Handle::default(),
+            obstacles: Handle::default(),
+            obstacles2: Handle::default(),
+            projectiles: Handle::default(),
+            ui: Handle::default(),
+        });
+
+        world
+            .spawn()
+            .insert(Transform::from_xyz(100., 100., 1.))
+            .insert(Velocity::default())
+            .insert(Collider::default())
+            .insert(Health::new(100.))
+            .insert(Player::default());
+        world
+            .spawn()
+            .insert(Transform::default())
+            .insert(Cursor::default());
+
+        let mut brain = Brain::new(enemies::TEMPERAMENT, Vec2::new(300., 50.));
+        brain.state = AiState::Chase;
+        brain.target = Some(Vec2::new(100., 100.));
+        let mut steering = Steering::new(140., 220.);
+        steering.velocity = Vec2::new(-20., 10.);
+        steering.wander_angle = 1.5;
+
+        let mut queue = CommandQueue::default();
+        let enemy = enemies::spawn_enemy(
+            &mut Commands::new(&mut queue, &world),
+            &world.resource::<TexturesSheets>().clone(),
+            Enemy {
+                sprite_index: 4,
+                gun: Emission::default(),
+            },
+            Transform::from_xyz(250., 80., 1.),
+            Health::new(40.),
+            steering,
+            brain,
+        );
+        queue.apply(&mut world);
+
+        (world, enemy)
+    }
+
+    fn encode(snapshot: &Snapshot) -> String {
+        ron::to_string(snapshot).unwrap()
+    }
+
+    #[test]
+    fn restore_puts_back_what_capture_recorded() {
+        let (mut world, enemy) = level();
+        let captured = Snapshot::capture(&mut world).unwrap();
+
+        // Play on a little, the enemy losing track of the ship.
+        world.resource_mut::<Score>().0 = 500;
+        world
+            .entity_mut(enemy)
+            .insert(Brain::new(enemies::TEMPERAMENT, Vec2::new(0., 0.)));
+        world.get_mut::<Transform>(enemy).unwrap().translation.x += 30.;
+
+        captured.restore(&mut world);
+        let restored = Snapshot::capture(&mut world).unwrap();
+
+        assert_eq!(encode(&restored), encode(&captured));
+    }
+
+    #[test]
+    fn checksum_ignores_spawn_order() {
+        let (mut world, _) = level();
+        let mut snapshot = Snapshot::capture(&mut world).unwrap();
+
+        let mut other = snapshot.enemies[0].clone();
+        other.transform.translation.x = 600.;
+        snapshot.enemies.push(other);
+
+        let mut reordered = snapshot.clone();
+        reordered.enemies.reverse();
+        assert_eq!(reordered.checksum(), snapshot.checksum());
+
+        // Nor does the mode, as each peer plays a different seat.
+        reordered.mode = GameMode::Online { local: 1 };
+        assert_eq!(reordered.checksum(), snapshot.checksum());
+
+        reordered.enemies[0].transform.translation.x += 1.;
+        assert_ne!(reordered.checksum(), snapshot.checksum());
+    }
+
+    #[test]
+    fn enemy_brains_survive_a_restore() {
+        let (mut world, enemy) = level();
+        let captured = Snapshot::capture(&mut world).unwrap();
+
+        world.despawn(enemy);
+        captured.restore(&mut world);
+
+        let mut brain_query = world.query::<&Brain>();
+        let brain = brain_query.single(&world);
+        assert_eq!(brain.state, AiState::Chase);
+        assert_eq!(brain.target, Some(Vec2::new(100., 100.)));
+        assert_eq!(brain.home, Vec2::new(300., 50.));
+    }
+}
@@ -0,0 +1,473 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boss::{self, Boss},
+    common::{Collider, Playfield},
+    enemies::{self, Enemy},
+    events::StageStarted,
+    graphics,
+    obstacles::{self, MovingObstacle, Obstacle, ObstaclePlugin, Spawner},
+    pickups::{self, Pickup, PickupKind},
+    player::Player,
+    pool::Pooled,
+    run::{RunSystem, Wave},
+    simulation::{SimulationApp, SimulationTime},
+    state::GameState,
+};
+
+/// Stages of the campaign, played in this order.
+const CAMPAIGN: &[&str] = &[
+    "stages/outskirts.stage.ron",
+    "stages/minefield.stage.ron",
+    "stages/dreadnought.stage.ron",
+];
+
+/// A hand-made level: what happens when, and what it takes to clear it.
+#[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6f1c2b9e-4d1a-4a8e-9a57-3c2f0e1b7d42"]
+pub struct Stage {
+    pub name: String,
+    /// Ordered by time once loaded.
+    pub timeline: Vec<Cue>,
+    pub victory: Victory,
+}
+
+/// Something happening `at` seconds into a stage.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cue {
+    pub at: f32,
+    pub event: StageEvent,
+}
+
+/// Positions are fractions of the playfield, `(0, 0)` being its bottom-left
+/// corner and `(1, 1)` its top-right one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum StageEvent {
+    /// One of the large obstacles, by sprite, with the size of its collider
+    /// when it differs from the sprite's usual one.
+    Obstacle {
+        sprite: usize,
+        position: Vec2,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        collider: Option<Vec2>,
+    },
+    /// One of the enemy ships, by sprite, patrolling around `position`.
+    Enemy {
+        ship: usize,
+        position: Vec2,
+    },
+    /// Enemies placed away from the players.
+    Squad(u32),
+    /// A boss by name, placed away from the players.
+    Boss(String),
+    Pickup {
+        kind: PickupKind,
+        position: Vec2,
+    },
+    /// Color of the background, in hex.
+    Background(String),
+}
+
+/// What clears a stage, once every event of its timeline happened.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Victory {
+    /// Nothing left to shoot.
+    Clear,
+    DefeatBoss,
+    /// Still flying that many seconds into the stage, whether or not the
+    /// timeline is over.
+    Survive(f32),
+}
+
+impl Stage {
+    pub fn sort_timeline(&mut self) {
+        self.timeline.sort_by(|a, b| a.at.total_cmp(&b.at));
+    }
+
+    /// Collider of a large obstacle placed by a stage, `None` if there's no
+    /// such obstacle.
+    pub fn obstacle_collider(
+        sprite: usize,
+        size: Option<Vec2>,
+    ) -> Option<Collider> {
+        let collider = ObstaclePlugin::for_large_obstacle(sprite)?;
+
+        Some(match size {
+            Some(size) => Collider {
+                width: size.x,
+                height: size.y,
+                ..collider
+            },
+            None => collider,
+        })
+    }
+}
+
+#[derive(Default)]
+struct StageLoader;
+
+impl AssetLoader for StageLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut stage: Stage = ron::de::from_bytes(bytes)?;
+            stage.sort_timeline();
+
+            load_context.set_default_asset(LoadedAsset::new(stage));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stage.ron"]
+    }
+}
+
+/// How far into the campaign a run is.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StageProgress {
+    /// Index in the campaign, its length once every stage is cleared.
+    pub stage: usize,
+    /// Seconds since the stage began.
+    pub elapsed: f32,
+    /// Index in the timeline of the next event to happen.
+    pub next_event: usize,
+    /// Set by the last `Background` event, if any.
+    pub background: Option<String>,
+}
+
+/// The stages of the campaign, and the progress of the run through them.
+/// Runs outside the campaign are made of endless random waves instead.
+pub struct Campaign {
+    stages: Vec<Handle<Stage>>,
+    /// Played alone instead of the campaign's stages, when trying one out.
+    trial: Option<Handle<Stage>>,
+    pub progress: Option<StageProgress>,
+}
+
+impl FromWorld for Campaign {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+
+        Self {
+            stages: CAMPAIGN
+                .iter()
+                .map(|path| asset_server.load(*path))
+                .collect(),
+            trial: None,
+            progress: None,
+        }
+    }
+}
+
+impl Campaign {
+    /// No stages at all, for tests that don't load assets.
+    #[cfg(test)]
+    pub fn empty() -> Self {
+        Self {
+            stages: Vec::new(),
+            trial: None,
+            progress: None,
+        }
+    }
+
+    /// Starts over from the first stage, of the campaign or of the stage
+    /// being tried out.
+    pub fn start(&mut self) {
+        self.progress = Some(StageProgress::default());
+    }
+
+    /// Back to endless waves.
+    pub fn stop(&mut self) {
+        self.trial = None;
+        self.progress = None;
+    }
+
+    /// Plays `stage` alone, as if it were the whole campaign.
+    #[cfg(debug_assertions)]
+    pub fn try_out(&mut self, stage: Handle<Stage>) {
+        self.trial = Some(stage);
+        self.start();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    pub fn is_trial(&self) -> bool {
+        self.trial.is_some()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.progress
+            .as_ref()
+            .is_some_and(|progress| progress.stage >= self.stages().len())
+    }
+
+    /// Number of the stage being played, from 1, staying on the last one
+    /// once it's cleared.
+    pub fn stage_number(&self) -> Option<usize> {
+        let progress = self.progress.as_ref()?;
+        Some((progress.stage + 1).min(self.stages().len()))
+    }
+
+    /// The stage being played, once it's loaded.
+    pub fn stage<'a>(&self, stages: &'a Assets<Stage>) -> Option<&'a Stage> {
+        let progress = self.progress.as_ref()?;
+        stages.get(self.stages().get(progress.stage)?)
+    }
+
+    fn stages(&self) -> &[Handle<Stage>] {
+        match &self.trial {
+            Some(trial) => std::slice::from_ref(trial),
+            None => &self.stages,
+        }
+    }
+}
+
+/// Where a run stands: its wave, or its stage in the campaign.
+#[derive(SystemParam)]
+pub struct Progress<'w, 's> {
+    pub wave: Res<'w, Wave>,
+    pub campaign: Res<'w, Campaign>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl Progress<'_, '_> {
+    pub fn label(&self) -> String {
+        match self.campaign.stage_number() {
+            Some(stage) => format!("STAGE {}", stage),
+            None => format!("WAVE {}", self.wave.0),
+        }
+    }
+}
+
+/// Anything a `Clear` victory waits for to be destroyed.
+type Remaining = Or<(
+    With<Enemy>,
+    With<Boss>,
+    (With<Obstacle>, Without<MovingObstacle>, Without<Pooled>),
+)>;
+
+/// Plays the stages of the campaign one after the other.
+pub struct StagePlugin;
+
+impl Plugin for StagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Stage>()
+            .init_asset_loader::<StageLoader>()
+            .init_resource::<Campaign>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Level)
+                    .with_system(Self::restart.after(RunSystem::Reset)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(Self::paint_background),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Level)
+                    .with_system(Self::reset_background),
+            )
+            .add_simulation_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(Self::run_stage)
+                    .with_system(Self::clear_stage.after(Self::run_stage)),
+            );
+    }
+}
+
+impl StagePlugin {
+    /// A campaign run always starts over from the first stage, unless a
+    /// saved one is about to be restored over it.
+    fn restart(mut campaign: ResMut<Campaign>) {
+        if campaign.is_active() {
+            campaign.start();
+        }
+    }
+
+    /// Moves the stage's clock forward and sets off whatever is due. Waits
+    /// for the stage to be loaded first.
+    fn run_stage(
+        mut spawner: Spawner,
+        mut campaign: ResMut<Campaign>,
+        stages: Res<Assets<Stage>>,
+        time: Res<SimulationTime>,
+        playfield: Res<Playfield>,
+        player_query: Query<&Transform, With<Player>>,
+        mut started_evw: EventWriter<StageStarted>,
+    ) {
+        let stage = match campaign.stage(&stages) {
+            Some(stage) => stage,
+            None => return,
+        };
+        let progress = campaign.progress.as_mut().unwrap();
+
+        if progress.elapsed == 0. && progress.next_event == 0 {
+            started_evw.send(StageStarted {
+                stage: progress.stage,
+                name: stage.name.clone(),
+            });
+        }
+
+        progress.elapsed += time.delta_seconds();
+
+        let avoid: Vec<Vec2> = player_query
+            .iter()
+            .map(|transform| transform.translation.truncate())
+            .collect();
+
+        while let Some(cue) = stage.timeline.get(progress.next_event) {
+            if cue.at > progress.elapsed {
+                break;
+            }
+
+            progress.next_event += 1;
+            Self::happen(
+                &cue.event,
+                &mut spawner,
+                &playfield,
+                &avoid,
+                progress,
+            );
+        }
+    }
+
+    fn happen(
+        event: &StageEvent,
+        spawner: &mut Spawner,
+        playfield: &Playfield,
+        avoid: &[Vec2],
+        progress: &mut StageProgress,
+    ) {
+        let Spawner {
+            commands, rng, ts, ..
+        } = spawner;
+        let at = |position: &Vec2| *position * playfield.size();
+
+        match event {
+            StageEvent::Obstacle {
+                sprite,
+                position,
+                collider,
+            } => {
+                let collider =
+                    match Stage::obstacle_collider(*sprite, *collider) {
+                        Some(collider) => collider,
+                        None => {
+                            warn!(
+                                "stage calls for unknown obstacle {}",
+                                sprite
+                            );
+                            return;
+                        }
+                    };
+
+                obstacles::spawn_obstacle(
+                    commands,
+                    ts,
+                    *sprite,
+                    Transform::from_translation(at(position).extend(1.)),
+                    collider,
+                );
+            }
+
+            StageEvent::Enemy { ship, position } => {
+                enemies::spawn_fresh(commands, ts, *ship, at(position));
+            }
+
+            StageEvent::Squad(size) => {
+                enemies::spawn_squad(
+                    commands, rng, playfield, ts, *size, avoid,
+                );
+            }
+
+            StageEvent::Boss(name) => match boss::by_name(name) {
+                Some(def) => {
+                    let position = boss::spawn_point(def, playfield, avoid);
+                    boss::spawn_boss(commands, ts, def, position);
+                }
+                None => warn!("stage calls for unknown boss {}", name),
+            },
+
+            StageEvent::Pickup { kind, position } => {
+                pickups::spawn_pickup(
+                    commands,
+                    ts,
+                    Pickup {
+                        kind: *kind,
+                        age: 0.,
+                    },
+                    at(position),
+                );
+            }
+
+            StageEvent::Background(color) => {
+                progress.background = Some(color.clone());
+            }
+        }
+    }
+
+    /// Moves on to the next stage once the current one's victory condition
+    /// is met.
+    fn clear_stage(
+        mut campaign: ResMut<Campaign>,
+        stages: Res<Assets<Stage>>,
+        remaining_query: Query<(), Remaining>,
+        boss_query: Query<(), With<Boss>>,
+    ) {
+        let stage = match campaign.stage(&stages) {
+            Some(stage) => stage,
+            None => return,
+        };
+        let progress = campaign.progress.as_ref().unwrap();
+        let timeline_over = progress.next_event >= stage.timeline.len();
+
+        let cleared = match stage.victory {
+            Victory::Survive(seconds) => progress.elapsed >= seconds,
+            _ if !timeline_over => false,
+            Victory::Clear => remaining_query.is_empty(),
+            Victory::DefeatBoss => boss_query.is_empty(),
+        };
+
+        if cleared {
+            campaign.progress = Some(StageProgress {
+                stage: progress.stage + 1,
+                background: progress.background.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    fn paint_background(
+        campaign: Res<Campaign>,
+        mut clear_color: ResMut<ClearColor>,
+    ) {
+        let color = campaign
+            .progress
+            .as_ref()
+            .and_then(|progress| progress.background.as_deref())
+            .and_then(|hex| Color::hex(hex).ok())
+            .unwrap_or_else(|| Color::hex(graphics::BG_COLOR).unwrap());
+
+        if clear_color.0 != color {
+            clear_color.0 = color;
+        }
+    }
+
+    fn reset_background(mut clear_color: ResMut<ClearColor>) {
+        clear_color.0 = Color::hex(graphics::BG_COLOR).unwrap();
+    }
+}
@@ -1,4 +1,25 @@
+use bevy::prelude::*;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum GameState {
+    MainMenu,
+    HighScores,
     Level,
+    Settings,
+    GameOver,
+    /// Laying out a stage, in debug builds.
+    Editor,
+}
+
+/// Despawned, along with its children, when the level is left for good.
+#[derive(Component, Default)]
+pub struct LevelScoped;
+
+pub fn despawn_with<T: Component>(
+    mut commands: Commands,
+    query: Query<Entity, With<T>>,
+) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
 }